@@ -5,16 +5,29 @@
 // Uses Manhattan distance with expansion offsets.
 
 // Parse the input string into a 2D grid representation.
-// Each line of the input becomes a vector of characters.
-fn parse_input(input: &str) -> Vec<Vec<char>> {
-    input.lines().map(|line| line.chars().collect()).collect()
+// Each non-blank line of the input becomes a vector of characters, and all of them must
+// have the same width. An empty input gives an empty grid.
+fn parse_input(input: &str) -> Result<Vec<Vec<char>>, String> {
+    let grid: Vec<Vec<char>> = input
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| line.chars().collect())
+        .collect();
+
+    let width = grid.first().map_or(0, Vec::len);
+    if let Some(row) = grid.iter().position(|row| row.len() != width) {
+        return Err(format!("row {row} has width {}, expected {width}", grid[row].len()));
+    }
+
+    Ok(grid)
 }
 
 // Count the number of empty rows and columns before each index in the grid.
 // This information is used to adjust the galaxy coordinates for cosmic expansion.
-fn count_empty_rows_cols(grid: &Vec<Vec<char>>) -> (Vec<usize>, Vec<usize>) {
+fn count_empty_rows_cols(grid: &[Vec<char>]) -> (Vec<usize>, Vec<usize>) {
     let rows = grid.len();
-    let cols = grid[0].len();
+    let cols = grid.first().map_or(0, Vec::len);
 
     let mut empty_rows = vec![0; rows];
     let mut empty_cols = vec![0; cols];
@@ -28,84 +41,181 @@ fn count_empty_rows_cols(grid: &Vec<Vec<char>>) -> (Vec<usize>, Vec<usize>) {
     // Count empty columns
     for j in 1..cols {
         empty_cols[j] = empty_cols[j - 1]
-            + if grid.iter().all(|row| row[j] == '.') { 1 } else { 0 };
+            + if grid.iter().all(|row| row[j - 1] == '.') { 1 } else { 0 };
     }
 
     (empty_rows, empty_cols)
 }
 
 // Calculate the total Manhattan distance between all unique galaxy pairs.
-// The Manhattan distance is the sum of the absolute differences in their respective coordinates.
+// The Manhattan distance is the sum of the absolute differences in their respective coordinates,
+// so each axis can be summed on its own.
 fn calculate_total_distance(galaxies: Vec<(usize, usize)>) -> usize {
-    let mut total_distance = 0;
-    for i in 0..galaxies.len() {
-        for j in i + 1..galaxies.len() {
-            let (x1, y1) = galaxies[i];
-            let (x2, y2) = galaxies[j];
-            total_distance += (x1.max(x2) - x1.min(x2)) + (y1.max(y2) - y1.min(y2));
-        }
+    let (mut xs, mut ys): (Vec<usize>, Vec<usize>) = galaxies.into_iter().unzip();
+    axis_distance(&mut xs) + axis_distance(&mut ys)
+}
+
+// Sum the differences between every pair of coordinates on one axis.
+// Once sorted, the k-th coordinate is at least each of the k before it, so it adds
+// k times itself minus the sum of those (a running prefix sum).
+fn axis_distance(coordinates: &mut [usize]) -> usize {
+    coordinates.sort_unstable();
+    let mut prefix_sum = 0;
+    let mut total = 0;
+    for (k, &coordinate) in coordinates.iter().enumerate() {
+        total += k * coordinate - prefix_sum;
+        prefix_sum += coordinate;
     }
-    total_distance
+    total
 }
 
-mod part1_impl {
-    // Adjust the coordinates of each galaxy based on the cosmic expansion.
-    // The coordinates are increased by the count of empty rows and columns before them.
-    pub fn adjust_galaxy_coordinates(
-        grid: &[Vec<char>],
-        empty_rows: &[usize],
-        empty_cols: &[usize],
-    ) -> Vec<(usize, usize)> {
-        grid.iter()
-            .enumerate()
-            .flat_map(|(i, row)| {
-                row.iter().enumerate().filter_map(move |(j, &c)| {
-                    if c == '#' {
-                        Some((i + empty_rows[i], j + empty_cols[j]))
-                    } else {
-                        None
-                    }
-                })
+// Adjust the coordinates of each galaxy based on the cosmic expansion.
+// Every empty row and column before a galaxy counts as `expansion` rows or columns,
+// so the coordinates grow by `expansion - 1` for each of them.
+fn adjust_galaxy_coordinates(
+    grid: &[Vec<char>],
+    empty_rows: &[usize],
+    empty_cols: &[usize],
+    expansion: usize,
+) -> Vec<(usize, usize)> {
+    let growth = expansion - 1;
+    grid.iter()
+        .enumerate()
+        .flat_map(|(i, row)| {
+            row.iter().enumerate().filter_map(move |(j, &c)| {
+                if c == '#' {
+                    Some((i + empty_rows[i] * growth, j + empty_cols[j] * growth))
+                } else {
+                    None
+                }
             })
-            .collect()
-    }
+        })
+        .collect()
 }
 
-#[aoc(day11, part1)]
-pub fn part1(input: &str) -> usize {
-    let grid = parse_input(input);
+/// The `(row, column)` of every galaxy after each empty row and column has been replaced by
+/// `expansion` of them (at least 1, which leaves the image as is), in reading order.
+///
+/// Fails if the expansion is 0 or the rows of the image differ in width.
+pub fn galaxies(input: &str, expansion: usize) -> Result<Vec<(usize, usize)>, String> {
+    if expansion == 0 {
+        return Err("expansion must be at least 1".to_string());
+    }
+    let grid = parse_input(input)?;
     let (empty_rows, empty_cols) = count_empty_rows_cols(&grid);
-    let galaxies = part1_impl::adjust_galaxy_coordinates(&grid, &empty_rows, &empty_cols);
-    calculate_total_distance(galaxies)
+    Ok(adjust_galaxy_coordinates(&grid, &empty_rows, &empty_cols, expansion))
 }
 
-mod part2_impl {
-    // Adjust the coordinates of each galaxy based on the cosmic expansion.
-    // The coordinates are increased by the count of empty rows and columns before them.
-    pub fn adjust_galaxy_coordinates(
-        grid: &[Vec<char>],
-        empty_rows: &[usize],
-        empty_cols: &[usize],
-    ) -> Vec<(usize, usize)> {
-        grid.iter()
-            .enumerate()
-            .flat_map(|(i, row)| {
-                row.iter().enumerate().filter_map(move |(j, &c)| {
-                    if c == '#' {
-                        Some((i + empty_rows[i] * 999_999, j + empty_cols[j] * 999_999))
-                    } else {
-                        None
-                    }
-                })
-            })
-            .collect()
-    }
+/// The length of the shortest path between two galaxies, moving only up, down, left or right.
+pub fn pair_distance(a: (usize, usize), b: (usize, usize)) -> usize {
+    a.0.abs_diff(b.0) + a.1.abs_diff(b.1)
+}
+
+/// Sums the shortest distances between every pair of galaxies after the expansion,
+/// i.e. `pair_distance` over every pair of `galaxies`. An image with fewer than two
+/// galaxies, including an empty one, gives 0. Fails as `galaxies` does.
+pub fn total_distance(input: &str, expansion: usize) -> Result<usize, String> {
+    Ok(calculate_total_distance(galaxies(input, expansion)?))
+}
+
+#[aoc(day11, part1)]
+pub fn part1(input: &str) -> Result<usize, String> {
+    total_distance(input, 2)
 }
 
 #[aoc(day11, part2)]
-pub fn part2(input: &str) -> usize {
-    let grid = parse_input(input);
-    let (empty_rows, empty_cols) = count_empty_rows_cols(&grid);
-    let galaxies = part2_impl::adjust_galaxy_coordinates(&grid, &empty_rows, &empty_cols);
-    calculate_total_distance(galaxies)
+pub fn part2(input: &str) -> Result<usize, String> {
+    total_distance(input, 1_000_000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{random_grid, rng};
+
+    const SAMPLE: &str = "\
+...#......
+.......#..
+#.........
+..........
+......#...
+.#........
+.........#
+..........
+.......#..
+#...#.....
+";
+
+    #[test]
+    fn sample_answers() {
+        assert_eq!(part1(SAMPLE), Ok(374));
+        assert_eq!(part2(SAMPLE), Ok(82000210));
+    }
+
+    #[test]
+    fn expansion_factors() {
+        assert_eq!(total_distance(SAMPLE, 2), part1(SAMPLE));
+        assert_eq!(total_distance(SAMPLE, 10), Ok(1030));
+        assert_eq!(total_distance(SAMPLE, 100), Ok(8410));
+        assert_eq!(total_distance(SAMPLE, 1_000_000), part2(SAMPLE));
+        // An expansion of 1 leaves empty rows and columns as they are
+        assert_eq!(total_distance("#.#", 1), Ok(2));
+
+        assert_eq!(total_distance(SAMPLE, 0), Err("expansion must be at least 1".to_string()));
+        assert!(galaxies(SAMPLE, 0).is_err());
+    }
+
+    /// Sums the distance of every pair of galaxies one pair at a time.
+    fn quadratic_distance(galaxies: &[(usize, usize)]) -> usize {
+        let mut total = 0;
+        for (i, &a) in galaxies.iter().enumerate() {
+            for &b in &galaxies[i + 1..] {
+                total += a.0.abs_diff(b.0) + a.1.abs_diff(b.1);
+            }
+        }
+        total
+    }
+
+    #[test]
+    fn prefix_sums_match_every_pair() {
+        for expansion in [2, 10, 100, 1_000_000] {
+            let galaxies = galaxies(SAMPLE, expansion).unwrap();
+            assert_eq!(calculate_total_distance(galaxies.clone()), quadratic_distance(&galaxies));
+        }
+
+        let mut seed = 12345;
+        for _ in 0..200 {
+            let (height, width) = (1 + rng(&mut seed) as usize % 13, 1 + rng(&mut seed) as usize % 13);
+            let image = random_grid(&mut seed, height, width, b"#....");
+            let galaxies = galaxies(&image, 7).unwrap();
+            assert_eq!(calculate_total_distance(galaxies.clone()), quadratic_distance(&galaxies), "{image}");
+        }
+    }
+
+    #[test]
+    fn galaxies_and_pair_distances() {
+        let expanded = galaxies(SAMPLE, 10).unwrap();
+        assert_eq!(expanded.len(), 9);
+        let pairs: usize =
+            (0..9).flat_map(|i| (i + 1..9).map(move |j| (i, j))).map(|(i, j)| pair_distance(expanded[i], expanded[j])).sum();
+        assert_eq!(Ok(pairs), total_distance(SAMPLE, 10));
+        assert_eq!(pairs, 1030);
+
+        // Galaxies 5 and 9 in the puzzle's numbering are 9 apart once expanded
+        let expanded = galaxies(SAMPLE, 2).unwrap();
+        assert_eq!(expanded[0], (0, 4));
+        assert_eq!(pair_distance(expanded[4], expanded[8]), 9);
+        assert_eq!(galaxies(SAMPLE, 1).unwrap()[0], (0, 3));
+    }
+
+    #[test]
+    fn empty_single_galaxy_and_ragged_images() {
+        assert_eq!(total_distance("", 2), Ok(0));
+        assert_eq!(part1("\n\n"), Ok(0));
+        assert_eq!(part1("..\n.#\n"), Ok(0));
+        assert_eq!(galaxies("...\n.#.\n...", 5), Ok(vec![(5, 5)]));
+
+        assert_eq!(part1("..#\n.#\n"), Err("row 1 has width 2, expected 3".to_string()));
+    }
 }
+
@@ -3,165 +3,254 @@
 // Count valid arrangements of operational (.) and damaged (#) springs.
 // Part 1: Single row arrangements matching damage group criteria
 // Part 2: Unfold rows 5x (requires dynamic programming with memoization)
+//
+// Both parts share one rolling-array DP over positions, one pass per group.
 
 use rayon::prelude::*;
 
-mod part1_impl {
-    // Counts the number of valid arrangements for a single row.
-    // `spring_layout` is the string representation of the springs with operational ('.'), damaged ('#'), or unknown ('?') status.
-    // `group_sizes` is an iterator over the sizes of groups of damaged springs.
-    pub fn count_arrangements(spring_layout: &str, group_sizes: impl Iterator<Item = usize>) -> u64 {
-        // Collect group sizes into a vector
-        let group_sizes = group_sizes.collect::<Vec<_>>();
-        // Prepend a '.' to handle edge cases and trim trailing operational springs
-        let spring_layout = format!(".{}", spring_layout.trim_end_matches('.'));
-        // Convert the string into a character vector for easier iteration
-        let spring_layout = spring_layout.chars().collect::<Vec<_>>();
-
-        // Dynamic programming table to store the number of ways to arrange springs up to a certain point
-        let mut dp = vec![0; spring_layout.len() + 1];
-        // Base case: there's one way to arrange an empty set of springs
-        dp[0] = 1;
-
-        // Initialize the dp table for the first sequence of operational springs
-        for (i, _) in spring_layout.iter().take_while(|&&c| c != '#').enumerate() {
-            dp[i + 1] = 1;
-        }
+// Counts the number of valid arrangements for a single row.
+// `spring_layout` is the string representation of the springs with operational ('.'), damaged ('#'), or unknown ('?') status.
+// `group_sizes` lists the sizes of the groups of damaged springs, in order.
+pub fn arrangements(spring_layout: &str, group_sizes: &[usize]) -> u128 {
+    // Prepend a '.' to handle edge cases and trim trailing operational springs
+    let spring_layout = format!(".{}", spring_layout.trim_end_matches('.'));
+    // Convert the string into a character vector for easier iteration
+    let spring_layout = spring_layout.chars().collect::<Vec<_>>();
+
+    // Dynamic programming table to store the number of ways to arrange springs up to a certain point
+    let mut dp = vec![0; spring_layout.len() + 1];
+    // Base case: there's one way to arrange an empty set of springs
+    dp[0] = 1;
+
+    // Initialize the dp table for the first sequence of operational springs
+    for (i, _) in spring_layout.iter().take_while(|&&c| c != '#').enumerate() {
+        dp[i + 1] = 1;
+    }
+
+    // Process each group of damaged springs
+    for &count in group_sizes {
+        // Temporary dp table for the current group of damaged springs
+        let mut new_dp = vec![0; spring_layout.len() + 1];
+        // Counter to track the length of the current sequence of damaged springs
+        let mut current_sequence_length = 0;
+
+        for (i, &c) in spring_layout.iter().enumerate() {
+            if c != '.' {
+                // Increase the sequence length for a damaged spring
+                current_sequence_length += 1;
+            } else {
+                // Reset the sequence length for an operational spring
+                current_sequence_length = 0;
+            }
 
-        // Process each group of damaged springs
-        for count in group_sizes {
-            // Temporary dp table for the current group of damaged springs
-            let mut new_dp = vec![0; spring_layout.len() + 1];
-            // Counter to track the length of the current sequence of damaged springs
-            let mut current_sequence_length = 0;
-
-            for (i, &c) in spring_layout.iter().enumerate() {
-                if c != '.' {
-                    // Increase the sequence length for a damaged spring
-                    current_sequence_length += 1;
-                } else {
-                    // Reset the sequence length for an operational spring
-                    current_sequence_length = 0;
-                }
-
-                // Carry over the number of ways from the previous spring if it's operational
-                if c != '#' {
-                    new_dp[i + 1] += new_dp[i];
-                }
-
-                // If the current sequence length matches the required count,
-                // add the number of ways from the dp table before this sequence started
-                if current_sequence_length >= count && i >= count && spring_layout[i - count] != '#' {
-                    new_dp[i + 1] += dp[i - count];
-                }
+            // Carry over the number of ways from the previous spring if it's operational
+            if c != '#' {
+                new_dp[i + 1] += new_dp[i];
             }
 
-            // Update the main dp table with the values calculated for the current group
-            dp = new_dp;
+            // If the current sequence length matches the required count,
+            // add the number of ways from the dp table before this sequence started
+            if current_sequence_length >= count && i >= count && spring_layout[i - count] != '#' {
+                new_dp[i + 1] += dp[i - count];
+            }
         }
 
-        // The last value in the dp table is the total number of valid arrangements for the row
-        *dp.last().unwrap() as u64
+        // Update the main dp table with the values calculated for the current group
+        dp = new_dp;
+    }
+
+    // The last value in the dp table is the total number of valid arrangements for the row
+    *dp.last().unwrap()
+}
+
+// Counts the same arrangements as `arrangements` by recursing over (position, group index)
+// and memoizing each pair, which is slower but follows the problem statement more directly.
+pub fn count_arrangements_memoized(spring_layout: &str, group_sizes: &[usize]) -> u128 {
+    let spring_layout = spring_layout.as_bytes();
+    let mut memo = vec![None; (spring_layout.len() + 1) * (group_sizes.len() + 1)];
+    count_from(spring_layout, group_sizes, 0, 0, &mut memo)
+}
+
+// Counts the ways to place groups `group..` into the springs from `position` onwards.
+fn count_from(spring_layout: &[u8], group_sizes: &[usize], position: usize, group: usize, memo: &mut [Option<u128>]) -> u128 {
+    if position >= spring_layout.len() {
+        return (group == group_sizes.len()) as u128;
+    }
+
+    let key = position * (group_sizes.len() + 1) + group;
+    if let Some(ways) = memo[key] {
+        return ways;
+    }
+
+    let mut ways = 0;
+
+    // Treat this spring as operational
+    if spring_layout[position] != b'#' {
+        ways += count_from(spring_layout, group_sizes, position + 1, group, memo);
     }
+
+    // Start the next damaged group here: it must fit, contain no operational springs,
+    // and be followed by an operational spring or the end of the row
+    if spring_layout[position] != b'.' && group < group_sizes.len() {
+        let end = position + group_sizes[group];
+        if end <= spring_layout.len()
+            && !spring_layout[position..end].contains(&b'.')
+            && spring_layout.get(end) != Some(&b'#')
+        {
+            ways += count_from(spring_layout, group_sizes, end + 1, group + 1, memo);
+        }
+    }
+
+    memo[key] = Some(ways);
+    ways
+}
+
+/// Parses a row like `???.### 1,1,3` into the spring layout and the group sizes, which must be
+/// positive.
+fn parse_row(line: &str) -> Result<(&str, Vec<usize>), String> {
+    let (spring_layout, group_sizes) = line
+        .trim()
+        .split_once(' ')
+        .ok_or_else(|| format!("missing group sizes in row: {line}"))?;
+    let group_sizes = group_sizes
+        .split(',')
+        .map(|num| {
+            num.trim()
+                .parse::<usize>()
+                .ok()
+                .filter(|&size| size > 0)
+                .ok_or_else(|| format!("invalid group size {num:?} in row: {line}"))
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok((spring_layout, group_sizes))
 }
 
 #[aoc(day12, part1)]
-pub fn part1(input: &str) -> u64 {
+pub fn part1(input: &str) -> Result<u64, String> {
     input
         .par_lines()
+        .filter(|line| !line.trim().is_empty())
         .map(|line| {
-            let (spring_layout, group_sizes) = line.split_once(' ').unwrap();
-            let group_sizes = group_sizes
-                .split(',')
-                .map(|num| num.parse::<usize>().unwrap());
-            part1_impl::count_arrangements(spring_layout, group_sizes)
+            let (spring_layout, group_sizes) = parse_row(line)?;
+            Ok(arrangements(spring_layout, &group_sizes) as u64)
         })
         .sum()
 }
 
-mod part2_impl {
-    // Counts the number of valid arrangements for a single row.
-    // `spring_layout` is the string representation of the springs with operational ('.'), damaged ('#'), or unknown ('?') status.
-    // `group_sizes` is an iterator over the sizes of groups of damaged springs.
-    pub fn count_arrangements(spring_layout: &str, group_sizes: impl Iterator<Item = usize>) -> u128 {
-        // Collect group sizes into a vector
-        let group_sizes = group_sizes.collect::<Vec<_>>();
-        // Prepend a '.' to handle edge cases and trim trailing operational springs
-        let spring_layout = format!(".{}", spring_layout.trim_end_matches('.'));
-        // Convert the string into a character vector for easier iteration
-        let spring_layout = spring_layout.chars().collect::<Vec<_>>();
-
-        // Dynamic programming table to store the number of ways to arrange springs up to a certain point
-        let mut dp = vec![0; spring_layout.len() + 1];
-        // Base case: there's one way to arrange an empty set of springs
-        dp[0] = 1;
-
-        // Initialize the dp table for the first sequence of operational springs
-        for (i, _) in spring_layout.iter().take_while(|&&c| c != '#').enumerate() {
-            dp[i + 1] = 1;
-        }
+#[aoc(day12, part2)]
+pub fn part2(input: &str) -> Result<u128, String> {
+    part2_factor(input, 5)
+}
 
-        // Process each group of damaged springs
-        for count in group_sizes {
-            // Temporary dp table for the current group of damaged springs
-            let mut new_dp = vec![0; spring_layout.len() + 1];
-            // Counter to track the length of the current sequence of damaged springs
-            let mut current_sequence_length = 0;
-
-            for (i, &c) in spring_layout.iter().enumerate() {
-                if c != '.' {
-                    // Increase the sequence length for a damaged spring
-                    current_sequence_length += 1;
-                } else {
-                    // Reset the sequence length for an operational spring
-                    current_sequence_length = 0;
-                }
-
-                // Carry over the number of ways from the previous spring if it's operational
-                if c != '#' {
-                    new_dp[i + 1] += new_dp[i];
-                }
-
-                // If the current sequence length matches the required count,
-                // add the number of ways from the dp table before this sequence started
-                if current_sequence_length >= count && i >= count && spring_layout[i - count] != '#' {
-                    new_dp[i + 1] += dp[i - count];
-                }
+/// Sums the arrangements after unfolding each row `factor` times: the springs are repeated
+/// with a '?' between copies and the group sizes are repeated. A factor of 1 matches part 1.
+pub fn part2_factor(input: &str, factor: usize) -> Result<u128, String> {
+    input
+        .par_lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (spring_layout, group_sizes) = parse_row(line)?;
+
+            // Build the expanded string
+            let extended_spring_layout = vec![spring_layout; factor].join("?");
+            let extended_group_sizes = group_sizes.repeat(factor);
+            Ok(arrangements(&extended_spring_layout, &extended_group_sizes))
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::rng;
+
+    const SAMPLE: &str = "\
+???.### 1,1,3
+.??..??...?##. 1,1,3
+?#?#?#?#?#?#?#? 1,3,1,6
+????.#...#... 4,1,1
+????.######..#####. 1,6,5
+?###???????? 3,2,1
+";
+
+    /// Random short rows with up to 4 groups of 1 to 3 damaged springs.
+    fn random_rows(mut seed: u64, count: usize) -> Vec<(String, Vec<usize>)> {
+        (0..count)
+            .map(|_| {
+                let len = 1 + rng(&mut seed) as usize % 14;
+                let layout = (0..len).map(|_| b".#?"[rng(&mut seed) as usize % 3] as char).collect();
+                let groups = (0..=rng(&mut seed) % 4).map(|_| 1 + rng(&mut seed) as usize % 3).collect();
+                (layout, groups)
+            })
+            .collect()
+    }
+
+    /// Counts arrangements by trying every assignment of the unknown springs.
+    fn brute_force(spring_layout: &str, group_sizes: &[usize]) -> u128 {
+        let unknown: Vec<usize> = spring_layout.match_indices('?').map(|(i, _)| i).collect();
+        let mut count = 0;
+        for mask in 0..1u32 << unknown.len() {
+            let mut springs = spring_layout.as_bytes().to_vec();
+            for (bit, &i) in unknown.iter().enumerate() {
+                springs[i] = if mask >> bit & 1 == 1 { b'#' } else { b'.' };
             }
+            let groups: Vec<usize> = springs.split(|&c| c == b'.').map(<[u8]>::len).filter(|&len| len > 0).collect();
+            count += (groups == group_sizes) as u128;
+        }
+        count
+    }
+
+    #[test]
+    fn sample_answers() {
+        assert_eq!(part1(SAMPLE), Ok(21));
+        assert_eq!(part2(SAMPLE), Ok(525152));
+    }
 
-            // Update the main dp table with the values calculated for the current group
-            dp = new_dp;
+    #[test]
+    fn memoized_count_matches_brute_force_and_part1() {
+        for (layout, groups) in random_rows(12345, 2000) {
+            let expected = brute_force(&layout, &groups);
+            assert_eq!(count_arrangements_memoized(&layout, &groups), expected, "{layout} {groups:?}");
+            let groups: Vec<_> = groups.iter().map(usize::to_string).collect();
+            assert_eq!(part1(&format!("{layout} {}", groups.join(","))), Ok(expected as u64), "{layout} {groups:?}");
         }
+    }
+
+    #[test]
+    fn arrangements_of_single_rows() {
+        assert_eq!(arrangements("???.###", &[1, 1, 3]), 1);
+        assert_eq!(arrangements(".??..??...?##.", &[1, 1, 3]), 4);
+        assert_eq!(arrangements("?###????????", &[3, 2, 1]), 10);
+        assert_eq!(arrangements("###", &[1]), 0);
 
-        // The last value in the dp table is the total number of valid arrangements for the row
-        *dp.last().unwrap()
+        for (layout, groups) in random_rows(999, 2000) {
+            assert_eq!(arrangements(&layout, &groups), count_arrangements_memoized(&layout, &groups), "{layout} {groups:?}");
+        }
     }
-}
 
-#[aoc(day12, part2)]
-pub fn part2(input: &str) -> u128 {
-    input
-        .par_lines()
-        .map(|line| {
-            let (spring_layout, group_sizes) = line.split_once(' ').unwrap();
+    #[test]
+    fn unfold_factors() {
+        assert_eq!(part2_factor(SAMPLE, 1), part1(SAMPLE).map(u128::from));
+        assert_eq!(part2_factor(SAMPLE, 5), part2(SAMPLE));
 
-            // Calculate the required capacity to avoid reallocations
-            let mut extended_spring_layout = String::with_capacity(spring_layout.len() * 5 + 4);
+        let rows: Vec<_> = SAMPLE.lines().map(|row| part2_factor(row, 2).unwrap()).collect();
+        assert_eq!(rows, [1, 32, 1, 2, 20, 150]);
+        assert_eq!(part2_factor(SAMPLE, 2), Ok(206));
+        let row = ".??..??...?##.";
+        assert_eq!(rows[1], brute_force(&format!("{row}?{row}"), &[1, 1, 3, 1, 1, 3]));
+    }
 
-            // Build the expanded string
-            for i in 0..5 {
-                if i > 0 {
-                    extended_spring_layout.push('?');
-                }
-                extended_spring_layout.push_str(spring_layout);
-            }
+    #[test]
+    fn malformed_rows_are_errors() {
+        assert_eq!(part1(&format!("\n{SAMPLE}\n\n")), Ok(21));
+        assert_eq!(part2(&format!("{SAMPLE}\n")), Ok(525152));
 
-            let group_sizes: Vec<usize> = group_sizes
-                .split(',')
-                .map(|num| num.parse::<usize>().unwrap())
-                .collect();
-            let n = group_sizes.len();
-            part2_impl::count_arrangements(&extended_spring_layout, group_sizes.into_iter().cycle().take(5 * n))
-        })
-        .sum()
+        assert_eq!(part1("???.###"), Err("missing group sizes in row: ???.###".to_string()));
+        assert_eq!(part2("???.### 1,x,3"), Err("invalid group size \"x\" in row: ???.### 1,x,3".to_string()));
+        assert_eq!(part1("???.### 1,0,3"), Err("invalid group size \"0\" in row: ???.### 1,0,3".to_string()));
+        assert!(part2("???.### 0").is_err());
+        assert!(part1("???.### ").is_err());
+    }
 }
+
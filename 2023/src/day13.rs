@@ -3,147 +3,307 @@
 // Find lines of reflection in patterns of ash (.) and rocks (#).
 // Part 1: Find perfect reflections
 // Part 2: Find reflections with exactly one smudge (bit difference)
+//
+// Both parts look for a fold whose mirrored lines differ in exactly the allowed number of cells.
+// Rows and columns are packed into u64 bitmasks when the pattern is at most 64 by 64, and
+// compared byte by byte otherwise.
 
 use rayon::prelude::*;
 
-// Converts a pattern grid into binary representations (horizontal and vertical).
-fn convert_grid_to_binary(grid: &str) -> (Vec<u32>, Vec<u32>) {
-    // Split the pattern into lines.
-    let lines: Vec<&str> = grid.lines().collect();
-    let num_rows = lines.len();
-    let num_cols = lines[0].len();
+// A row or column of a pattern, which can be compared against its mirror image.
+trait Line {
+    // Counts the positions where the two lines differ.
+    fn differences(&self, other: &Self) -> u32;
+}
+
+impl Line for u64 {
+    fn differences(&self, other: &Self) -> u32 {
+        (self ^ other).count_ones()
+    }
+}
+
+impl Line for Vec<u8> {
+    fn differences(&self, other: &Self) -> u32 {
+        self.iter().zip(other).filter(|(a, b)| a != b).count() as u32
+    }
+}
+
+// Splits a pattern into its rows, failing if it has none or if the rows differ in width.
+fn pattern_rows(grid: &str) -> Result<Vec<&[u8]>, String> {
+    let rows: Vec<&[u8]> = grid.lines().map(str::as_bytes).collect();
+    let Some(first) = rows.first() else {
+        return Err("empty pattern".to_string());
+    };
+    if let Some(row) = rows.iter().find(|row| row.len() != first.len()) {
+        return Err(format!("pattern rows have different widths: {} and {}", first.len(), row.len()));
+    }
+
+    Ok(rows)
+}
+
+// Converts a pattern's rows into binary representations (horizontal and vertical).
+// Returns `None` if the pattern has more than 64 rows or columns.
+fn convert_grid_to_binary(rows: &[&[u8]]) -> Option<(Vec<u64>, Vec<u64>)> {
+    let num_rows = rows.len();
+    let num_cols = rows[0].len();
+
+    // Each bit of a row is a column and each bit of a column is a row.
+    if num_rows > 64 || num_cols > 64 {
+        return None;
+    }
 
     // Initialize vectors to store the binary representation.
     let mut horizontal = vec![0; num_rows];
     let mut vertical = vec![0; num_cols];
 
     // Iterate through the characters in the pattern and set corresponding bits in the binary vectors.
-    for (i, line) in lines.iter().enumerate() {
-        for (j, char) in line.chars().enumerate() {
-            if char == '#' {
+    for (i, row) in rows.iter().enumerate() {
+        for (j, &cell) in row.iter().enumerate() {
+            if cell == b'#' {
                 // Set the corresponding bit in the binary representation.
-                horizontal[i] |= 1 << j;
-                vertical[j] |= 1 << i;
+                horizontal[i] |= 1u64 << j;
+                vertical[j] |= 1u64 << i;
             }
         }
     }
 
     // Return the binary representations.
+    Some((horizontal, vertical))
+}
+
+// Converts a pattern's rows of any size into its rows and columns as bytes.
+fn convert_grid_to_bytes(rows: &[&[u8]]) -> (Vec<Vec<u8>>, Vec<Vec<u8>>) {
+    let horizontal: Vec<Vec<u8>> = rows.iter().map(|row| row.to_vec()).collect();
+    let vertical = (0..rows[0].len()).map(|j| rows.iter().map(|row| row[j]).collect()).collect();
+
     (horizontal, vertical)
 }
 
-// Part 1 implementation
-mod part1_impl {
-    // Calculates the reflection score by summing the positions of reflection lines for horizontal and vertical reflections.
-    pub fn find_reflection_score(horizontal: &[u32], vertical: &[u32]) -> u32 {
-        // Calculate the reflection score for horizontal reflection and multiply it by 100.
-        check_reflections(horizontal) * 100 + check_reflections(vertical)
-    }
+// Finds the first fold where the mirrored lines differ in exactly `smudges` positions in total.
+// Returns the number of lines before the fold, or `None` if there is no such fold.
+fn find_fold<L: Line>(arr: &[L], smudges: u32) -> Option<usize> {
+    (1..arr.len()).find(|&mid| {
+        // Pair each line before the fold with its mirror image after it, stopping at the nearer edge.
+        let differences: u32 = (0..mid).rev().zip(mid..arr.len()).map(|(left, right)| arr[left].differences(&arr[right])).sum();
+        differences == smudges
+    })
+}
 
-    // Checks for reflection points in the binary representation.
-    // It returns the position (line/column number) of the reflection point or 0 if no reflection point is found.
-    fn check_reflections(arr: &[u32]) -> u32 {
-        for line in 0..arr.len() {
-            // Check if the current line is a reflection point.
-            if is_reflection_point(arr, line) {
-                return (line + 1).try_into().unwrap();
-            }
+/// Finds the line of reflection in a single pattern when exactly `smudges` cells must be
+/// flipped to make it a perfect mirror (0 for part 1, 1 for part 2).
+///
+/// Returns the number of rows above a horizontal line of reflection and the number of
+/// columns left of a vertical one, each `None` if the pattern has no such line.
+/// Fails if the pattern is empty or its rows differ in width.
+pub fn find_reflection(grid: &str, smudges: u32) -> Result<(Option<usize>, Option<usize>), String> {
+    let rows = pattern_rows(grid)?;
+    Ok(match convert_grid_to_binary(&rows) {
+        Some((horizontal, vertical)) => (find_fold(&horizontal, smudges), find_fold(&vertical, smudges)),
+        None => {
+            let (horizontal, vertical) = convert_grid_to_bytes(&rows);
+            (find_fold(&horizontal, smudges), find_fold(&vertical, smudges))
         }
-        0
-    }
+    })
+}
 
-    // Checks if the given position (line/column) is a reflection point.
-    // A reflection point occurs when the binary values on both sides of the mid-point are the same.
-    // This function returns true if it's a reflection point and false otherwise.
-    fn is_reflection_point(arr: &[u32], mid: usize) -> bool {
-        let mut left: i32 = mid as i32;
-        let mut right = mid + 1;
-
-        // Compare binary values from left and right, moving towards the edges.
-        while left > 0 && right < arr.len() - 1 && arr[left as usize] == arr[right] {
-            left = left.saturating_sub(1);
-            right += 1;
-        }
+// Scores a pattern as 100 times the rows above its horizontal line of reflection plus the
+// columns left of its vertical one, or `None` if it has neither.
+fn reflection_score(grid: &str, smudges: u32) -> Result<Option<u32>, String> {
+    Ok(match find_reflection(grid, smudges)? {
+        (None, None) => None,
+        (rows, cols) => Some((rows.unwrap_or(0) * 100 + cols.unwrap_or(0)) as u32),
+    })
+}
 
-        // Check if the reflection point is at the edge (no match on one side) and the values are the same.
-        (left == 0 || right == arr.len() - 1) && arr[left as usize] == arr[right]
-    }
+/// Scores each pattern in the input for the given smudge count, with `None` for patterns
+/// that have no line of reflection (which the part totals count as 0). Blank patterns are
+/// skipped, and a pattern whose rows differ in width is an error.
+pub fn pattern_scores(input: &str, smudges: u32) -> Result<Vec<Option<u32>>, String> {
+    // Split the input into separate patterns using "\n\n" as the delimiter.
+    let grids: Vec<&str> = input.split("\n\n").filter(|grid| !grid.trim().is_empty()).collect();
+
+    grids.par_iter().map(|&grid| reflection_score(grid, smudges)).collect()
+}
+
+// Sums the reflection scores of every pattern in the input.
+fn total_score(input: &str, smudges: u32) -> Result<u32, String> {
+    Ok(pattern_scores(input, smudges)?.into_iter().flatten().sum())
 }
 
 #[aoc(day13, part1)]
-pub fn part1(input: &str) -> u32 {
-    // Split the input into separate patterns using "\n\n" as the delimiter.
-    let grids: Vec<&str> = input.split("\n\n").collect();
-
-    // Use Rayon's parallel iterator to process patterns concurrently.
-    grids.par_iter()
-         .map(|&grid| {
-             // Convert the pattern into binary representations (horizontal and vertical).
-             let (horizontal, vertical) = convert_grid_to_binary(grid);
-
-             // Calculate the reflection score for this pattern.
-             part1_impl::find_reflection_score(&horizontal, &vertical)
-         })
-         .sum() // Sum the reflection scores from all patterns.
+pub fn part1(input: &str) -> Result<u32, String> {
+    total_score(input, 0)
 }
 
-// Part 2 implementation
-mod part2_impl {
-    // Calculates the reflection score by summing the positions of reflection lines for horizontal and vertical reflections,
-    // considering smudges.
-    pub fn find_reflection_score(horizontal: &[u32], vertical: &[u32]) -> u32 {
-        // Calculate the reflection score for horizontal reflection and multiply it by 100.
-        check_reflections(horizontal) * 100 + check_reflections(vertical)
-    }
+#[aoc(day13, part2)]
+pub fn part2(input: &str) -> Result<u32, String> {
+    // Exactly one smudge must be fixed to reveal the new line of reflection.
+    total_score(input, 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::rng;
+
+    const SAMPLE: &str = "\
+#.##..##.
+..#.##.#.
+##......#
+##......#
+..#.##.#.
+..##..##.
+#.#.##.#.
+
+#...##..#
+#....#..#
+..##..###
+#####.##.
+#####.##.
+..##..###
+#....#..#
+";
 
-    // Checks for reflection points in the binary representation, considering smudges.
-    // It returns the position (line/column number) of the reflection point or 0 if no reflection point is found.
-    fn check_reflections(arr: &[u32]) -> u32 {
-        for line in 0..arr.len() {
-            // Check if the current line is a reflection point with a smudge.
-            if is_smudged_reflection_point(arr, line) {
-                return (line + 1).try_into().unwrap();
+    /// A random pattern up to `max_width` columns wide, often with a mirror planted in its
+    /// rows and sometimes with one cell flipped afterwards.
+    fn random_pattern(seed: &mut u64, max_width: usize) -> String {
+        let height = 1 + rng(seed) as usize % if max_width > 20 { 80 } else { 12 };
+        let width = 1 + rng(seed) as usize % max_width;
+        let mut rows: Vec<Vec<u8>> =
+            (0..height).map(|_| (0..width).map(|_| if rng(seed).is_multiple_of(2) { b'#' } else { b'.' }).collect()).collect();
+
+        if height >= 2 && rng(seed).is_multiple_of(2) {
+            let mid = 1 + rng(seed) as usize % (height - 1);
+            for k in 0..mid.min(height - mid) {
+                rows[mid + k] = rows[mid - 1 - k].clone();
+            }
+            if rng(seed).is_multiple_of(2) {
+                let cell = &mut rows[rng(seed) as usize % height][rng(seed) as usize % width];
+                *cell = if *cell == b'#' { b'.' } else { b'#' };
             }
         }
-        0
+
+        rows.iter().map(|row| String::from_utf8_lossy(row)).collect::<Vec<_>>().join("\n")
+    }
+
+    /// Scores a pattern by comparing every fold cell by cell.
+    fn brute_force(grid: &str, smudges: usize) -> u32 {
+        let rows: Vec<&[u8]> = grid.lines().map(str::as_bytes).collect();
+        let cols: Vec<Vec<u8>> = (0..rows[0].len()).map(|j| rows.iter().map(|row| row[j]).collect()).collect();
+        let fold = |lines: Vec<&[u8]>| {
+            (1..lines.len())
+                .find(|&mid| {
+                    let pairs = (0..mid).rev().zip(mid..lines.len());
+                    let differences: usize =
+                        pairs.map(|(l, r)| lines[l].iter().zip(lines[r]).filter(|(a, b)| a != b).count()).sum();
+                    differences == smudges
+                })
+                .unwrap_or(0) as u32
+        };
+        fold(rows.clone()) * 100 + fold(cols.iter().map(Vec::as_slice).collect())
+    }
+
+    #[test]
+    fn sample_answers() {
+        assert_eq!(part1(SAMPLE), Ok(405));
+        assert_eq!(part2(SAMPLE), Ok(400));
+    }
+
+    #[test]
+    fn reflections_at_the_edges() {
+        // The last two rows mirror each other, so the fold is after row 4
+        let pattern = "#..\n.#.\n..#\n###\n###";
+        assert_eq!(part1(pattern), Ok(400));
+        assert_eq!(part1(pattern), Ok(brute_force(pattern, 0)));
+        assert_eq!(part1("###\n###\n#..\n.#."), Ok(100));
+        assert_eq!(part1("#.#"), Ok(0));
+        assert_eq!(part1("##"), Ok(1));
+
+        let mut seed = 777;
+        for _ in 0..3000 {
+            let pattern = random_pattern(&mut seed, 20);
+            assert_eq!(part1(&pattern), Ok(brute_force(&pattern, 0)), "{pattern}");
+            assert_eq!(part2(&pattern), Ok(brute_force(&pattern, 1)), "{pattern}");
+        }
+    }
+
+    #[test]
+    fn patterns_wider_than_32_columns() {
+        // 40 columns mirrored between columns 20 and 21, with no two neighbouring rows alike
+        let mirrored = |half: String| format!("{half}{}", half.chars().rev().collect::<String>());
+        let a = mirrored((0..20).map(|i| if i % 3 == 0 { '#' } else { '.' }).collect());
+        let b = mirrored((0..20).map(|i| if i % 4 == 1 { '#' } else { '.' }).collect());
+        let pattern = format!("{a}\n{b}\n{a}\n{b}\n{a}");
+        assert_eq!(a.len(), 40);
+        assert_eq!(find_reflection(&pattern, 0), Ok((None, Some(20))));
+        assert_eq!(part1(&pattern), Ok(20));
+        assert_eq!(part2(&pattern), Ok(brute_force(&pattern, 1)));
+
+        let mut seed = 4242;
+        for _ in 0..100 {
+            let pattern = random_pattern(&mut seed, 100);
+            assert_eq!(part1(&pattern), Ok(brute_force(&pattern, 0)), "{pattern}");
+            assert_eq!(part2(&pattern), Ok(brute_force(&pattern, 1)), "{pattern}");
+        }
+    }
+
+    #[test]
+    fn empty_and_ragged_patterns() {
+        assert_eq!(part1(&format!("\n\n{SAMPLE}\n\n\n")), Ok(405));
+        assert_eq!(part1(""), Ok(0));
+        assert_eq!(find_reflection("", 0), Err("empty pattern".to_string()));
+
+        // A later row wider than the first would otherwise set bits past the last column
+        assert_eq!(part1("#.\n#.#"), Err("pattern rows have different widths: 2 and 3".to_string()));
+        assert!(part2(&format!("{SAMPLE}\n\n#..\n#")).is_err());
     }
 
-    // Checks if the given position (line/column) is a reflection point with a smudge.
-    // A reflection point with a smudge occurs when there is exactly one difference (smudge)
-    // between the binary values on both sides of the mid-point.
-    // This function returns true if it's a reflection point with one smudge and false otherwise.
-    fn is_smudged_reflection_point(arr: &[u32], mid: usize) -> bool {
-        let mut left: i32 = mid as i32;
-        let mut right = mid + 1;
-        let mut smudges = 0;
-
-        // Compare binary values from left and right, moving towards the edges, and count smudges.
-        while left >= 0 && right < arr.len() {
-            smudges += (arr[left as usize] ^ arr[right]).count_ones();
-            if smudges > 1 {
-                return false;
+    #[test]
+    fn reflections_of_the_sample_patterns() {
+        let (first, second) = SAMPLE.split_once("\n\n").unwrap();
+        assert_eq!(find_reflection(first, 0), Ok((None, Some(5))));
+        assert_eq!(find_reflection(second, 0), Ok((Some(4), None)));
+        assert_eq!(find_reflection(first, 1), Ok((Some(3), None)));
+        assert_eq!(find_reflection(second, 1), Ok((Some(1), None)));
+
+        let mut seed = 99;
+        for _ in 0..150 {
+            let pattern = random_pattern(&mut seed, 70);
+            for smudges in 0..2 {
+                let (rows, cols) = find_reflection(&pattern, smudges).unwrap();
+                let score = rows.unwrap_or(0) as u32 * 100 + cols.unwrap_or(0) as u32;
+                assert_eq!(score, brute_force(&pattern, smudges as usize), "{pattern}");
             }
-            left = left.saturating_sub(1);
-            right += 1;
         }
-        // Check if the reflection point is at the edge (no match on one side) and there is exactly one smudge.
-        (left <= 0 || right == arr.len()) && smudges == 1
     }
-}
 
-#[aoc(day13, part2)]
-pub fn part2(input: &str) -> u32 {
-    // Split the input into separate patterns using "\n\n" as the delimiter.
-    let grids: Vec<&str> = input.split("\n\n").collect();
-
-    // Use Rayon's parallel iterator to process patterns concurrently.
-    grids.par_iter()
-         .map(|&grid| {
-             // Convert the pattern into binary representations (horizontal and vertical).
-             let (horizontal, vertical) = convert_grid_to_binary(grid);
-
-             // Calculate the reflection score for this pattern, considering smudges.
-             part2_impl::find_reflection_score(&horizontal, &vertical)
-         })
-         .sum() // Sum the reflection scores from all patterns.
+    #[test]
+    fn one_core_scores_both_parts() {
+        let (first, second) = SAMPLE.split_once("\n\n").unwrap();
+        assert_eq!(reflection_score(first, 0), Ok(Some(5)));
+        assert_eq!(reflection_score(second, 0), Ok(Some(400)));
+        assert_eq!(reflection_score(first, 1), Ok(Some(300)));
+        assert_eq!(reflection_score(second, 1), Ok(Some(100)));
+        assert_eq!(total_score(SAMPLE, 0), part1(SAMPLE));
+        assert_eq!(total_score(SAMPLE, 1), Ok(400));
+
+        // Each smudge count finds different folds, and one smudge allows a fold each way
+        let pattern = "#..#\n.##.\n..#.";
+        assert_eq!(reflection_score(pattern, 0), Ok(None));
+        assert_eq!(reflection_score(pattern, 1), Ok(Some(202)));
+        assert_eq!(reflection_score(pattern, 2), Ok(Some(1)));
+    }
+
+    #[test]
+    fn patterns_without_a_reflection_are_none() {
+        assert_eq!(pattern_scores(SAMPLE, 0), Ok(vec![Some(5), Some(400)]));
+
+        let input = "#.\n.#\n\n#.#\n#.#";
+        assert_eq!(pattern_scores(input, 0), Ok(vec![None, Some(100)]));
+        assert_eq!(part1(input), Ok(100));
+        // Both patterns are two smudges from any fold
+        assert_eq!(pattern_scores(input, 1), Ok(vec![None, None]));
+        assert_eq!(part2(input), Ok(0));
+    }
 }
+
@@ -2,206 +2,515 @@
 //
 // Part 1: Tilt platform north and calculate load
 // Part 2: Simulate 1 billion tilt cycles using cycle detection
+//
+// Part 2 stores the rocks as one bitmask per row, so tilting rolls whole rows at once with
+// shifts and masks.
 
 use rayon::prelude::*;
 
-    const BATCH_SIZE: usize = 100;
+const BATCH_SIZE: usize = 100;
 
-    #[aoc(day14, part1)]
+#[aoc(day14, part1)]
 pub fn part1(input: &str) -> usize {
-        let columns = transpose_input(input);
-        calculate_total_load(columns, input.lines().next().unwrap().len())
-    }
+    let columns = transpose_input(input);
+    calculate_total_load(columns, input.lines().next().unwrap().len())
+}
 
-    fn transpose_input(input: &str) -> Vec<char> {
-        let lines: Vec<&str> = input.lines().collect();
-        let num_rows = lines.len();
-        let num_columns = lines[0].len();
+fn transpose_input(input: &str) -> Vec<char> {
+    let lines: Vec<&str> = input.lines().collect();
+    let num_rows = lines.len();
+    let num_columns = lines[0].len();
 
-        let mut transposed = vec!['.'; num_rows * num_columns];
+    let mut transposed = vec!['.'; num_rows * num_columns];
 
-        for (row, line) in lines.iter().enumerate() {
-            for (col, char) in line.chars().enumerate() {
-                transposed[col * num_rows + row] = char;
-            }
+    for (row, line) in lines.iter().enumerate() {
+        for (col, char) in line.chars().enumerate() {
+            transposed[col * num_rows + row] = char;
         }
+    }
+
+    transposed
+}
+
+fn calculate_total_load(columns: Vec<char>, num_rows: usize) -> usize {
+    // Create ranges for each batch and debug print them
+    let batch_ranges: Vec<_> = (0..columns.len() / num_rows)
+        .step_by(BATCH_SIZE)
+        .map(|start_col| {
+            start_col * num_rows..((start_col + BATCH_SIZE) * num_rows).min(columns.len())
+        })
+        .collect();
 
-        transposed
-    }
-
-    fn calculate_total_load(columns: Vec<char>, num_rows: usize) -> usize {
-        // Create ranges for each batch and debug print them
-        let batch_ranges: Vec<_> = (0..columns.len() / num_rows)
-            .step_by(BATCH_SIZE)
-            .map(|start_col| {
-                start_col * num_rows..((start_col + BATCH_SIZE) * num_rows).min(columns.len())
-            })
-            .collect();
-
-        // Process each batch in parallel
-        batch_ranges
-            .into_par_iter()
-            .map(|range| {
-                (range.start..range.end)
-                    .step_by(num_rows)
-                    .map(|col_start| {
-                        let mut total_load = 0;
-                        let mut next_load = num_rows;
-
-                        for row in 0..num_rows {
-                            match columns[col_start + row] {
-                                'O' => {
-                                    total_load += next_load;
-                                    next_load -= 1;
-                                }
-                                '#' => next_load = num_rows - row - 1,
-                                _ => (),
+    // Process each batch in parallel
+    batch_ranges
+        .into_par_iter()
+        .map(|range| {
+            (range.start..range.end)
+                .step_by(num_rows)
+                .map(|col_start| {
+                    let mut total_load = 0;
+                    let mut next_load = num_rows;
+
+                    for row in 0..num_rows {
+                        match columns[col_start + row] {
+                            'O' => {
+                                total_load += next_load;
+                                next_load -= 1;
                             }
+                            '#' => next_load = num_rows - row - 1,
+                            _ => (),
                         }
+                    }
 
-                        total_load
-                    })
-                    .sum::<usize>()
-            })
-            .sum()
-    }
+                    total_load
+                })
+                .sum::<usize>()
+        })
+        .sum()
+}
 
-use rustc_hash::FxHashSet as HashSet;
-    use std::hash::{Hash, Hasher};
-    
-    #[aoc(day14, part2)]
+#[aoc(day14, part2)]
 pub fn part2(input: &str) -> usize {
-        let mut grid: Vec<Vec<char>> = input.lines().map(|line| line.chars().collect()).collect();
-        let mut seen_states = HashSet::default();
-        let total_cycles = 1_000_000_000;
-        let mut cycle_start = 0;
-    
-        for cycle in 1.. {
-            flip_up(&mut grid);
-            flip_left(&mut grid);
-            flip_down(&mut grid);
-            flip_right(&mut grid);
-    
-            let state_hash = hash_grid(&grid);
-            if !seen_states.insert(state_hash) {
-                cycle_start = cycle;
-                break;
-            }
+    load_after(input, 1_000_000_000)
+}
+
+/// The north load after running the given number of spin cycles.
+pub fn load_after(input: &str, total_cycles: u64) -> usize {
+    let grid: Vec<Vec<char>> = input.lines().map(|line| line.chars().collect()).collect();
+
+    // Rows fit in a u128 bitmask on any real input; wider platforms use the char grid
+    match RoundRocks::parse(&grid) {
+        Some((rocks, cubes)) => load_after_spins(
+            rocks,
+            total_cycles,
+            |rocks| rocks.spin(&cubes),
+            RoundRocks::north_load,
+        ),
+        None => load_after_spins(grid, total_cycles, |grid| spin_cycle(grid), |grid| calculate_north_support_load(grid)),
+    }
+}
+
+// Spins the platform `total_cycles` times and returns the final north load.
+//
+// The states form a tail of `mu` spins leading into a loop of `lambda` spins. Brent's
+// algorithm finds both by comparing real states, so only a couple of states are kept
+// at a time, and then the answer is the state at `mu + (total_cycles - mu) % lambda`.
+fn load_after_spins<S: Clone + Eq>(
+    start: S,
+    total_cycles: u64,
+    spin: impl Fn(&mut S),
+    load: impl Fn(&S) -> usize,
+) -> usize {
+    if total_cycles == 0 {
+        return load(&start);
+    }
+
+    // Find the loop length: the hare runs ahead while the tortoise teleports to it at each
+    // power of two, until the hare catches up with the tortoise.
+    let mut tortoise = start.clone();
+    let mut hare = start.clone();
+    spin(&mut hare);
+    let (mut power, mut lambda, mut spins) = (1u64, 1u64, 1u64);
+
+    while tortoise != hare {
+        // Reaching the target before closing the loop means we can stop right here
+        if spins == total_cycles {
+            return load(&hare);
         }
-    
-        // Directly calculate cycle length
-        let cycle_length = calculate_cycle_length(&mut grid);
-    
-        let effective_cycle = (total_cycles - cycle_start) % cycle_length + cycle_start;
-        for _ in cycle_start..effective_cycle {
-            flip_up(&mut grid);
-            flip_left(&mut grid);
-            flip_down(&mut grid);
-            flip_right(&mut grid);
+        if power == lambda {
+            tortoise = hare.clone();
+            power *= 2;
+            lambda = 0;
         }
-    
-        calculate_north_support_load(&grid)
+        spin(&mut hare);
+        lambda += 1;
+        spins += 1;
     }
 
-    fn flip_up(grid: &mut Vec<Vec<char>>) {
-        for x in 0..grid[0].len() {
-            let mut binding = 0;
-            for y in 0..grid.len() {
-                if grid[y][x] == 'O' {
-                    grid[y][x] = '.';
-                    grid[binding][x] = 'O';
-                    binding += 1;
-                } else if grid[y][x] == '#' {
-                    binding = y + 1;
-                }
+    // Find the tail length: with the hare lambda spins ahead, both meet where the loop starts
+    let mut tortoise = start.clone();
+    let mut hare = start;
+    for _ in 0..lambda {
+        spin(&mut hare);
+    }
+    let mut mu = 0u64;
+    while tortoise != hare {
+        spin(&mut tortoise);
+        spin(&mut hare);
+        mu += 1;
+    }
+
+    // The tortoise is at the loop start after mu spins, so only the remainder is left.
+    // Closing the loop took more than mu spins, all short of the target, so it is past the tail.
+    for _ in 0..(total_cycles - mu) % lambda {
+        spin(&mut tortoise);
+    }
+
+    load(&tortoise)
+}
+
+/// The round rocks on the platform as one bitmask per row, with bit `x` set for column `x`.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct RoundRocks(Vec<u128>);
+
+/// The cube-shaped rocks, which never move, in the same layout as `RoundRocks`.
+struct CubeRocks {
+    rows: Vec<u128>,
+    // Bits for the columns that exist on the platform
+    mask: u128,
+}
+
+impl RoundRocks {
+    /// Splits a grid into its round and cube-shaped rocks, or returns `None` if a row is
+    /// wider than 128 columns.
+    fn parse(grid: &[Vec<char>]) -> Option<(RoundRocks, CubeRocks)> {
+        let width = grid.first().map_or(0, Vec::len);
+        if width > 128 {
+            return None;
+        }
+
+        let bits = |rock: char| -> Vec<u128> {
+            grid.iter()
+                .map(|row| row.iter().enumerate().filter(|&(_, &c)| c == rock).fold(0, |bits, (x, _)| bits | 1 << x))
+                .collect()
+        };
+        let mask = if width == 128 { u128::MAX } else { (1 << width) - 1 };
+
+        Some((RoundRocks(bits('O')), CubeRocks { rows: bits('#'), mask }))
+    }
+
+    /// Rolls every rock as far north as it can go.
+    fn roll_north(&mut self, cubes: &CubeRocks) {
+        let rows = &mut self.0;
+
+        // Rows above are already settled, so rocks in each row keep moving up until blocked
+        for y in 1..rows.len() {
+            let mut moving = rows[y];
+            let mut row = y;
+            while row > 0 && moving != 0 {
+                moving &= !(rows[row - 1] | cubes.rows[row - 1]);
+                rows[row] &= !moving;
+                rows[row - 1] |= moving;
+                row -= 1;
             }
         }
     }
 
-    fn flip_left(grid: &mut Vec<Vec<char>>) {
-        for y in 0..grid.len() {
-            let mut binding = 0;
-            for x in 0..grid[0].len() {
-                if grid[y][x] == 'O' {
-                    grid[y][x] = '.';
-                    grid[y][binding] = 'O';
-                    binding += 1;
-                } else if grid[y][x] == '#' {
-                    binding = x + 1;
+    /// Rolls every rock as far south as it can go.
+    fn roll_south(&mut self, cubes: &CubeRocks) {
+        let rows = &mut self.0;
+
+        // Rows below are already settled, so rocks in each row keep moving down until blocked
+        for y in (0..rows.len().saturating_sub(1)).rev() {
+            let mut moving = rows[y];
+            let mut row = y;
+            while row + 1 < rows.len() && moving != 0 {
+                moving &= !(rows[row + 1] | cubes.rows[row + 1]);
+                rows[row] &= !moving;
+                rows[row + 1] |= moving;
+                row += 1;
+            }
+        }
+    }
+
+    /// Rolls every rock as far west (towards column 0) as it can go.
+    fn roll_west(&mut self, cubes: &CubeRocks) {
+        for (rocks, &cube) in self.0.iter_mut().zip(&cubes.rows) {
+            // Step every rock with an empty cell to its west until none can move
+            loop {
+                let empty = !(*rocks | cube) & cubes.mask;
+                let moving = *rocks & (empty << 1);
+                if moving == 0 {
+                    break;
                 }
+                *rocks = (*rocks & !moving) | (moving >> 1);
             }
         }
     }
 
-    fn flip_down(grid: &mut Vec<Vec<char>>) {
-        for x in 0..grid[0].len() {
-            let mut binding = (grid.len() - 1) as i32;
-            for y in (0..grid.len()).rev() {
-                if grid[y][x] == 'O' {
-                    grid[y][x] = '.';
-                    grid[binding as usize][x] = 'O';
-                    binding -= 1;
-                } else if grid[y][x] == '#' {
-                    binding = y as i32 - 1;
+    /// Rolls every rock as far east as it can go.
+    fn roll_east(&mut self, cubes: &CubeRocks) {
+        for (rocks, &cube) in self.0.iter_mut().zip(&cubes.rows) {
+            // Step every rock with an empty cell to its east until none can move
+            loop {
+                let empty = !(*rocks | cube) & cubes.mask;
+                let moving = *rocks & (empty >> 1);
+                if moving == 0 {
+                    break;
                 }
+                *rocks = (*rocks & !moving) | (moving << 1);
             }
         }
     }
 
-    fn flip_right(grid: &mut Vec<Vec<char>>) {
+    /// Tilts the platform north, then west, then south, then east.
+    fn spin(&mut self, cubes: &CubeRocks) {
+        self.roll_north(cubes);
+        self.roll_west(cubes);
+        self.roll_south(cubes);
+        self.roll_east(cubes);
+    }
+
+    /// Each rock's load is the number of rows from it to the south edge, inclusive.
+    fn north_load(&self) -> usize {
+        let height = self.0.len();
+        self.0.iter().enumerate().map(|(y, row)| row.count_ones() as usize * (height - y)).sum()
+    }
+}
+
+/// Runs one spin cycle: tilts the platform north, then west, then south, then east.
+pub fn spin_cycle(grid: &mut [Vec<char>]) {
+    tilt_north(grid);
+    tilt_west(grid);
+    tilt_south(grid);
+    tilt_east(grid);
+}
+
+/// Tilts the platform north, rolling every round rock ('O') up until it hits the edge or
+/// another rock. Cube-shaped rocks ('#') stay put.
+pub fn tilt_north(grid: &mut [Vec<char>]) {
+    for x in 0..grid.first().map_or(0, Vec::len) {
+        let mut binding = 0;
         for y in 0..grid.len() {
-            let mut binding = grid[0].len() as i32 - 1;
-            for x in (0..grid[0].len()).rev() {
-                if grid[y][x] == 'O' {
-                    grid[y][x] = '.';
-                    grid[y][binding as usize] = 'O';
-                    binding -= 1;
-                } else if grid[y][x] == '#' {
-                    binding = x as i32 - 1;
-                }
+            if grid[y][x] == 'O' {
+                grid[y][x] = '.';
+                grid[binding][x] = 'O';
+                binding += 1;
+            } else if grid[y][x] == '#' {
+                binding = y + 1;
             }
         }
     }
+}
 
-    fn calculate_north_support_load(grid: &[Vec<char>]) -> usize {
-        let mut load = 0;
-        let grid_height = grid.len();
-    
-        // Iterate over each column.
+/// Tilts the platform west, rolling every round rock as far left as it can go.
+pub fn tilt_west(grid: &mut [Vec<char>]) {
+    for y in 0..grid.len() {
+        let mut binding = 0;
         for x in 0..grid[0].len() {
-            for y in (0..grid_height).rev() {
-                if grid[y][x] == 'O' {
-                    // Load is the distance from the 'O' to the bottom of the grid.
-                    load += grid_height - y;
-                }
+            if grid[y][x] == 'O' {
+                grid[y][x] = '.';
+                grid[y][binding] = 'O';
+                binding += 1;
+            } else if grid[y][x] == '#' {
+                binding = x + 1;
+            }
+        }
+    }
+}
+
+/// Tilts the platform south, rolling every round rock as far down as it can go.
+pub fn tilt_south(grid: &mut [Vec<char>]) {
+    for x in 0..grid.first().map_or(0, Vec::len) {
+        let mut binding = (grid.len() - 1) as i32;
+        for y in (0..grid.len()).rev() {
+            if grid[y][x] == 'O' {
+                grid[y][x] = '.';
+                grid[binding as usize][x] = 'O';
+                binding -= 1;
+            } else if grid[y][x] == '#' {
+                binding = y as i32 - 1;
+            }
+        }
+    }
+}
+
+/// Tilts the platform east, rolling every round rock as far right as it can go.
+pub fn tilt_east(grid: &mut [Vec<char>]) {
+    for y in 0..grid.len() {
+        let mut binding = grid[0].len() as i32 - 1;
+        for x in (0..grid[0].len()).rev() {
+            if grid[y][x] == 'O' {
+                grid[y][x] = '.';
+                grid[y][binding as usize] = 'O';
+                binding -= 1;
+            } else if grid[y][x] == '#' {
+                binding = x as i32 - 1;
+            }
+        }
+    }
+}
+
+/// The total load on the north support beams: each round rock counts the number of rows
+/// from it to the south edge, including its own.
+pub fn calculate_north_support_load(grid: &[Vec<char>]) -> usize {
+    let mut load = 0;
+    let grid_height = grid.len();
+
+    // Iterate over each row.
+    for (y, row) in grid.iter().enumerate() {
+        // Load is the distance from each 'O' to the bottom of the grid.
+        load += row.iter().filter(|&&c| c == 'O').count() * (grid_height - y);
+    }
+    load
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{random_grid, rng};
+    use std::collections::HashMap;
+
+    const SAMPLE: &str = "\
+O....#....
+O.OO#....#
+.....##...
+OO.#O....O
+.O.....O#.
+O.#..O.#.#
+..O..#O..O
+.......O..
+#....###..
+#OO..#....
+";
+
+    fn to_grid(input: &str) -> Vec<Vec<char>> {
+        input.lines().map(|line| line.chars().collect()).collect()
+    }
+
+    /// Spins the char grid, remembering every state seen to jump ahead once one repeats.
+    fn brute_force(input: &str, total_cycles: usize) -> usize {
+        let mut grid = to_grid(input);
+        let mut seen = HashMap::from([(grid.clone(), 0)]);
+        let mut loads = vec![calculate_north_support_load(&grid)];
+        for cycle in 1..=total_cycles {
+            spin_cycle(&mut grid);
+            loads.push(calculate_north_support_load(&grid));
+            if let Some(start) = seen.insert(grid.clone(), cycle) {
+                return loads[start + (total_cycles - start) % (cycle - start)];
+            }
+        }
+        loads[total_cycles]
+    }
+
+    #[test]
+    fn sample_answers() {
+        assert_eq!(part1(SAMPLE), 136);
+        assert_eq!(part2(SAMPLE), 64);
+    }
+
+    #[test]
+    fn part2_matches_a_map_of_seen_states() {
+        let mut seed = 5;
+        for _ in 0..200 {
+            let (height, width) = (1 + rng(&mut seed) as usize % 12, 1 + rng(&mut seed) as usize % 12);
+            let grid = random_grid(&mut seed, height, width, b"#OO...");
+            assert_eq!(part2(&grid), brute_force(&grid, 1_000_000_000), "{grid}");
+        }
+    }
+
+    #[test]
+    fn bitset_rolls_match_char_tilts() {
+        type Roll = fn(&mut RoundRocks, &CubeRocks);
+        type Tilt = fn(&mut [Vec<char>]);
+        let directions: [(Roll, Tilt); 4] = [
+            (RoundRocks::roll_north, tilt_north),
+            (RoundRocks::roll_west, tilt_west),
+            (RoundRocks::roll_south, tilt_south),
+            (RoundRocks::roll_east, tilt_east),
+        ];
+
+        let mut seed = 31337;
+        let grids = (0..200).map(|_| {
+            let (height, width) = (1 + rng(&mut seed) as usize % 20, 1 + rng(&mut seed) as usize % 128);
+            random_grid(&mut seed, height, width, b"#OO...")
+        });
+        for input in std::iter::once(SAMPLE.to_string()).chain(grids) {
+            for (roll, tilt) in directions {
+                let mut grid = to_grid(&input);
+                let (mut rocks, cubes) = RoundRocks::parse(&grid).unwrap();
+                roll(&mut rocks, &cubes);
+                tilt(&mut grid);
+                assert!(rocks == RoundRocks::parse(&grid).unwrap().0, "{input}");
+                assert_eq!(rocks.north_load(), calculate_north_support_load(&grid));
             }
         }
-        load
-    }
-    
-
-    fn hash_grid(grid: &[Vec<char>]) -> u64 {
-        let mut hasher = std::collections::hash_map::DefaultHasher::new();
-        grid.hash(&mut hasher);
-        hasher.finish()
-    }
-
-    fn calculate_cycle_length(grid: &mut Vec<Vec<char>>) -> usize {
-        let initial_state_hash = hash_grid(grid);
-        let mut cycle_count = 0;
-    
-        loop {
-            cycle_count += 1;
-            flip_up(grid);
-            flip_left(grid);
-            flip_down(grid);
-            flip_right(grid);
-    
-            let current_state_hash = hash_grid(grid);
-            if current_state_hash == initial_state_hash {
-                break;
+
+        // Rows wider than 128 columns fall back to the char grid
+        let padded: String = SAMPLE.lines().map(|line| format!("{line}{}\n", "#".repeat(130))).collect();
+        assert!(RoundRocks::parse(&to_grid(&padded)).is_none());
+        assert_eq!(part2(&padded), 64);
+    }
+
+    const AFTER_ONE_CYCLE: &str = "\
+.....#....
+....#...O#
+...OO##...
+.OO#......
+.....OOO#.
+.O#...O#.#
+....O#....
+......OOOO
+#...O###..
+#..OO#....
+";
+
+    #[test]
+    fn one_spin_cycle_of_the_sample() {
+        let mut grid = to_grid(SAMPLE);
+        spin_cycle(&mut grid);
+        assert_eq!(grid, to_grid(AFTER_ONE_CYCLE));
+
+        let mut grid = to_grid(SAMPLE);
+        tilt_north(&mut grid);
+        assert_eq!(calculate_north_support_load(&grid), 136);
+        assert_eq!(grid[0].iter().collect::<String>(), "OOOO.#.O..");
+
+        let mut empty: Vec<Vec<char>> = Vec::new();
+        spin_cycle(&mut empty);
+        assert_eq!(calculate_north_support_load(&empty), 0);
+    }
+
+    #[test]
+    fn cycles_after_a_tail() {
+        // The first three states never come back, then the loads repeat 7, 10, 8
+        let grid = "O...\n.##O\n..#O\n...O";
+        let loads: Vec<_> = (0..10).map(|cycles| load_after(grid, cycles)).collect();
+        assert_eq!(loads, [10, 8, 6, 7, 10, 8, 7, 10, 8, 7]);
+        // Counting the loop from the start instead of from its entry would give 8
+        assert_eq!(part2(grid), 10);
+
+        let mut seed = 11;
+        for _ in 0..100 {
+            let grid = random_grid(&mut seed, 7, 9, b"#OO...");
+            for cycles in 0..40 {
+                assert_eq!(load_after(&grid, cycles), brute_force(&grid, cycles as usize), "{grid}");
             }
         }
-    
-        cycle_count
     }
+
+    #[test]
+    fn loads_after_the_documented_cycles() {
+        let after_two_cycles = "\
+.....#....
+....#...O#
+.....##...
+..O#......
+.....OOO#.
+.O#...O#.#
+....O#...O
+.......OOO
+#..OO###..
+#.OOO#...O
+";
+        let after_three_cycles = "\
+.....#....
+....#...O#
+.....##...
+..O#......
+.....OOO#.
+.O#...O#.#
+....O#...O
+.......OOO
+#...O###.O
+#.OOO#...O
+";
+
+        assert_eq!(load_after(SAMPLE, 0), calculate_north_support_load(&to_grid(SAMPLE)));
+        assert_eq!(load_after(SAMPLE, 1), calculate_north_support_load(&to_grid(AFTER_ONE_CYCLE)));
+        assert_eq!(load_after(SAMPLE, 2), calculate_north_support_load(&to_grid(after_two_cycles)));
+        assert_eq!(load_after(SAMPLE, 3), calculate_north_support_load(&to_grid(after_three_cycles)));
+        assert_eq!(load_after(SAMPLE, 1_000_000_000), 64);
+    }
+}
+
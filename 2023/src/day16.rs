@@ -2,116 +2,431 @@
 //
 // Part 1: Trace light beam through mirrors and count energized tiles
 // Part 2: Find optimal entry point for maximum energized tiles
+//
+// Part 2 cuts the beam's paths into straight segments between mirrors and splitters once,
+// then each entry point only hops between segments instead of walking every tile again.
 
 use rayon::prelude::*;
+use rustc_hash::FxHashMap as HashMap;
+use Dir::*;
+
+/// A beam direction. Each is a distinct bit, so a tile can record every direction light
+/// has crossed it in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum Dir {
+    North = 1,
+    East = 2,
+    South = 4,
+    West = 8,
+}
+
+/// The directions a beam leaves each kind of tile in, indexed by tile kind and then by the
+/// direction it entered heading (`Dir::index` order).
+const REFLECTIONS: [[&[Dir]; 4]; 5] = [
+    // '.' lets the beam pass
+    [&[North], &[East], &[South], &[West]],
+    // '|' splits beams hitting it side-on
+    [&[North], &[North, South], &[South], &[North, South]],
+    // '-' splits beams hitting it side-on
+    [&[East, West], &[East], &[East, West], &[West]],
+    // '/' turns the beam
+    [&[East], &[North], &[West], &[South]],
+    // '\\' turns the beam
+    [&[West], &[South], &[East], &[North]],
+];
+
+impl Dir {
+    /// The direction's bit in a tile's light mask.
+    pub fn bit(self) -> u8 {
+        self as u8
+    }
 
-const NORTH: u8 = 1;
-const EAST: u8 = 2;
-const SOUTH: u8 = 4;
-const WEST: u8 = 8;
+    /// The direction's position among North, East, South, West.
+    fn index(self) -> usize {
+        (self as u8).trailing_zeros() as usize
+    }
+
+    /// The `(dx, dy)` step for this direction.
+    fn offset(self) -> (i64, i64) {
+        match self {
+            North => (0, -1),
+            East => (1, 0),
+            South => (0, 1),
+            West => (-1, 0),
+        }
+    }
+
+    /// The directions a beam leaves `tile` in after entering it heading this way. Mirrors
+    /// turn the beam, splitters hit side-on split it in two, and empty space lets it pass.
+    /// Unknown tiles absorb the beam.
+    pub fn reflect(self, tile: u8) -> &'static [Dir] {
+        let kind = match tile {
+            b'.' => 0,
+            b'|' => 1,
+            b'-' => 2,
+            b'/' => 3,
+            b'\\' => 4,
+            _ => return &[],
+        };
+        REFLECTIONS[kind][self.index()]
+    }
+}
 
 #[aoc(day16, part1)]
 pub fn part1(input: &str) -> usize {
     let grid = parse_input(input);
-    energize_count(&grid, 0, 0, EAST)
+    energize_count(&grid, 0, 0, East)
 }
 
-fn parse_input(input: &str) -> Vec<Vec<char>> {
-    input
-        .lines()
-        .map(|line| line.trim().chars().collect())
-        .filter(|v: &Vec<char>| !v.is_empty())
-        .collect()
+/// The contraption's tiles, stored row by row in one flat buffer.
+pub struct Grid {
+    pub width: usize,
+    pub height: usize,
+    tiles: Vec<u8>,
+}
+
+impl Grid {
+    /// The tile at `(x, y)`, or `None` outside the grid.
+    pub fn get(&self, x: i64, y: i64) -> Option<u8> {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return None;
+        }
+        self.tiles.get(y as usize * self.width + x as usize).copied()
+    }
+}
+
+/// Parses the contraption into a grid of tiles, skipping blank lines.
+pub fn parse_input(input: &str) -> Grid {
+    let rows: Vec<&[u8]> = input.lines().map(|line| line.trim().as_bytes()).filter(|row| !row.is_empty()).collect();
+
+    Grid {
+        width: rows.first().map_or(0, |row| row.len()),
+        height: rows.len(),
+        tiles: rows.concat(),
+    }
+}
+
+fn energize_count(grid: &Grid, start_x: i64, start_y: i64, start_dir: Dir) -> usize {
+    energized_tiles(grid, (start_x, start_y, start_dir)).iter().flatten().filter(|&&dirs| dirs != 0).count()
 }
 
-fn energize_count(grid: &Vec<Vec<char>>, start_x: i64, start_y: i64, start_dir: u8) -> usize {
-    let (width, height) = (grid[0].len(), grid.len());
-    let mut total = 0;
-    let mut light = vec![vec![0u8; width]; height];
+/// Traces the beam entering at `(x, y)` heading in direction `dir`, returning for every tile
+/// the bits of the directions light crossed it in (0 for tiles that stay dark).
+pub fn energized_tiles(grid: &Grid, (start_x, start_y, start_dir): (i64, i64, Dir)) -> Vec<Vec<u8>> {
+    let mut light = vec![vec![0u8; grid.width]; grid.height];
     let mut queue = vec![(start_y, start_x, start_dir)];
 
-    while let Some((y, x, dir_bit)) = queue.pop() {
-        if x < 0 || y < 0 || x >= width as i64 || y >= height as i64 {
+    while let Some((y, x, dir)) = queue.pop() {
+        let Some(cell) = grid.get(x, y) else {
             continue;
-        }
+        };
         let (yi, xi) = (y as usize, x as usize);
 
-        if light[yi][xi] & dir_bit != 0 {
+        if light[yi][xi] & dir.bit() != 0 {
             continue;
         }
 
-        light[yi][xi] |= dir_bit;
-        if light[yi][xi] == dir_bit {
-            total += 1;
+        light[yi][xi] |= dir.bit();
+
+        for &next in dir.reflect(cell) {
+            let (dx, dy) = next.offset();
+            queue.push((y + dy, x + dx, next));
         }
+    }
 
-        let cell = grid[yi][xi];
-        match dir_bit {
-            NORTH => match cell {
-                '.' | '|' => queue.push((y - 1, x, NORTH)),
-                '/' => queue.push((y, x + 1, EAST)),
-                '\\' => queue.push((y, x - 1, WEST)),
-                '-' => {
-                    queue.push((y, x + 1, EAST));
-                    queue.push((y, x - 1, WEST));
-                }
-                _ => (),
-            },
-            SOUTH => match cell {
-                '.' | '|' => queue.push((y + 1, x, SOUTH)),
-                '/' => queue.push((y, x - 1, WEST)),
-                '\\' => queue.push((y, x + 1, EAST)),
-                '-' => {
-                    queue.push((y, x + 1, EAST));
-                    queue.push((y, x - 1, WEST));
-                }
-                _ => (),
-            },
-            EAST => match cell {
-                '.' | '-' => queue.push((y, x + 1, EAST)),
-                '/' => queue.push((y - 1, x, NORTH)),
-                '\\' => queue.push((y + 1, x, SOUTH)),
-                '|' => {
-                    queue.push((y - 1, x, NORTH));
-                    queue.push((y + 1, x, SOUTH));
+    light
+}
+
+/// The `(x, y)` position of every tile energized by the beam entering at `(start_x, start_y)`
+/// heading in `start_dir`, in reading order.
+pub fn energized_positions(input: &str, start_x: i64, start_y: i64, start_dir: Dir) -> Vec<(usize, usize)> {
+    let grid = parse_input(input);
+    let light = energized_tiles(&grid, (start_x, start_y, start_dir));
+
+    light
+        .iter()
+        .enumerate()
+        .flat_map(|(y, row)| row.iter().enumerate().filter(|&(_, &dirs)| dirs != 0).map(move |(x, _)| (x, y)))
+        .collect()
+}
+
+/// A straight run of tiles the beam crosses from one state until it is turned, split, or
+/// leaves the grid.
+struct Segment {
+    // The tiles crossed, as flat indices `start`, `start + stride`, ... (`len` of them)
+    start: usize,
+    stride: isize,
+    len: usize,
+    // The segments the beam continues into afterwards
+    next: Vec<usize>,
+}
+
+/// The beam's paths cut into segments between mirrors and splitters, shared by every entry
+/// point so each path is only walked once.
+struct Segments {
+    segments: Vec<Segment>,
+    // Segment id for each `(tile, direction)` state that starts one
+    ids: HashMap<(i64, i64, Dir), usize>,
+}
+
+impl Segments {
+    /// Walks every segment reachable from the given entry states.
+    fn new(grid: &Grid, entries: &[(i64, i64, Dir)]) -> Self {
+        let width = grid.width;
+        let in_bounds = |x: i64, y: i64| grid.get(x, y).is_some();
+        let mut segments = Segments { segments: Vec::new(), ids: HashMap::default() };
+        let mut pending: Vec<(i64, i64, Dir)> = entries.iter().copied().filter(|&(x, y, _)| in_bounds(x, y)).collect();
+        let mut links = Vec::new();
+
+        while let Some(state @ (mut x, mut y, dir)) = pending.pop() {
+            if segments.ids.contains_key(&state) {
+                continue;
+            }
+
+            // Follow the beam while it passes straight through tiles
+            let (dx, dy) = dir.offset();
+            let start = y as usize * width + x as usize;
+            let mut len = 1;
+            let mut exits = Vec::new();
+            loop {
+                let out = dir.reflect(grid.get(x, y).unwrap_or(b'.'));
+                if out != [dir] {
+                    exits.extend(out.iter().map(|&next| (x + next.offset().0, y + next.offset().1, next)));
+                    break;
                 }
-                _ => (),
-            },
-            WEST => match cell {
-                '.' | '-' => queue.push((y, x - 1, WEST)),
-                '/' => queue.push((y + 1, x, SOUTH)),
-                '\\' => queue.push((y - 1, x, NORTH)),
-                '|' => {
-                    queue.push((y - 1, x, NORTH));
-                    queue.push((y + 1, x, SOUTH));
+                if !in_bounds(x + dx, y + dy) {
+                    break;
                 }
-                _ => (),
-            },
-            _ => (),
+                (x, y) = (x + dx, y + dy);
+                len += 1;
+            }
+
+            let exits: Vec<_> = exits.into_iter().filter(|&(x, y, _)| in_bounds(x, y)).collect();
+            pending.extend(&exits);
+            links.push(exits);
+            segments.ids.insert(state, segments.segments.len());
+            segments.segments.push(Segment { start, stride: dy as isize * width as isize + dx as isize, len, next: Vec::new() });
+        }
+
+        // Every exit was queued, so it now has a segment of its own
+        for (segment, exits) in segments.segments.iter_mut().zip(links) {
+            segment.next = exits.iter().map(|exit| segments.ids[exit]).collect();
         }
+
+        segments
     }
 
-    total
+    /// Counts the tiles energized by the beam entering at the given state.
+    fn energize_count(&self, entry: (i64, i64, Dir), tiles: usize) -> usize {
+        let Some(&first) = self.ids.get(&entry) else {
+            return 0;
+        };
+
+        let mut energized = vec![0u64; tiles.div_ceil(64)];
+        let mut visited = vec![false; self.segments.len()];
+        let mut queue = vec![first];
+        visited[first] = true;
+
+        while let Some(id) = queue.pop() {
+            let segment = &self.segments[id];
+            for k in 0..segment.len {
+                let tile = segment.start.wrapping_add_signed(segment.stride * k as isize);
+                energized[tile / 64] |= 1 << (tile % 64);
+            }
+            for &next in &segment.next {
+                if !visited[next] {
+                    visited[next] = true;
+                    queue.push(next);
+                }
+            }
+        }
+
+        energized.iter().map(|bits| bits.count_ones() as usize).sum()
+    }
 }
 
 #[aoc(day16, part2)]
 pub fn part2(input: &str) -> usize {
     let grid = parse_input(input);
-    let (width, height) = (grid[0].len() as i64, grid.len() as i64);
+    best_entry(&grid).0
+}
+
+/// Every way for the beam to enter from the edge: each edge tile, heading into the grid.
+fn edge_positions(grid: &Grid) -> Vec<(i64, i64, Dir)> {
+    let (width, height) = (grid.width as i64, grid.height as i64);
 
     let mut edge_positions = Vec::new();
     for x in 0..width {
-        edge_positions.push((x, 0, SOUTH));
-        edge_positions.push((x, height - 1, NORTH));
+        edge_positions.push((x, 0, South));
+        edge_positions.push((x, height - 1, North));
     }
     for y in 0..height {
-        edge_positions.push((0, y, EAST));
-        edge_positions.push((width - 1, y, WEST));
+        edge_positions.push((0, y, East));
+        edge_positions.push((width - 1, y, West));
     }
 
     edge_positions
+}
+
+/// Finds the edge entry that energizes the most tiles, returning that count and the entry
+/// as `(x, y, direction)`. Ties go to the first entry in `edge_positions` order.
+pub fn best_entry(grid: &Grid) -> (usize, (i64, i64, Dir)) {
+    best_of(grid, &edge_positions(grid))
+}
+
+/// The most tiles energized by a beam entering at any of the `candidates`, each given as
+/// `(x, y, direction)`. Unlike part 2, candidates may start anywhere, including inside the
+/// grid; ones outside it energize nothing.
+pub fn max_energization(input: &str, candidates: &[(i64, i64, Dir)]) -> usize {
+    best_of(&parse_input(input), candidates).0
+}
+
+/// Finds the entry that energizes the most tiles, preferring earlier entries on ties.
+fn best_of(grid: &Grid, entries: &[(i64, i64, Dir)]) -> (usize, (i64, i64, Dir)) {
+    let segments = Segments::new(grid, entries);
+    let tiles = grid.width * grid.height;
+
+    entries
         .par_iter()
-        .map(|&(x, y, dir)| energize_count(&grid, x, y, dir))
+        .enumerate()
+        .map(|(i, &entry)| (segments.energize_count(entry, tiles), std::cmp::Reverse(i), entry))
         .max()
-        .unwrap_or(0)
+        .map_or((0, (0, 0, East)), |(count, _, entry)| (count, entry))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{random_grid, rng};
+
+    const SAMPLE: &str = r".|...\....
+|.-.\.....
+.....|-...
+........|.
+..........
+.........\
+..../.\\..
+.-.-/..|..
+.|....-|.\
+..//.|....
+";
+
+    /// A random contraption where roughly one tile in `sparsity / 4` is a mirror or splitter.
+    fn random_contraption(seed: &mut u64, height: usize, width: usize, sparsity: usize) -> String {
+        let mut tiles = br"|-/\".to_vec();
+        tiles.resize(sparsity, b'.');
+        random_grid(seed, height, width, &tiles)
+    }
+
+    /// The most tiles energized from any edge, tracing every entry from scratch.
+    fn naive_best(grid: &Grid) -> usize {
+        edge_positions(grid).into_iter().map(|entry| energized(grid, entry)).max().unwrap_or(0)
+    }
+
+    fn energized(grid: &Grid, entry: (i64, i64, Dir)) -> usize {
+        energized_tiles(grid, entry).iter().flatten().filter(|&&dirs| dirs != 0).count()
+    }
+
+    #[test]
+    fn sample_answers() {
+        assert_eq!(part1(SAMPLE), 46);
+        assert_eq!(part2(SAMPLE), 51);
+    }
+
+    #[test]
+    fn best_entry_reproduces_part2() {
+        let grid = parse_input(SAMPLE);
+        let (count, entry) = best_entry(&grid);
+        assert_eq!((count, entry), (51, (3, 0, South)));
+        assert_eq!(count, part2(SAMPLE));
+        assert_eq!(energized(&grid, entry), 51);
+        assert_eq!(energized(&grid, (0, 0, East)), part1(SAMPLE));
+    }
+
+    #[test]
+    fn segments_match_walking_every_entry() {
+        let mut seed = 2024;
+        for _ in 0..300 {
+            let (height, width) = (1 + rng(&mut seed) as usize % 25, 1 + rng(&mut seed) as usize % 25);
+            let input = random_contraption(&mut seed, height, width, 12);
+            assert_eq!(part2(&input), naive_best(&parse_input(&input)), "{input}");
+        }
+
+        // Sparse mirrors make long segments
+        let input = random_contraption(&mut seed, 110, 110, 40);
+        assert_eq!(part2(&input), naive_best(&parse_input(&input)));
+        assert_eq!(naive_best(&parse_input(SAMPLE)), 51);
+    }
+
+    #[test]
+    fn flat_grid_lookups() {
+        let grid = parse_input(SAMPLE);
+        assert_eq!((grid.width, grid.height), (10, 10));
+        assert_eq!(grid.get(1, 0), Some(b'|'));
+        assert_eq!(grid.get(9, 5), Some(b'\\'));
+        assert_eq!(grid.get(9, 9), Some(b'.'));
+        assert_eq!(grid.get(-1, 0), None);
+        assert_eq!(grid.get(10, 0), None);
+        assert_eq!(grid.get(0, 10), None);
+
+        let padded = format!("\n{}\n\n", SAMPLE.replace('\n', "\r\n"));
+        assert_eq!(part1(&padded), 46);
+        assert_eq!(part2(&padded), 51);
+        assert_eq!(part2(""), 0);
+    }
+
+    #[test]
+    fn energized_positions_of_the_sample() {
+        let positions = energized_positions(SAMPLE, 0, 0, East);
+        assert_eq!(positions.len(), 46);
+        assert_eq!(positions.len(), part1(SAMPLE));
+        assert_eq!(positions[..5], [(0, 0), (1, 0), (2, 0), (3, 0), (4, 0)]);
+        assert!(positions.contains(&(1, 9)) && !positions.contains(&(0, 9)));
+        assert_eq!(energized_positions(SAMPLE, 3, 0, South).len(), 51);
+    }
+
+    #[test]
+    fn direction_table_matches_the_mirror_rules() {
+        for dir in [North, East, South, West] {
+            assert_eq!(dir.reflect(b'.'), [dir]);
+            assert_eq!(dir.reflect(b'#'), []);
+        }
+
+        let turns = [(North, East), (East, North), (South, West), (West, South)];
+        for (dir, turned) in turns {
+            assert_eq!(dir.reflect(b'/'), [turned]);
+        }
+        let turns = [(North, West), (West, North), (South, East), (East, South)];
+        for (dir, turned) in turns {
+            assert_eq!(dir.reflect(b'\\'), [turned]);
+        }
+
+        assert_eq!(North.reflect(b'|'), [North]);
+        assert_eq!(East.reflect(b'|'), [North, South]);
+        assert_eq!(West.reflect(b'-'), [West]);
+        assert_eq!(South.reflect(b'-'), [East, West]);
+
+        let bits = [North, East, South, West].map(Dir::bit);
+        assert_eq!(bits.iter().fold(0, |all, bit| all | bit).count_ones(), 4);
+    }
+
+    #[test]
+    fn max_energization_from_any_candidates() {
+        assert_eq!(max_energization(SAMPLE, &[(0, 0, East)]), part1(SAMPLE));
+        assert_eq!(max_energization(SAMPLE, &[]), 0);
+        assert_eq!(max_energization(SAMPLE, &[(-1, 0, East), (0, 0, East)]), 46);
+        assert_eq!(max_energization(SAMPLE, &edge_positions(&parse_input(SAMPLE))), part2(SAMPLE));
+
+        let mut seed = 9;
+        for _ in 0..100 {
+            let input = random_contraption(&mut seed, 12, 13, 12);
+            let grid = parse_input(&input);
+            let candidates: Vec<_> =
+                (0..20).map(|k| ((k * 7 % 13) as i64, (k * 5 % 12) as i64, [North, East, South, West][k % 4])).collect();
+            let best = candidates.iter().map(|&entry| energized(&grid, entry)).max().unwrap();
+            assert_eq!(max_energization(&input, &candidates), best, "{input}");
+        }
+    }
 }
+
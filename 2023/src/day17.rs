@@ -2,412 +2,380 @@
 //
 // Find minimum heat loss path from top-left to bottom-right with movement constraints.
 // Part 1: Cannot move more than 3 consecutive blocks in same direction
-// Part 2: Ultra crucibles must move 4-10 blocks before turning (Dijkstra's algorithm)
-
-mod part1_impl {
-    use std::collections::BinaryHeap;
-    use rustc_hash::FxHashSet as HashSet;
-
-    /// Represents the possible directions of movement.
-    #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
-    pub enum Direction {
-        Up,
-        Down,
-        Left,
-        Right,
-    }
+// Part 2: Ultra crucibles must move 4-10 blocks before turning
+//
+// Both parts share one Dijkstra solver parameterized by the minimum and maximum run length.
+// Heat loss per block is a single digit, so a bucket queue replaces the binary heap.
 
-    impl Direction {
-        /// Applies this direction to the given point, returning the new point.
-        /// Calculates the next point based on the current direction.
-        pub fn apply(self, point: Point) -> Option<Point> {
-            match self {
-                Direction::Up => point.y.checked_sub(1).map(|y| Point { x: point.x, y }),
-                Direction::Down => Some(Point { x: point.x, y: point.y + 1 }),
-                Direction::Left => point.x.checked_sub(1).map(|x| Point { x, y: point.y }),
-                Direction::Right => Some(Point { x: point.x + 1, y: point.y }),
-            }
+use crate::dijkstra::BucketQueue;
+use rustc_hash::FxHashMap as HashMap;
+
+/// Represents the possible directions of movement.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    /// Applies this direction to the given point, returning the new point.
+    /// Calculates the next point based on the current direction.
+    fn apply(self, point: Point) -> Option<Point> {
+        match self {
+            Direction::Up => point.y.checked_sub(1).map(|y| Point { x: point.x, y }),
+            Direction::Down => Some(Point { x: point.x, y: point.y + 1 }),
+            Direction::Left => point.x.checked_sub(1).map(|x| Point { x, y: point.y }),
+            Direction::Right => Some(Point { x: point.x + 1, y: point.y }),
         }
     }
+}
 
-    /// Represents a state in the pathfinding algorithm.
-    #[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
-    struct State {
-        current: Point,
-        direction: Direction,
-        direction_count: usize,
-    }
+/// Represents a state in the pathfinding algorithm.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+struct State {
+    current: Point,
+    direction: Direction,
+    direction_count: usize,
+}
 
-    /// Represents a point on the grid.
-    #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
-    pub struct Point {
-        pub x: usize,
-        pub y: usize,
-    }
+/// Represents a point on the grid.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Point {
+    pub x: usize,
+    pub y: usize,
+}
 
-    /// Represents a path including its current state, cost, and heuristic.
-    /// 'cost' is the accumulated heat loss so far, and 'heuristic' is the estimated cost to reach the goal.
-    #[derive(Copy, Clone, Eq, PartialEq, Debug)]
-    struct Path {
-        state: State,
-        cost: usize,
-        heuristic: usize,
-    }
+/// Represents a path including its current state and cost.
+/// 'cost' is the accumulated heat loss so far.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+struct Path {
+    state: State,
+    cost: usize,
+}
 
-    impl Path {
-        /// Generates the next possible paths from the current state, taking into account the movement constraints and avoiding backtracking.
-        fn next_paths(&self, grid: &[Vec<usize>], goal: Point) -> Vec<Path> {
-            let mut paths = Vec::with_capacity(3); // Capacity reduced to 3 as backtracking is not allowed
-            let directions = match self.state.direction {
+impl Path {
+    /// Generates the next possible paths from the current state, taking into account the movement constraints and avoiding backtracking.
+    /// Until `min_steps` blocks have been travelled the crucible may only continue straight ahead.
+    fn next_paths(&self, grid: &[Vec<u8>], min_steps: usize, max_steps: usize) -> Vec<Path> {
+        let mut paths = Vec::with_capacity(3); // Capacity reduced to 3 as backtracking is not allowed
+        let directions: &[Direction] = if self.state.direction_count < min_steps {
+            // Not allowed to turn yet: only the current direction is valid.
+            &[self.state.direction]
+        } else {
+            match self.state.direction {
                 // Choose directions that don't lead back to the previous block.
                 Direction::Up => &[Direction::Up, Direction::Left, Direction::Right],
                 Direction::Down => &[Direction::Down, Direction::Left, Direction::Right],
                 Direction::Left => &[Direction::Left, Direction::Up, Direction::Down],
                 Direction::Right => &[Direction::Right, Direction::Up, Direction::Down],
-            };
-    
-            for &direction in directions {
-                if let Some(next_point) = direction.apply(self.state.current) {
-                    let new_direction_count = if direction == self.state.direction {
-                        self.state.direction_count + 1
-                    } else {
-                        1
-                    };
-    
-                    // Ensuring the crucible doesn't move more than three blocks in the same direction.
-                    if new_direction_count <= 3 {
-                        if let Some(cost) = get_value(grid, next_point) {
-                            let total_cost = self.cost + cost;
-                            let heuristic = manhattan_distance(next_point, goal);
-                            paths.push(Path {
-                                state: State {
-                                    current: next_point,
-                                    direction,
-                                    direction_count: new_direction_count,
-                                },
-                                cost: total_cost,
-                                heuristic,
-                            });
-                        }
+            }
+        };
+
+        for &direction in directions {
+            if let Some(next_point) = direction.apply(self.state.current) {
+                let new_direction_count = if direction == self.state.direction {
+                    self.state.direction_count + 1
+                } else {
+                    1
+                };
+
+                // Ensuring the crucible doesn't move more than `max_steps` blocks in the same direction.
+                if new_direction_count <= max_steps {
+                    if let Some(cost) = get_value(grid, next_point) {
+                        let total_cost = self.cost + cost;
+                        paths.push(Path {
+                            state: State {
+                                current: next_point,
+                                direction,
+                                direction_count: new_direction_count,
+                            },
+                            cost: total_cost,
+                        });
                     }
                 }
             }
-    
-            paths
         }
-    }
-    
-    impl Ord for Path {
-        /// Compares paths for ordering in the priority queue.
-        /// Paths with lower total (cost + heuristic) are considered higher priority.
-        fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-            (other.cost + other.heuristic).cmp(&(self.cost + self.heuristic))
-                .then_with(|| self.state.current.x.cmp(&other.state.current.x))
-                .then_with(|| self.state.current.y.cmp(&other.state.current.y))
-        }
-    }
 
-    impl PartialOrd for Path {
-        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-            Some(self.cmp(other))
-        }
+        paths
     }
+}
 
-    /// Parses the input string into a 2D grid of heat loss values.
-    fn parse_input(input: &str) -> Vec<Vec<usize>> {
-        input
-            .lines()
-            .map(|line| line.chars().map(|c| c.to_digit(10).unwrap() as usize).collect())
-            .collect()
-    }
+/// Parses the input string into a 2D grid of heat loss values.
+///
+/// Surrounding whitespace and blank lines are ignored. Fails if the grid is empty, a cell
+/// is not a digit, or the rows differ in width.
+fn parse_input(input: &str) -> Result<Vec<Vec<u8>>, String> {
+    let grid = input
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .enumerate()
+        .map(|(row, line)| {
+            line.chars()
+                .map(|c| {
+                    c.to_digit(10)
+                        .map(|digit| digit as u8)
+                        .ok_or_else(|| format!("invalid heat loss {c:?} in row {row}"))
+                })
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .collect::<Result<Vec<_>, _>>()?;
 
-    /// Retrieves the heat loss value for a given point in the grid.
-    fn get_value(grid: &[Vec<usize>], point: Point) -> Option<usize> {
-        grid.get(point.y).and_then(|row| row.get(point.x).copied())
+    let width = grid.first().map(Vec::len).ok_or("empty grid")?;
+    if let Some(row) = grid.iter().position(|row| row.len() != width) {
+        return Err(format!("row {row} has width {}, expected {width}", grid[row].len()));
     }
 
-    /// Calculates the Manhattan distance between two points.
-    /// Used as a heuristic for estimating the remaining cost to the goal.
-    fn manhattan_distance(p1: Point, p2: Point) -> usize {
-        (p1.x as isize - p2.x as isize).unsigned_abs() + (p1.y as isize - p2.y as isize).unsigned_abs()
+    Ok(grid)
+}
+
+/// Retrieves the heat loss value for a given point in the grid.
+fn get_value(grid: &[Vec<u8>], point: Point) -> Option<usize> {
+    grid.get(point.y).and_then(|row| row.get(point.x)).map(|&cost| cost as usize)
+}
+
+/// Runs Dijkstra's algorithm, recording for each reached state its best cost and predecessor state.
+///
+/// The crucible must travel at least `min_steps` blocks before turning (or stopping at the
+/// goal) and at most `max_steps` blocks in a straight line. Returns the cost and final state
+/// at the goal, or `None` if the goal is unreachable.
+fn search(
+    grid: &[Vec<u8>],
+    min_steps: usize,
+    max_steps: usize,
+    best: &mut HashMap<State, (usize, State)>,
+) -> Option<(usize, State)> {
+    let grid_height = grid.len();
+    let grid_width = grid.first().map_or(0, Vec::len);
+    if grid_width == 0 {
+        return None;
     }
+    let goal = Point { x: grid_width - 1, y: grid_height - 1 };
+    let mut queue = BucketQueue::new(); // Priority queue of states keyed on heat loss.
 
-    /// Solves the least heat loss problem using a modified A* algorithm.
-    pub fn least_heat_loss(input: &str) -> usize {
-        let grid = parse_input(input);
-        let grid_height = grid.len();
-        let grid_width = grid.first().map_or(0, Vec::len);
-        let mut visited = HashSet::default(); // Tracks visited states to avoid revisiting.
-    
-        // Define the first starting state (moving to the right)
-        let start_state_right = State {
-            current: Point { x: 0, y: 0 },
-            direction: Direction::Right,
-            direction_count: 0,
-        };
-        visited.insert(start_state_right); // Insert the first starting state
-    
-        // Define the second starting state (moving down)
-        let start_state_down = State {
+    // Start in the top-left corner, moving either right or down.
+    // Start states are their own predecessor, which terminates path reconstruction.
+    for direction in [Direction::Right, Direction::Down] {
+        let state = State {
             current: Point { x: 0, y: 0 },
-            direction: Direction::Down,
+            direction,
             direction_count: 0,
         };
-        visited.insert(start_state_down); // Insert the second starting state
-    
-        let mut heap = BinaryHeap::new(); // Priority queue for exploring paths.
-    
-        // Push both starting states into the heap
-        heap.push(Path {
-            state: start_state_right,
-            cost: 0,
-            heuristic: manhattan_distance(start_state_right.current, Point { x: grid_width - 1, y: grid_height - 1 }),
-        });
-        heap.push(Path {
-            state: start_state_down,
-            cost: 0,
-            heuristic: manhattan_distance(start_state_down.current, Point { x: grid_width - 1, y: grid_height - 1 }),
-        });
-    
-        while let Some(current_path) = heap.pop() {
-            if current_path.state.current.x == grid_width - 1
-                && current_path.state.current.y == grid_height - 1
-            {
-                // Termination condition: Reached the goal.
-                return current_path.cost;
+        best.insert(state, (0, state));
+        queue.push(0, state);
+    }
+
+    while let Some((cost, state)) = queue.pop() {
+        let current_path = Path { state, cost };
+
+        // Skip stale entries: a cheaper path to this state has already been queued.
+        if best.get(&current_path.state).is_some_and(|&(cost, _)| current_path.cost > cost) {
+            continue;
+        }
+
+        if current_path.state.current == goal && current_path.state.direction_count >= min_steps {
+            // Termination condition: Reached the goal.
+            return Some((current_path.cost, current_path.state));
+        }
+
+        // Generate and explore next possible paths.
+        for path in current_path.next_paths(grid, min_steps, max_steps) {
+            // Only keep paths that improve on the best known cost for their state.
+            let known = best.entry(path.state).or_insert((usize::MAX, current_path.state));
+            if path.cost < known.0 {
+                *known = (path.cost, current_path.state);
+                queue.push(path.cost, path.state);
             }
-    
-            // Generate and explore next possible paths.
-            current_path.next_paths(&grid, Point { x: grid_width - 1, y: grid_height - 1 })
-                .into_iter()
-                .filter(|path| visited.insert(path.state))
-                .for_each(|path| heap.push(path));
         }
-    
-        usize::MAX // No valid path found.
     }
+
+    None // No valid path found.
 }
 
-#[aoc(day17, part1)]
-pub fn part1(input: &str) -> usize {
-    part1_impl::least_heat_loss(input)
+/// Solves the least heat loss problem over an already-parsed grid.
+///
+/// Each cell holds the heat loss of entering that block, expected to be in `0..=9`.
+/// Rows should share the same width. Returns `usize::MAX` if the goal is unreachable
+/// (including for an empty grid).
+pub fn solve(grid: &[Vec<u8>], min_steps: usize, max_steps: usize) -> usize {
+    let mut best = HashMap::default();
+    search(grid, min_steps, max_steps, &mut best).map_or(usize::MAX, |(cost, _)| cost)
 }
 
-mod part2_impl {
-    use std::collections::BinaryHeap;
-    use rustc_hash::FxHashSet as HashSet;
-
-    /// Represents the possible directions of movement.
-    #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
-    pub enum Direction {
-        Up,
-        Down,
-        Left,
-        Right,
-    }
+/// Solves the least heat loss problem and also returns the route taken.
+///
+/// The route lists every block visited, starting at `(0, 0)` and ending at the bottom-right
+/// corner. The heat loss of every block after the first sums to the returned cost.
+/// An unreachable goal yields `(usize::MAX, vec![])`.
+pub fn least_heat_loss_with_path(input: &str, min_steps: usize, max_steps: usize) -> Result<(usize, Vec<Point>), String> {
+    let grid = parse_input(input)?;
+    let mut best = HashMap::default();
+    let Some((cost, mut state)) = search(&grid, min_steps, max_steps, &mut best) else {
+        return Ok((usize::MAX, Vec::new()));
+    };
 
-    impl Direction {
-        /// Applies this direction to the given point, returning the new point.
-        /// Calculates the next point based on the current direction.
-        pub fn apply(self, point: Point) -> Option<Point> {
-            match self {
-                Direction::Up => point.y.checked_sub(1).map(|y| Point { x: point.x, y }),
-                Direction::Down => Some(Point { x: point.x, y: point.y + 1 }),
-                Direction::Left => point.x.checked_sub(1).map(|x| Point { x, y: point.y }),
-                Direction::Right => Some(Point { x: point.x + 1, y: point.y }),
-            }
+    // Walk predecessors back to a start state, then reverse into start-to-goal order.
+    let mut path = vec![state.current];
+    while let Some(&(_, previous)) = best.get(&state) {
+        if previous == state {
+            break;
         }
+        state = previous;
+        path.push(state.current);
     }
+    path.reverse();
 
-    /// Represents a state in the pathfinding algorithm.
-    #[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
-    struct State {
-        current: Point,
-        direction: Direction,
-        direction_count: usize,
-    }
+    Ok((cost, path))
+}
 
-    /// Represents a point on the grid.
-    #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
-    pub struct Point {
-        pub x: usize,
-        pub y: usize,
-    }
+#[aoc(day17, part1)]
+pub fn part1(input: &str) -> Result<usize, String> {
+    Ok(solve(&parse_input(input)?, 1, 3))
+}
+
+#[aoc(day17, part2)]
+pub fn part2(input: &str) -> Result<usize, String> {
+    Ok(solve(&parse_input(input)?, 4, 10))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::rng;
+
+    const SAMPLE: &str = "\
+2413432311323
+3215453535623
+3255245654254
+3446585845452
+4546657867536
+1438598798454
+4457876987766
+3637877979653
+4654967986887
+4564679986453
+1224686865563
+2546548887735
+4322674655533
+";
 
-    /// Represents a path including its current state, cost, and heuristic.
-    /// 'cost' is the accumulated heat loss so far, and 'heuristic' is the estimated cost to reach the goal.
-    #[derive(Copy, Clone, Eq, PartialEq, Debug)]
-    struct Path {
-        state: State,
-        cost: usize,
-        heuristic: usize,
+    #[test]
+    fn sample_answers() {
+        assert_eq!(part1(SAMPLE), Ok(102));
+        assert_eq!(part2(SAMPLE), Ok(94));
     }
 
-    impl Path {
-        /// Generates the next possible paths from the current state, taking into account the movement constraints and avoiding backtracking.
-        fn next_paths(&self, grid: &[Vec<usize>], goal: Point) -> Vec<Path> {
-            let min_steps = 4;
-            let max_steps = 10;
-            let mut paths = Vec::with_capacity(3); // Capacity reduced to 3 as backtracking is not allowed
-    
-            if self.state.direction_count < min_steps {
-                let directions = &[self.state.direction]; // Allow only the current direction
-                for &direction in directions {
-                    if let Some(next_point) = direction.apply(self.state.current) {
-                        let new_direction_count = self.state.direction_count + 1;
-    
-                        if let Some(cost) = get_value(grid, next_point) {
-                            let total_cost = self.cost + cost;
-                            let heuristic = manhattan_distance(next_point, goal);
-                            paths.push(Path {
-                                state: State {
-                                    current: next_point,
-                                    direction,
-                                    direction_count: new_direction_count,
-                                },
-                                cost: total_cost,
-                                heuristic,
-                            });
-                        }
-                    }
-                }
-            } else {
-                let directions = match self.state.direction {
-                    // Choose directions that don't lead back to the previous block.
-                    Direction::Up => &[Direction::Up, Direction::Left, Direction::Right],
-                    Direction::Down => &[Direction::Down, Direction::Left, Direction::Right],
-                    Direction::Left => &[Direction::Left, Direction::Up, Direction::Down],
-                    Direction::Right => &[Direction::Right, Direction::Up, Direction::Down],
-                };
-    
-                for &direction in directions {
-                    if let Some(next_point) = direction.apply(self.state.current) {
-                        let new_direction_count = if direction == self.state.direction {
-                            self.state.direction_count + 1
-                        } else {
-                            1
-                        };
-    
-                        // Ensuring the crucible doesn't move more than three blocks in the same direction.
-                        if new_direction_count <= max_steps {
-                            if let Some(cost) = get_value(grid, next_point) {
-                                let total_cost = self.cost + cost;
-                                let heuristic = manhattan_distance(next_point, goal);
-                                let new_direction = direction; // Create a separate variable for direction
-                                paths.push(Path {
-                                    state: State {
-                                        current: next_point,
-                                        direction: new_direction,
-                                        direction_count: new_direction_count,
-                                    },
-                                    cost: total_cost,
-                                    heuristic,
-                                });
-                            }
-                        }
-                    }
-                }
-            }
-    
-            paths
-        }
+    #[test]
+    fn parts_share_one_solver() {
+        let grid = parse_input(SAMPLE).unwrap();
+        assert_eq!(solve(&grid, 1, 3), 102);
+        assert_eq!(solve(&grid, 4, 10), 94);
+
+        // The ultra crucible can't stop at the goal before moving 4 blocks in a line.
+        let grid = parse_input("111111111111\n999999999991\n999999999991\n999999999991\n999999999991\n").unwrap();
+        assert_eq!(solve(&grid, 4, 10), 71);
     }
-    
-    
-    impl Ord for Path {
-        /// Compares paths for ordering in the priority queue.
-        /// Paths with lower total (cost + heuristic) are considered higher priority.
-        fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-            (other.cost + other.heuristic).cmp(&(self.cost + self.heuristic))
-                .then_with(|| self.state.current.x.cmp(&other.state.current.x))
-                .then_with(|| self.state.current.y.cmp(&other.state.current.y))
+
+    // Checks that `path` is a valid route for the given run limits that loses `cost` heat.
+    fn assert_valid_route(input: &str, min_steps: usize, max_steps: usize, cost: usize, path: &[Point]) {
+        let grid = parse_input(input).unwrap();
+        let goal = Point { x: grid[0].len() - 1, y: grid.len() - 1 };
+        assert_eq!(path.first(), Some(&Point { x: 0, y: 0 }));
+        assert_eq!(path.last(), Some(&goal));
+        assert_eq!(path[1..].iter().map(|&point| get_value(&grid, point).unwrap()).sum::<usize>(), cost);
+
+        // Every step moves one block, and straight runs stay within the limits.
+        let steps: Vec<(isize, isize)> = path
+            .windows(2)
+            .map(|w| (w[1].x as isize - w[0].x as isize, w[1].y as isize - w[0].y as isize))
+            .collect();
+        assert!(steps.iter().all(|&(dx, dy)| dx.abs() + dy.abs() == 1));
+        for run in steps.chunk_by(|a, b| a == b) {
+            assert!((min_steps..=max_steps).contains(&run.len()), "run of {} in {path:?}", run.len());
         }
     }
 
-    impl PartialOrd for Path {
-        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-            Some(self.cmp(other))
+    #[test]
+    fn path_matches_cost_and_limits() {
+        for (min_steps, max_steps, expected) in [(1, 3, 102), (4, 10, 94)] {
+            let (cost, path) = least_heat_loss_with_path(SAMPLE, min_steps, max_steps).unwrap();
+            assert_eq!(cost, expected);
+            assert_valid_route(SAMPLE, min_steps, max_steps, cost, &path);
         }
     }
 
-    /// Parses the input string into a 2D grid of heat loss values.
-    fn parse_input(input: &str) -> Vec<Vec<usize>> {
-        input
-            .lines()
-            .map(|line| line.chars().map(|c| c.to_digit(10).unwrap() as usize).collect())
-            .collect()
+    #[test]
+    fn parsing_tolerates_blank_lines_and_rejects_bad_grids() {
+        assert_eq!(part1(&format!("{SAMPLE}\n\n")), Ok(102));
+        assert_eq!(part2(&format!("\n{SAMPLE}  \n")), Ok(94));
+        assert_eq!(parse_input("123\n12\n"), Err("row 1 has width 2, expected 3".to_string()));
+        assert_eq!(parse_input("1x\n"), Err("invalid heat loss 'x' in row 0".to_string()));
+        assert_eq!(parse_input("\n\n"), Err("empty grid".to_string()));
     }
 
-    /// Retrieves the heat loss value for a given point in the grid.
-    fn get_value(grid: &[Vec<usize>], point: Point) -> Option<usize> {
-        grid.get(point.y).and_then(|row| row.get(point.x).copied())
-    }
+    // A plain Dijkstra over a `BinaryHeap`, keeping the best cost per state, as an independent
+    // reference for the bucket queue. (The solver before it was A* with a Manhattan distance
+    // heuristic, which finds the same costs.) States aren't `Ord`, so the heap holds indices
+    // into `states`.
+    fn binary_heap_search(grid: &[Vec<u8>], min_steps: usize, max_steps: usize) -> usize {
+        use std::cmp::Reverse;
+        use std::collections::BinaryHeap;
 
-    /// Calculates the Manhattan distance between two points.
-    /// Used as a heuristic for estimating the remaining cost to the goal.
-    fn manhattan_distance(p1: Point, p2: Point) -> usize {
-        (p1.x as isize - p2.x as isize).unsigned_abs() + (p1.y as isize - p2.y as isize).unsigned_abs()
-    }
+        let goal = Point { x: grid[0].len() - 1, y: grid.len() - 1 };
+        let mut best = HashMap::default();
+        let mut states = Vec::new();
+        let mut heap = BinaryHeap::new();
+        for direction in [Direction::Right, Direction::Down] {
+            let state = State { current: Point { x: 0, y: 0 }, direction, direction_count: 0 };
+            best.insert(state, 0);
+            heap.push(Reverse((0, states.len())));
+            states.push(state);
+        }
 
-    /// Solves the least heat loss problem using a modified A* algorithm.
-    pub fn least_heat_loss(input: &str) -> usize {
-        let grid = parse_input(input);
-        let grid_height = grid.len();
-        let grid_width = grid.first().map_or(0, Vec::len);
-        let mut visited = HashSet::default(); // Tracks visited states to avoid revisiting.
-    
-        // Define the first starting state (moving to the right)
-        let start_state_right = State {
-            current: Point { x: 0, y: 0 },
-            direction: Direction::Right,
-            direction_count: 0,
-        };
-        visited.insert(start_state_right); // Insert the first starting state
-    
-        // Define the second starting state (moving down)
-        let start_state_down = State {
-            current: Point { x: 0, y: 0 },
-            direction: Direction::Down,
-            direction_count: 0,
-        };
-        visited.insert(start_state_down); // Insert the second starting state
-    
-        let mut heap = BinaryHeap::new(); // Priority queue for exploring paths.
-    
-        // Push both starting states into the heap
-        heap.push(Path {
-            state: start_state_right,
-            cost: 0,
-            heuristic: manhattan_distance(start_state_right.current, Point { x: grid_width - 1, y: grid_height - 1 }),
-        });
-        heap.push(Path {
-            state: start_state_down,
-            cost: 0,
-            heuristic: manhattan_distance(start_state_down.current, Point { x: grid_width - 1, y: grid_height - 1 }),
-        });
-    
-        while let Some(current_path) = heap.pop() {
-            if current_path.state.current.x == grid_width - 1
-                && current_path.state.current.y == grid_height - 1
-                && current_path.state.direction_count >= 4
-            {
-                // Termination condition: Reached the goal.
-                return current_path.cost;
+        while let Some(Reverse((cost, index))) = heap.pop() {
+            let state = states[index];
+            if best.get(&state).is_some_and(|&known| cost > known) {
+                continue;
+            }
+            if state.current == goal && state.direction_count >= min_steps {
+                return cost;
+            }
+            for path in (Path { state, cost }).next_paths(grid, min_steps, max_steps) {
+                let known = best.entry(path.state).or_insert(usize::MAX);
+                if path.cost < *known {
+                    *known = path.cost;
+                    heap.push(Reverse((path.cost, states.len())));
+                    states.push(path.state);
+                }
             }
-    
-            // Generate and explore next possible paths.
-            current_path.next_paths(&grid, Point { x: grid_width - 1, y: grid_height - 1 })
-                .into_iter()
-                .filter(|path| visited.insert(path.state))
-                .for_each(|path| heap.push(path));
         }
-    
-        usize::MAX // No valid path found.
+        usize::MAX
     }
-    
-}
 
-#[aoc(day17, part2)]
-pub fn part2(input: &str) -> usize {
-    part2_impl::least_heat_loss(input)
+    #[test]
+    fn bucket_queue_matches_binary_heap_on_a_full_size_grid() {
+        // A pseudo-random 141x141 grid of 1-9 heat losses, the size of a real input.
+        let mut seed = 17;
+        let grid: Vec<Vec<u8>> = (0..141).map(|_| (0..141).map(|_| (rng(&mut seed) % 9) as u8 + 1).collect()).collect();
+
+        assert_eq!(solve(&grid, 1, 3), binary_heap_search(&grid, 1, 3));
+        assert_eq!(solve(&grid, 4, 10), binary_heap_search(&grid, 4, 10));
+    }
+
+    #[test]
+    fn solve_takes_a_prebuilt_grid() {
+        let grid = [vec![1, 1, 1], vec![9, 9, 1], vec![9, 9, 1]];
+        assert_eq!(solve(&grid, 1, 3), 4);
+        assert_eq!(solve(&grid, 4, 10), usize::MAX); // Too small to ever turn
+        assert_eq!(solve(&[], 1, 3), usize::MAX);
+    }
+
+    #[test]
+    fn cheaper_path_to_a_queued_state_still_counts() {
+        // Zero heat loss blocks make the first path to reach a state not the cheapest one.
+        // Marking states as visited when first queued answered 13 here.
+        assert_eq!(part1("0402\n1410\n5637\n"), Ok(12));
+    }
 }
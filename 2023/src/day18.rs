@@ -4,87 +4,278 @@
 // Part 2: Calculate lagoon area from hex-encoded instructions
 //
 // Uses shoelace formula + Pick's theorem for polygon area calculation.
-    #[aoc(day18, part1)]
+
+#[aoc(day18, part1)]
 pub fn part1(dig_plan: &str) -> i64 {
-        let mut x: i64 = 0;
-        let mut area: i64 = 0;
-        let mut perimeter: i64 = 0;
-
-        for line in dig_plan.lines() {
-            let mut parts = line.split_whitespace();
-            let direction = parts.next().and_then(|d| d.chars().next()).unwrap_or_default();
-            let length = parts.next().and_then(|l| l.parse::<i64>().ok()).unwrap_or_default();
-
-            match direction {
-                'R' => {
-                    perimeter += length;
-                    x += length;
-                },
-                'L' => {
-                    perimeter += length;
-                    x -= length;
-                },
-                'D' => {
-                    perimeter += length;
-                    area += x * length; // Adding rectangles formed by vertical movement
-                },
-                'U' => {
-                    perimeter += length;
-                    area -= x * length; // Subtracting rectangles formed by vertical movement
-                },
-                _ => {}
+    lagoon_area(plain_moves(dig_plan))
+}
+
+/// Function to calculate the cubic meters of lava the lagoon can hold based on the corrected dig plan.
+#[aoc(day18, part2)]
+pub fn part2(dig_plan: &str) -> i64 {
+    lagoon_area(hex_moves(dig_plan))
+}
+
+/// The trench's corners, starting from the position after the first move and ending back
+/// at the starting point `(0, 0)` for a closed loop. Right is +x and down is +y.
+///
+/// With `use_hex` the moves come from the hex codes as in part 2, otherwise from the plain
+/// direction and length as in part 1.
+pub fn trench_vertices(dig_plan: &str, use_hex: bool) -> Vec<(i64, i64)> {
+    let moves: Vec<(char, i64)> = if use_hex { hex_moves(dig_plan).collect() } else { plain_moves(dig_plan).collect() };
+
+    moves
+        .into_iter()
+        .filter_map(|(direction, length)| {
+            let (dx, dy) = match direction {
+                'R' => (1, 0),
+                'L' => (-1, 0),
+                'D' => (0, 1),
+                'U' => (0, -1),
+                _ => return None,
+            };
+            Some((dx * length, dy * length))
+        })
+        .scan((0, 0), |(x, y), (dx, dy)| {
+            (*x, *y) = (*x + dx, *y + dy);
+            Some((*x, *y))
+        })
+        .collect()
+}
+
+/// The smallest and largest corners of the box around the trench, including its start,
+/// as `((min_x, min_y), (max_x, max_y))`.
+pub fn bounding_box(dig_plan: &str, use_hex: bool) -> ((i64, i64), (i64, i64)) {
+    trench_vertices(dig_plan, use_hex)
+        .into_iter()
+        .fold(((0, 0), (0, 0)), |((min_x, min_y), (max_x, max_y)), (x, y)| {
+            ((min_x.min(x), min_y.min(y)), (max_x.max(x), max_y.max(y)))
+        })
+}
+
+/// Reads each line's direction letter and length, e.g. `R 6 (#70c710)` is `('R', 6)`.
+fn plain_moves(dig_plan: &str) -> impl Iterator<Item = (char, i64)> + '_ {
+    dig_plan.lines().map(|line| {
+        let mut parts = line.split_whitespace();
+        let direction = parts.next().and_then(|d| d.chars().next()).unwrap_or_default();
+        let length = parts.next().and_then(|l| l.parse::<i64>().ok()).unwrap_or_default();
+        (direction, length)
+    })
+}
+
+/// Decodes each line's hex code: the first five digits are the length and the last is the
+/// direction, e.g. `#70c710` is `('R', 461937)`. Lines without a valid code are skipped.
+fn hex_moves(dig_plan: &str) -> impl Iterator<Item = (char, i64)> + '_ {
+    dig_plan.lines().filter_map(|line| decode_hex(line).ok())
+}
+
+/// Decodes a line's `(#rrrrrd)` color code into a direction and length.
+fn decode_hex(line: &str) -> Result<(char, i64), String> {
+    let start = line.find('#').ok_or_else(|| format!("missing color code: {line}"))?;
+    let hex_code = line[start + 1..].trim_end().trim_end_matches(')');
+
+    if hex_code.len() != 6 || !hex_code.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("color code is not 6 hex digits: {line}"));
+    }
+
+    let length = i64::from_str_radix(&hex_code[..5], 16).map_err(|err| format!("{err}: {line}"))?;
+    let direction = match &hex_code[5..] {
+        "0" => 'R',
+        "1" => 'D',
+        "2" => 'L',
+        "3" => 'U',
+        digit => return Err(format!("invalid direction digit {digit}: {line}")),
+    };
+
+    Ok((direction, length))
+}
+
+/// Like `part2`, but fails on any non-blank line without a valid color code instead of
+/// skipping it, since a dropped instruction silently changes the area.
+pub fn part2_strict(dig_plan: &str) -> Result<i64, String> {
+    let moves = dig_plan
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(decode_hex)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(lagoon_area(moves.into_iter()))
+}
+
+/// Counts the cubic meters dug out by following the moves: the trench itself plus everything
+/// it encloses.
+///
+/// The shoelace formula gives the area inside the trench's center line, and Pick's theorem
+/// adds the outer half of the trench.
+fn lagoon_area(moves: impl Iterator<Item = (char, i64)>) -> i64 {
+    let mut x: i64 = 0;
+    let mut area: i64 = 0;
+    let mut perimeter: i64 = 0;
+
+    for (direction, length) in moves {
+        match direction {
+            'R' => {
+                perimeter += length;
+                x += length;
+            },
+            'L' => {
+                perimeter += length;
+                x -= length;
+            },
+            'D' => {
+                perimeter += length;
+                area += x * length; // Adding rectangles formed by vertical movement
+            },
+            'U' => {
+                perimeter += length;
+                area -= x * length; // Subtracting rectangles formed by vertical movement
+            },
+            _ => {}
+        }
+    }
+
+    // Adjusting the area calculation to include the interior. The sign of the area only
+    // depends on whether the trench runs clockwise, so take its magnitude.
+    area.abs() + perimeter / 2 + 1
+}
+
+/// Like `part1`, but directions may also be diagonal: `UR`, `UL`, `DR` or `DL` (in either
+/// letter order) move one step on both axes per meter of length, e.g. `UR 3` ends 3 right and
+/// 3 up.
+///
+/// Pick's theorem still applies, with the boundary counting every step: a diagonal of
+/// length `n` passes through `n` new grid points, just like a straight one.
+pub fn lagoon_area_with_diagonals(dig_plan: &str) -> Result<i64, String> {
+    let (mut x, mut y) = (0i64, 0i64);
+    let mut area: i64 = 0;
+    let mut perimeter: i64 = 0;
+
+    for line in dig_plan.lines().filter(|line| !line.trim().is_empty()) {
+        let mut parts = line.split_whitespace();
+        let direction = parts.next().unwrap_or_default();
+        let length = parts
+            .next()
+            .and_then(|l| l.parse::<i64>().ok())
+            .ok_or_else(|| format!("missing length: {line}"))?;
+
+        // Combine each letter's step, rejecting repeated axes like `UU` or `UD`
+        let (mut dx, mut dy) = (0, 0);
+        for letter in direction.chars() {
+            match letter {
+                'R' | 'L' if dx == 0 => dx = if letter == 'R' { 1 } else { -1 },
+                'D' | 'U' if dy == 0 => dy = if letter == 'D' { 1 } else { -1 },
+                _ => return Err(format!("invalid direction {direction}: {line}")),
             }
         }
+        if (dx, dy) == (0, 0) {
+            return Err(format!("missing direction: {line}"));
+        }
 
-        // Adjusting the area calculation to include the interior
-        area + perimeter / 2 + 1
+        // Shoelace term for the edge from (x, y) to the next corner
+        let (next_x, next_y) = (x + dx * length, y + dy * length);
+        area += x * next_y - next_x * y;
+        perimeter += length;
+        (x, y) = (next_x, next_y);
     }
 
-/// Function to calculate the cubic meters of lava the lagoon can hold based on the corrected dig plan.
-    #[aoc(day18, part2)]
-pub fn part2(dig_plan: &str) -> i64 {
-        let mut x: i64 = 0;
-        let mut area: i64 = 0;
-        let mut perimeter: i64 = 0;
-
-        for line in dig_plan.lines() {
-            if let Some(start) = line.find('#') {
-                let hex_code = &line[start + 1..];
-
-                let length = match i64::from_str_radix(&hex_code[..5], 16) {
-                    Ok(l) => l,
-                    Err(_) => continue, // Skip if the length part is not valid
-                };
-
-                let direction = match hex_code.chars().nth(5) {
-                    Some('0') => 'R',
-                    Some('1') => 'D',
-                    Some('2') => 'L',
-                    Some('3') => 'U',
-                    _ => continue, // Skip if the direction is not valid
-                };
-
-                match direction {
-                    'R' => {
-                        perimeter += length;
-                        x += length;
-                    },
-                    'L' => {
-                        perimeter += length;
-                        x -= length;
-                    },
-                    'D' => {
-                        perimeter += length;
-                        area += x * length;
-                    },
-                    'U' => {
-                        perimeter += length;
-                        area -= x * length;
-                    },
-                    _ => {}
-                }
-            }
+    // Halving the doubled area and the boundary together keeps odd triangle areas exact
+    Ok((area.abs() + perimeter) / 2 + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\
+R 6 (#70c710)
+D 5 (#0dc571)
+L 2 (#5713f0)
+D 2 (#d2c081)
+R 2 (#59c680)
+D 2 (#411b91)
+L 5 (#8ceee2)
+U 2 (#caa173)
+L 1 (#1b58a2)
+U 2 (#caa171)
+R 2 (#7807d2)
+U 3 (#a77fa3)
+L 2 (#015232)
+U 2 (#7a21e3)
+";
+
+    #[test]
+    fn sample_answers() {
+        assert_eq!(part1(SAMPLE), 62);
+        assert_eq!(part2(SAMPLE), 952408144115);
+    }
+
+    #[test]
+    fn one_area_for_both_move_sources() {
+        assert_eq!(lagoon_area(plain_moves(SAMPLE)), 62);
+        assert_eq!(lagoon_area(hex_moves(SAMPLE)), 952408144115);
+
+        // A 3 by 3 square dug either way round
+        assert_eq!(lagoon_area([('R', 2), ('D', 2), ('L', 2), ('U', 2)].into_iter()), 9);
+        assert_eq!(part1("R 2\nU 2\nL 2\nD 2"), 9);
+        assert_eq!(lagoon_area(std::iter::empty()), 1);
+    }
+
+    /// The lagoon's size from its corners, by the shoelace formula and Pick's theorem.
+    fn area_of_vertices(vertices: &[(i64, i64)]) -> i64 {
+        let corners: Vec<_> = std::iter::once((0, 0)).chain(vertices.iter().copied()).collect();
+        let area: i64 = corners.windows(2).map(|w| w[0].0 * w[1].1 - w[1].0 * w[0].1).sum();
+        let border: i64 = corners.windows(2).map(|w| (w[0].0 - w[1].0).abs() + (w[0].1 - w[1].1).abs()).sum();
+        area.abs() / 2 + border / 2 + 1
+    }
+
+    #[test]
+    fn vertices_and_bounding_box() {
+        for use_hex in [false, true] {
+            let vertices = trench_vertices(SAMPLE, use_hex);
+            assert_eq!(vertices.len(), SAMPLE.lines().count());
+            assert_eq!(vertices.last(), Some(&(0, 0)));
+            let expected = if use_hex { part2(SAMPLE) } else { part1(SAMPLE) };
+            assert_eq!(area_of_vertices(&vertices), expected);
         }
 
-        area + perimeter / 2 + 1
+        assert_eq!(trench_vertices(SAMPLE, false)[..3], [(6, 0), (6, 5), (4, 5)]);
+        assert_eq!(bounding_box(SAMPLE, false), ((0, 0), (6, 9)));
+        assert_eq!(bounding_box("L 2\nU 3\nR 2\nD 3", false), ((-2, -3), (0, 0)));
+    }
+
+    #[test]
+    fn strict_mode_rejects_malformed_codes() {
+        assert_eq!(part2_strict(SAMPLE), Ok(952408144115));
+        assert_eq!(part2_strict(&format!("\n{SAMPLE}\n\n")), Ok(952408144115));
+
+        let truncated = SAMPLE.replace("#70c710", "#70c71");
+        assert_eq!(part2_strict(&truncated), Err("color code is not 6 hex digits: R 6 (#70c71)".to_string()));
+        // The lenient part 2 drops the line and gets a different area
+        assert_ne!(part2(&truncated), part2(SAMPLE));
+
+        let bad_direction = SAMPLE.replace("#70c710", "#70c714");
+        assert_eq!(part2_strict(&bad_direction), Err("invalid direction digit 4: R 6 (#70c714)".to_string()));
+        assert!(part2_strict("R 6").is_err());
+        assert!(part2_strict("R 6 (#70c71g)").is_err());
     }
+
+    #[test]
+    fn diamonds_and_triangles_with_diagonals() {
+        assert_eq!(lagoon_area_with_diagonals(SAMPLE), Ok(62));
+
+        // Diamonds hold every point within their radius: 13 for 2 and 25 for 3
+        assert_eq!(lagoon_area_with_diagonals("DR 2\nUR 2\nUL 2\nLD 2"), Ok(13));
+        assert_eq!(lagoon_area_with_diagonals("DR 3\nUR 3\nUL 3\nDL 3"), Ok(25));
+
+        // Right triangles have odd doubled areas, so the halves must be summed before rounding
+        assert_eq!(lagoon_area_with_diagonals("DR 1\nU 1\nL 1"), Ok(3));
+        assert_eq!(lagoon_area_with_diagonals("DR 3\nU 3\nL 3"), Ok(10));
+        assert_eq!(lagoon_area_with_diagonals("R 4\nUL 4\nD 4"), Ok(15));
+
+        assert!(lagoon_area_with_diagonals("UD 2").is_err());
+        assert!(lagoon_area_with_diagonals("RR 2").is_err());
+        assert!(lagoon_area_with_diagonals("X 2").is_err());
+        assert!(lagoon_area_with_diagonals("R").is_err());
+    }
+}
+
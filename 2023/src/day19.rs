@@ -3,28 +3,64 @@
 // Evaluate machine parts through conditional workflows.
 // Part 1: Sum ratings of accepted parts
 // Part 2: Count total possible combinations of valid part ranges
+//
+// The rating categories are discovered from the input, so variants with more (or other)
+// categories than x, m, a, s work too. The usual four keep fixed-size arrays as a fast path.
 
 use std::collections::HashMap;
 
-// A struct representing a single workflow rule.
-// It holds a reference to a target workflow name and an optional condition.
+/// A single workflow rule, shared by both parts.
+/// It holds a reference to a target workflow name and an optional condition: the category
+/// index (0 to 3 for x, m, a, s), the comparison byte (`<` or `>`), and the limit.
 #[derive(Debug, Clone)]
-struct WorkflowRule<'a>(&'a str, Option<(usize, u8, i16)>);
+pub struct Rule<'a>(&'a str, Option<(usize, u8, i16)>);
+
+// Lists the letter of every rating category in index order: x, m, a, s always take indices
+// 0 to 3, whether or not the input uses them, and any other letters follow in order of
+// first appearance.
+fn categories(input: &str) -> Vec<u8> {
+    let mut categories = b"xmas".to_vec();
+    for line in input.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let (_, fields) = line.split_once('{').unwrap_or(("", line));
+        // Conditions look like `a<2006:qkq` and ratings like `x=787`.
+        for field in fields.trim_end_matches('}').split(',') {
+            if field.find(['<', '>', '=']) == Some(1) && !categories.contains(&field.as_bytes()[0]) {
+                categories.push(field.as_bytes()[0]);
+            }
+        }
+    }
+    categories
+}
 
 // Parses the input string into a collection of workflows and part ratings.
-fn parse(input: &str) -> (HashMap<&str, Vec<WorkflowRule<'_>>>, Vec<Vec<i16>>) {
+// `blank` is an all-zero part with one rating per category, which each part starts from.
+fn parse<'a, P: Clone + AsMut<[i16]>>(
+    input: &'a str,
+    categories: &[u8],
+    blank: P,
+) -> (HashMap<&'a str, Vec<Rule<'a>>>, Vec<P>) {
     // Split the input into two sections: workflows and ratings.
     let (workflows_section, ratings_section) = input.split_once("\n\n").unwrap();
     // Parse these sections separately.
-    let workflows = parse_workflows(workflows_section);
-    let ratings = parse_ratings(ratings_section);
+    let workflows = parse_workflows_with(workflows_section, categories);
+    let ratings = parse_ratings(ratings_section, categories, blank);
     (workflows, ratings)
 }
 
-// Parses the workflows section and returns a map of workflow names to their rules.
-fn parse_workflows(workflows_str: &str) -> HashMap<&str, Vec<WorkflowRule<'_>>> {
+/// Parses the workflows section and returns a map of workflow names to their rules.
+/// Given a whole input, it stops at the blank line before the part ratings.
+///
+/// Categories are indexed as described for `classify_part`, with any letters other than
+/// x, m, a, s taken from the given text.
+pub fn parse_workflows(workflows_str: &str) -> HashMap<&str, Vec<Rule<'_>>> {
+    parse_workflows_with(workflows_str, &categories(workflows_str))
+}
+
+// Parses the workflows section, indexing the categories by their position in `categories`.
+fn parse_workflows_with<'a>(workflows_str: &'a str, categories: &[u8]) -> HashMap<&'a str, Vec<Rule<'a>>> {
     workflows_str
         .lines()
+        .take_while(|line| !line.trim().is_empty())
         .map(|line| {
             let (workflow_name, rules) = line.split_once('{').unwrap();
             let rules = rules[..rules.len() - 1]
@@ -33,20 +69,14 @@ fn parse_workflows(workflows_str: &str) -> HashMap<&str, Vec<WorkflowRule<'_>>>
                     if let Some((condition, target)) = rule.split_once(':') {
                         // Parses the condition and target of a rule.
                         let condition_byte = condition.as_bytes()[0];
-                        let condition_position = match condition_byte {
-                            b'x' => 0,
-                            b'm' => 1,
-                            b'a' => 2,
-                            b's' => 3,
-                            _ => unreachable!(), // This should never happen if input is well-formed.
-                        };
-                        WorkflowRule(
+                        let condition_position = categories.iter().position(|&c| c == condition_byte).unwrap();
+                        Rule(
                             target,
                             Some((condition_position, condition.as_bytes()[1], condition[2..].parse().unwrap())),
                         )
                     } else {
                         // Rules without a condition just have a target workflow.
-                        WorkflowRule(rule, None)
+                        Rule(rule, None)
                     }
                 })
                 .collect();
@@ -55,187 +85,310 @@ fn parse_workflows(workflows_str: &str) -> HashMap<&str, Vec<WorkflowRule<'_>>>
         .collect()
 }
 
-// Parses the part ratings section into a vector of vectors.
-fn parse_ratings(ratings_str: &str) -> Vec<Vec<i16>> {
+// Parses the part ratings section into the ratings of each part, in category index order.
+fn parse_ratings<P: Clone + AsMut<[i16]>>(ratings_str: &str, categories: &[u8], blank: P) -> Vec<P> {
     ratings_str
         .lines()
         .map(|line| {
-            line[1..line.len() - 1] // Removes curly braces.
-                .split(',')
-                .map(|rating| rating[2..].parse().unwrap()) // Parses each rating value.
-                .collect()
+            let mut part = blank.clone();
+            for rating in line[1..line.len() - 1].split(',') {
+                // Removes curly braces, then places each rating value at its category's index.
+                let index = categories.iter().position(|&c| c == rating.as_bytes()[0]).unwrap();
+                part.as_mut()[index] = rating[2..].parse().unwrap();
+            }
+            part
         })
         .collect()
 }
 
-// Determines if a part is accepted according to the workflow rules.
-fn is_part_accepted<'a>(
-    workflows: &'a HashMap<&str, Vec<WorkflowRule<'a>>>,
-    part_ratings: &[i16],
-) -> bool {
-    // Calls `process_workflow` and checks if the result equals 1, indicating acceptance.
-    process_workflow(
-        workflows,
-        "in",
-        [
-            (part_ratings[0], part_ratings[0] + 1),
-            (part_ratings[1], part_ratings[1] + 1),
-            (part_ratings[2], part_ratings[2] + 1),
-            (part_ratings[3], part_ratings[3] + 1),
-        ],
-    ) == 1
-}
-
-// Processes the workflow recursively and returns the total rating of accepted parts.
-fn process_workflow<'a>(
-    workflows: &'a HashMap<&str, Vec<WorkflowRule<'a>>>,
-    initial_workflow: &'a str,
-    initial_possible_ranges: [(i16, i16); 4],
-) -> i64 {
-    let mut stack = vec![(initial_workflow, initial_possible_ranges)];
-    let mut total = 0;
-
-    // Iteratively process the workflow.
-    while let Some((current_workflow, possible_ranges)) = stack.pop() {
+/// Runs a part with the given ratings through the workflows, starting at `in`, and returns
+/// whether it ends up accepted.
+///
+/// Ratings are in category index order: x, m, a, s first, then any other categories in order
+/// of first appearance. For the usual puzzle that is `[x, m, a, s]`.
+///
+/// A part sent to a workflow that doesn't exist, or that falls through every rule of one,
+/// is treated as rejected.
+pub fn classify_part<P: AsRef<[i16]>>(workflows: &HashMap<&str, Vec<Rule<'_>>>, part: P) -> bool {
+    let part = part.as_ref();
+    let mut current_workflow = "in";
+
+    loop {
         match current_workflow {
-            "A" => {
-                // If 'A' is reached, calculate the product of the possible ranges.
-                total += possible_ranges
-                    .iter()
-                    .map(|(lower, upper)| i64::from(upper - lower))
-                    .product::<i64>();
-            }
-            "R" => continue, // Skip 'R' as it represents rejection.
-            _ => {
-                // For other workflow rules, check conditions and process further.
-                if let Some(rules) = workflows.get(current_workflow) {
-                    for rule in rules {
-                        match rule {
-                            WorkflowRule(target_workflow, None) => {
-                                // Push the target workflow if no condition is specified.
-                                stack.push((target_workflow, possible_ranges));
-                                break;
-                            }
-                            WorkflowRule(target_workflow, Some((index, operator, limit))) => {
-                                let (lower, upper) = possible_ranges[*index];
-                                if (operator == &b'<' && upper <= *limit) || (operator == &b'>' && lower > *limit) {
-                                    // Push the target workflow if the condition is met.
-                                    stack.push((target_workflow, possible_ranges));
-                                    break;
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+            "A" => return true,
+            "R" => return false,
+            _ => (),
+        }
+
+        match next_workflow(workflows, current_workflow, part) {
+            Some(target_workflow) => current_workflow = target_workflow,
+            None => return false,
         }
     }
+}
+
+/// The workflows a part passes through, starting with `in` and ending with `"A"` if it is
+/// accepted or `"R"` if it is rejected. Ratings are ordered as for `classify_part`.
+///
+/// A part that gets stuck, on a workflow that doesn't exist or by falling through every rule
+/// of one, ends with `"R"` after the last workflow it reached.
+pub fn trace<'a, P: AsRef<[i16]>>(workflows: &HashMap<&'a str, Vec<Rule<'a>>>, part: P) -> Vec<&'a str> {
+    let part = part.as_ref();
+    let mut path = vec!["in"];
 
-    total
+    while let Some(&current_workflow) = path.last().filter(|&&name| name != "A" && name != "R") {
+        path.push(next_workflow(workflows, current_workflow, part).unwrap_or("R"));
+    }
+    path
+}
+
+// Finds where a workflow sends the part: the target of the first rule whose condition holds,
+// or that has none. Returns `None` if the workflow doesn't exist or no rule applies.
+fn next_workflow<'a>(workflows: &HashMap<&'a str, Vec<Rule<'a>>>, workflow: &str, part: &[i16]) -> Option<&'a str> {
+    let rules = workflows.get(workflow)?;
+    rules
+        .iter()
+        .find(|Rule(_, condition)| match *condition {
+            None => true,
+            Some((index, operator, limit)) => {
+                (operator == b'<' && part[index] < limit) || (operator == b'>' && part[index] > limit)
+            }
+        })
+        .map(|&Rule(target_workflow, _)| target_workflow)
 }
 
 // Calculates the sum of ratings for all parts that are accepted by the workflows.
 #[aoc(day19, part1)]
 pub fn part1(input: &str) -> i64 {
-    let (workflows, ratings) = parse(input);
+    let categories = categories(input);
+    if categories.len() == 4 {
+        sum_accepted(input, &categories, [0; 4])
+    } else {
+        sum_accepted(input, &categories, vec![0; categories.len()])
+    }
+}
+
+// Sums the ratings of the accepted parts, each part stored like `blank`.
+fn sum_accepted<P: Clone + AsRef<[i16]> + AsMut<[i16]>>(input: &str, categories: &[u8], blank: P) -> i64 {
+    let (workflows, ratings) = parse(input, categories, blank);
     ratings
         .into_iter()
-        .filter_map(|part_ratings| {
-            // For each set of part ratings, check if it's accepted and sum up their ratings.
-            is_part_accepted(&workflows, &part_ratings).then(|| {
-                part_ratings
-                    .iter()
-                    .map(|&rating| i64::from(rating))
-                    .sum::<i64>()
-            })
-        })
+        // For each set of part ratings, check if it's accepted and sum up their ratings.
+        .filter(|part_ratings| classify_part(&workflows, part_ratings))
+        .map(|part_ratings| part_ratings.as_ref().iter().map(|&rating| i64::from(rating)).sum::<i64>())
         .sum()
 }
 
-// Represents a single workflow rule for part2, holding a target workflow name and an optional condition.
-#[derive(Debug)]
-struct WorkflowRule2<'a>(&'a str, Option<(usize, u8, i16)>);
-
-// Parses the input string into a collection of workflows.
-// Returns a map of workflow names to their associated rules.
-fn parse_to_workflows(input: &str) -> HashMap<&str, Vec<WorkflowRule2<'_>>> {
-    let (workflows, _) = input.split_once("\n\n").unwrap();
-    workflows
-        .lines()
-        .map(|line| {
-            let (name, rules) = line.split_once('{').unwrap();
-            let rules = rules[..rules.len() - 1]
-                .split(',')
-                .map(|rule| {
-                    // Parse each rule, splitting it into a condition and a target.
-                    if let Some((condition, target)) = rule.split_once(':') {
-                        let condition_byte = condition.as_bytes()[0];
-                        let condition_position = match condition_byte {
-                            b'x' => 0, b'm' => 1, b'a' => 2, b's' => 3,
-                            _ => unreachable!(), // Ensures well-formed input.
-                        };
-                        WorkflowRule2(
-                            target,
-                            Some((condition_position, condition.as_bytes()[1], condition[2..].parse().unwrap())),
-                        )
-                    } else {
-                        // If no condition is specified, only the target is considered.
-                        WorkflowRule2(rule, None)
-                    }
-                })
-                .collect();
-            (name, rules)
-        })
-        .collect()
-}
-
-// Processes the workflow rules iteratively and calculates the total accepted combinations.
-fn process_rule(
-    workflows: &HashMap<&str, Vec<WorkflowRule2<'_>>>,
+// Follows the workflow rules from `rule`, splitting the ranges of possible ratings at each
+// condition, and collects every range combination that ends up accepted.
+// Ranges are half-open, so `(1, 4001)` covers ratings 1 to 4000.
+fn collect_accepted<R: Clone + AsRef<[(i16, i16)]> + AsMut<[(i16, i16)]>>(
+    workflows: &HashMap<&str, Vec<Rule<'_>>>,
     rule: &str,
-    mut possible: [(i16, i16); 4],
-) -> i64 {
+    mut possible: R,
+    accepted: &mut Vec<R>,
+) {
     match rule {
-        "A" => {
-            // If 'A' is reached, calculate the product of the possible ranges.
-            return possible
-                .into_iter()
-                .map(|(l, h)| i64::from(h - l))
-                .product::<i64>()
-        }
-        "R" => return 0, // Ignore 'R' as it represents rejection.
+        "A" => return accepted.push(possible),
+        "R" => return, // Ignore 'R' as it represents rejection.
         _ => (),
     }
-    let mut total = 0;
-    // Iterate through the rules to evaluate conditions and compute totals.
-    for WorkflowRule2(target, condition) in &workflows[&rule] {
-        let short = |target| total + process_rule(workflows, target, possible);
+    // Like `classify_part`, a workflow that doesn't exist rejects everything sent to it.
+    let Some(rules) = workflows.get(rule) else {
+        return;
+    };
+    // Iterate through the rules to evaluate conditions and split the ranges accordingly.
+    for Rule(target, condition) in rules {
         match *condition {
-            None => return short(target), // No condition means direct transition to the target.
-            Some((idx, op, limit)) => match (possible[idx], op) {
-                // Evaluate each condition and update totals and ranges accordingly.
-                ((_, u), b'<') if u <= limit => return short(target),
-                ((l, _), b'>') if l > limit => return short(target),
+            None => return collect_accepted(workflows, target, possible, accepted), // No condition means direct transition to the target.
+            Some((idx, op, limit)) => match (possible.as_ref()[idx], op) {
+                // Send the whole range on if it all passes, or split off the part that passes.
+                ((_, u), b'<') if u <= limit => return collect_accepted(workflows, target, possible, accepted),
+                ((l, _), b'>') if l > limit => return collect_accepted(workflows, target, possible, accepted),
                 ((l, u), b'<') if l < limit => {
-                    possible[idx] = (l, limit);
-                    total += process_rule(workflows, target, possible);
-                    possible[idx] = (limit, u);
+                    possible.as_mut()[idx] = (l, limit);
+                    collect_accepted(workflows, target, possible.clone(), accepted);
+                    possible.as_mut()[idx] = (limit, u);
                 }
-                ((l, u), b'>') if u >= limit => {
-                    possible[idx] = (limit + 1, u);
-                    total += process_rule(workflows, target, possible);
-                    possible[idx] = (l, limit + 1);
+                ((l, u), b'>') if u > limit + 1 => {
+                    possible.as_mut()[idx] = (limit + 1, u);
+                    collect_accepted(workflows, target, possible.clone(), accepted);
+                    possible.as_mut()[idx] = (l, limit + 1);
                 }
                 _ => (),
             },
         };
     }
-    total
+}
+
+// Parses the workflows and collects the half-open ranges of every accepted box of ratings,
+// starting from `full`, which holds every category's whole range.
+fn accepted_boxes<R: Clone + AsRef<[(i16, i16)]> + AsMut<[(i16, i16)]>>(
+    input: &str,
+    categories: &[u8],
+    full: R,
+) -> Vec<R> {
+    let workflows = parse_workflows_with(input, categories);
+    let mut accepted = Vec::new();
+    collect_accepted(&workflows, "in", full, &mut accepted);
+    accepted
 }
 
 // Entry point to calculate the total number of accepted combinations for a given input.
 #[aoc(day19, part2)]
 pub fn part2(input: &str) -> i64 {
-    let workflows = parse_to_workflows(input);
-    process_rule(&workflows, "in", [(1, 4001); 4])
+    let categories = categories(input);
+    if categories.len() == 4 {
+        count_combinations(accepted_boxes(input, &categories, [(1, 4001); 4]))
+    } else {
+        count_combinations(accepted_boxes(input, &categories, vec![(1, 4001); categories.len()]))
+    }
+}
+
+// Each accepted combination of ranges is a box of rating combinations.
+fn count_combinations<R: AsRef<[(i16, i16)]>>(boxes: Vec<R>) -> i64 {
+    boxes
+        .iter()
+        .map(|ranges| ranges.as_ref().iter().map(|(l, h)| i64::from(h - l)).product::<i64>())
+        .sum()
 }
+
+/// Every disjoint box of accepted ratings, with the inclusive `(min, max)` range of each
+/// category in x, m, a, s order. Their sizes add up to `part2`.
+///
+/// # Panics
+///
+/// If the input's categories aren't exactly x, m, a, s.
+pub fn accepted_ranges(input: &str) -> Vec<[(i16, i16); 4]> {
+    let categories = categories(input);
+    assert_eq!(categories, b"xmas", "accepted_ranges needs the x, m, a, s categories");
+    accepted_boxes(input, &categories, [(1, 4001); 4])
+        .into_iter()
+        .map(|ranges| ranges.map(|(l, h)| (l, h - 1)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\
+px{a<2006:qkq,m>2090:A,rfg}
+pv{a>1716:R,A}
+lnx{m>1548:A,A}
+rfg{s<537:gd,x>2440:R,A}
+qs{s>3448:A,lnx}
+qkq{x<1416:A,crn}
+crn{x>2662:A,R}
+in{s<1351:px,qqz}
+qqz{s>2770:qs,m<1801:hdj,R}
+gd{a>3333:R,R}
+hdj{m>838:A,pv}
+
+{x=787,m=2655,a=1222,s=2876}
+{x=1679,m=44,a=2067,s=496}
+{x=2036,m=264,a=79,s=2244}
+{x=2461,m=1339,a=466,s=291}
+{x=2127,m=1623,a=2188,s=1013}
+";
+
+    #[test]
+    fn sample_answers() {
+        assert_eq!(part1(SAMPLE), 19114);
+        assert_eq!(part2(SAMPLE), 167409079868000);
+    }
+
+    #[test]
+    fn accepted_ranges_are_disjoint_and_sum_to_part2() {
+        let ranges = accepted_ranges(SAMPLE);
+        let volume = |ranges: &[(i16, i16); 4]| ranges.iter().map(|&(l, h)| i64::from(h - l + 1)).product::<i64>();
+        assert_eq!(ranges.iter().map(volume).sum::<i64>(), part2(SAMPLE));
+
+        for (i, a) in ranges.iter().enumerate() {
+            assert!(a.iter().all(|&(l, h)| 1 <= l && l <= h && h <= 4000));
+            for b in &ranges[i + 1..] {
+                assert!((0..4).any(|k| a[k].1 < b[k].0 || b[k].1 < a[k].0), "{a:?} overlaps {b:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn one_parser_for_both_parts() {
+        let workflows = parse_workflows(SAMPLE);
+        assert_eq!(workflows.len(), 11);
+        assert!(matches!(workflows["in"][..], [Rule("px", Some((3, b'<', 1351))), Rule("qqz", None)]));
+        assert!(matches!(workflows["crn"][..], [Rule("A", Some((0, b'>', 2662))), Rule("R", None)]));
+
+        // Limits at the edges of a range that was already split
+        let input = "in{x<100:a,R}\na{x>100:A,x>98:A,R}\n\n{x=99,m=1,a=1,s=1}\n{x=98,m=1,a=1,s=1}";
+        assert_eq!(part1(input), 102);
+        assert_eq!(part2(input), 4000i64.pow(3));
+        let input = "in{x<100:a,R}\na{x>100:R,A}\n\n{x=1,m=1,a=1,s=1}";
+        assert_eq!(part1(input), 4);
+        assert_eq!(part2(input), 99 * 4000i64.pow(3));
+    }
+
+    #[test]
+    fn classify_sample_parts() {
+        let workflows = parse_workflows(SAMPLE);
+        assert!(classify_part(&workflows, [787, 2655, 1222, 2876]));
+        assert!(!classify_part(&workflows, [1679, 44, 2067, 496]));
+        assert!(classify_part(&workflows, [2036, 264, 79, 2244]));
+        assert!(!classify_part(&workflows, [2461, 1339, 466, 291]));
+        assert!(classify_part(&workflows, [2127, 1623, 2188, 1013]));
+
+        // Parts sent to a missing workflow, or falling through every rule, are rejected
+        assert!(!classify_part(&parse_workflows("in{x<5:nope,A}"), [1, 1, 1, 1]));
+        assert!(!classify_part(&parse_workflows("in{x<5:A}"), [9, 1, 1, 1]));
+    }
+
+    #[test]
+    fn five_categories_and_missing_workflows() {
+        let five = SAMPLE
+            .replace("in{s<1351:px,qqz}", "in{z>3000:R,s<1351:px,qqz}")
+            .replace("s=2876}", "s=2876,z=3001}")
+            .replace("s=2244}", "s=2244,z=3000}")
+            .replace("s=1013}", "s=1013,z=5}");
+        assert_eq!(part2(&five), 167409079868000 * 3000);
+        // The first part is now rejected, and the other accepted parts add their z ratings
+        assert_eq!(part1(&five), 4623 + 3000 + 6951 + 5);
+        let workflows = parse_workflows(&five);
+        assert!(!classify_part(&workflows, [787, 2655, 1222, 2876, 3001]));
+        assert!(classify_part(&workflows, vec![787, 2655, 1222, 2876, 3000]));
+
+        // Categories other than x, m, a, s, listed in any order
+        let other = "in{q<5:A,m>10:A,R}\n\n{q=4,m=1}\n{m=11,q=9}\n{q=6,m=3}";
+        assert_eq!(part1(other), 5 + 20);
+        assert_eq!(part2(other), (4 * 4000 + 3990 * 4000 - 4 * 3990) * 4000i64.pow(3));
+
+        // Ranges sent to a workflow that doesn't exist are rejected, as single parts are
+        let missing = "in{x<5:nope,A}\n\n{x=1,m=1,a=1,s=1}\n{x=5,m=1,a=1,s=1}";
+        assert_eq!(part1(missing), 8);
+        assert_eq!(part2(missing), 3996 * 4000i64.pow(3));
+    }
+
+    #[test]
+    fn traces_end_at_accept_or_reject() {
+        let workflows = parse_workflows(SAMPLE);
+        assert_eq!(trace(&workflows, [787, 2655, 1222, 2876]), ["in", "qqz", "qs", "lnx", "A"]);
+        assert_eq!(trace(&workflows, [1679, 44, 2067, 496]), ["in", "px", "rfg", "gd", "R"]);
+        assert_eq!(trace(&workflows, [2127, 1623, 2188, 1013]), ["in", "px", "rfg", "A"]);
+
+        let workflows = parse_workflows("in{x<5:nope,R}");
+        assert_eq!(trace(&workflows, [1, 1, 1, 1]), ["in", "nope", "R"]);
+    }
+
+    #[test]
+    fn workflows_without_x_keep_the_usual_indices() {
+        let workflows = parse_workflows("in{s<5:A,R}");
+        assert!(matches!(workflows["in"][..], [Rule("A", Some((3, b'<', 5))), Rule("R", None)]));
+        assert!(classify_part(&workflows, [100, 100, 100, 1]));
+        assert_eq!(trace(&workflows, [100, 100, 100, 1]), ["in", "A"]);
+        assert_eq!(trace(&workflows, [1, 1, 1, 100]), ["in", "R"]);
+
+        let workflows = parse_workflows("in{q>5:R,m<10:A,R}");
+        assert!(matches!(workflows["in"][..], [Rule("R", Some((4, b'>', 5))), Rule("A", Some((1, b'<', 10))), _]));
+        assert!(classify_part(&workflows, [0, 9, 0, 0, 5]));
+    }
+}
+
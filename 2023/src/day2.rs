@@ -1,6 +1,7 @@
 // Day 2: Cube Conundrum
 //
-// Determine which games are possible with only 12 red, 13 green, and 14 blue cubes.
+// Determine which games are possible with only 12 red, 13 green, and 14 blue cubes
+// (or any other cube budget).
 // Part 1: Sum IDs of valid games
 // Part 2: Sum the "power" (product of minimum required cubes) for each game
 
@@ -34,9 +35,15 @@ fn parse_and_validate_game(line: &str, max_red: u32, max_green: u32, max_blue: u
 
 #[aoc(day2, part1)]
 pub fn part1(input: &str) -> u32 {
+    sum_valid_games(input, 12, 13, 14)
+}
+
+/// Sums the IDs of the games that are possible with a bag holding only the given numbers
+/// of red, green and blue cubes.
+pub fn sum_valid_games(input: &str, red: u32, green: u32, blue: u32) -> u32 {
     input
         .lines()
-        .filter_map(|line| parse_and_validate_game(line, 12, 13, 14))
+        .filter_map(|line| parse_and_validate_game(line, red, green, blue))
         .sum()
 }
 
@@ -86,3 +93,33 @@ pub fn part2(input: &str) -> u32 {
         .filter_map(parse_and_min_product_game)
         .sum()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\
+Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green
+Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue
+Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red
+Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red
+Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green
+";
+
+    #[test]
+    fn sample_answers() {
+        assert_eq!(part1(SAMPLE), 8);
+        assert_eq!(part2(SAMPLE), 2286);
+    }
+
+    #[test]
+    fn other_cube_limits() {
+        assert_eq!(sum_valid_games(SAMPLE, 12, 13, 14), part1(SAMPLE));
+        // Enough of every color for games 3 and 4 too
+        assert_eq!(sum_valid_games(SAMPLE, 20, 20, 20), 15);
+        // Few enough that only games 1, 2 and 5 fit, then only game 1
+        assert_eq!(sum_valid_games(SAMPLE, 6, 3, 6), 1 + 2 + 5);
+        assert_eq!(sum_valid_games(SAMPLE, 4, 2, 6), 1);
+        assert_eq!(sum_valid_games(SAMPLE, 0, 0, 0), 0);
+    }
+}
@@ -1,30 +1,40 @@
 // Day 20: Pulse Propagation
 //
-// The input has a hidden structure: 4 independent 12-bit binary counters.
+// The input has a hidden structure: independent 12-bit binary counters (4 in the real input).
 // Each counter is a chain of flip-flops that resets when hitting a threshold.
 // We extract threshold values from the graph structure, then calculate pulse counts from those values.
+//
+// `simulate` propagates pulses one at a time instead, as a reference for any input.
+
+use num_integer::Integer;
+use std::collections::{HashMap, VecDeque};
 
-use std::collections::HashMap;
+type Input = Vec<u32>;
 
-type Input = [u32; 4];
+// Counters are chains of 12 flip-flops.
+const COUNTER_BITS: u32 = 12;
 
-pub fn parse_input(input: &str) -> Input {
+/// Extracts each counter's threshold from the module graph.
+///
+/// Fails if the input doesn't have the binary counter structure, in which case `simulate`
+/// and `part2_simulated` still apply.
+pub fn parse_input(input: &str) -> Result<Input, String> {
     // Build graph: module name -> (children, is_flip_flop)
     let mut graph: HashMap<&str, Vec<&str>> = HashMap::new();
     let mut is_flipflop: HashMap<&str, bool> = HashMap::new();
 
-    for line in input.lines() {
+    for line in input.lines().filter(|line| !line.trim().is_empty()) {
         // Extract lowercase identifiers (module names)
         let mut tokens = line
             .split(|c: char| !c.is_ascii_lowercase())
             .filter(|s| !s.is_empty());
 
-        let key = tokens.next().unwrap();
+        let key = tokens.next().ok_or_else(|| format!("malformed module: {line}"))?;
         let children: Vec<&str> = tokens.collect();
 
         graph.insert(key, children);
         // Flip-flops start with '%', conjunctions with '&'
-        is_flipflop.insert(key, !line.starts_with('&'));
+        is_flipflop.insert(key, line.starts_with('%'));
     }
 
     // Follow chains from broadcaster, building binary counter values
@@ -32,15 +42,18 @@ pub fn parse_input(input: &str) -> Input {
     let mut counter_values = Vec::new();
 
     // Start from each broadcaster output
-    for &start in &graph["broadcaster"] {
+    for &start in graph.get("broadcaster").ok_or("no broadcaster module")? {
         stack.push((start, 0u32, 1u32)); // (node, value, bit_position)
     }
 
     while let Some((node, mut value, bit)) = stack.pop() {
-        let children = &graph[node];
+        let children = graph.get(node).ok_or_else(|| format!("{node} is not a module"))?;
+        if bit >= 1 << COUNTER_BITS {
+            return Err(format!("counter chain through {node} is longer than {COUNTER_BITS} bits"));
+        }
 
         // Find next flip-flop in chain
-        if let Some(&next) = children.iter().find(|&&k| is_flipflop[k]) {
+        if let Some(&next) = children.iter().find(|&&k| is_flipflop.get(k) == Some(&true)) {
             // If this flip-flop has 2 children (one forward, one back to conjunction),
             // it means this bit is SET in the counter threshold
             if children.len() == 2 {
@@ -53,58 +66,356 @@ pub fn parse_input(input: &str) -> Input {
         }
     }
 
-    counter_values.try_into().unwrap()
+    if counter_values.is_empty() {
+        return Err("no counter chains".to_string());
+    }
+
+    Ok(counter_values)
 }
 
 /// Count pulses by tracking state transitions with XOR operations
+///
+/// Fails if the input isn't made of binary counters, or if a counter would reset within
+/// the first 1000 presses; `simulate` covers those inputs.
 #[aoc(day20, part1)]
-pub fn part1(input: &str) -> u32 {
-    let counters = parse_input(input);
+pub fn part1(input: &str) -> Result<u64, String> {
+    let counters = parse_input(input)?;
 
     // Verify assumption: all counter values > 1000 (no resets in first 1000 presses)
-    assert!(counters.iter().all(|&n| n > 1000));
+    if let Some(&n) = counters.iter().find(|&&n| n <= 1000) {
+        return Err(format!("counter resets at {n}, within the first 1000 presses"));
+    }
 
     // Calculate feedback pattern for each counter
     // Feedback width = 13 - count_ones(value)
     // (conjunctions feed back in inverse pattern to inputs, plus LSB always set)
-    let pairs: Vec<(u32, u32)> = counters
+    let pairs: Vec<(u32, u64)> = counters
         .iter()
-        .map(|&n| (n, 13 - n.count_ones()))
+        .map(|&n| (n, u64::from(COUNTER_BITS + 1 - n.count_ones())))
         .collect();
 
-    // Button and broadcaster contribute 5 low pulses per press
-    let mut low = 5000;
+    // Button and broadcaster contribute one low pulse each, plus one per chain, per press.
+    // Counted in u64, as the product overflows a u32 from 10 chains on.
+    let chains = counters.len() as u64;
+    let mut low = 1000 * (1 + chains);
     let mut high = 0;
 
     for n in 0..1000u32 {
         // Rising edge: flip-flops changing 0→1 emit HIGH pulse
         let rising: u32 = !n & (n + 1);
-        high += 4 * rising.count_ones(); // 4 independent chains
+        high += chains * u64::from(rising.count_ones()); // One step per independent chain
 
         // Falling edge: flip-flops changing 1→0 emit LOW pulse
         let falling: u32 = n & !(n + 1);
-        low += 4 * falling.count_ones();
+        low += chains * u64::from(falling.count_ones());
 
         // For each counter, calculate feedback pulses
         for &(value, feedback) in &pairs {
             // Rising edges hitting counter bits
-            let factor = (rising & value).count_ones();
+            let factor = u64::from((rising & value).count_ones());
             high += factor * (feedback + 3);
             low += factor;
 
             // Falling edges hitting counter bits
-            let factor = (falling & value).count_ones();
+            let factor = u64::from((falling & value).count_ones());
             high += factor * (feedback + 2);
             low += 2 * factor;
         }
     }
 
-    low * high
+    Ok(low * high)
 }
 
 /// Part 2: LCM of counter values (coprime → LCM = product)
 #[aoc(day20, part2)]
-pub fn part2(input: &str) -> u64 {
-    let counters = parse_input(input);
-    counters.iter().map(|&n| n as u64).product()
+pub fn part2(input: &str) -> Result<u64, String> {
+    let counters = parse_input(input)?;
+    Ok(counters.iter().map(|&n| n as u64).product())
+}
+
+/// Part 2 by simulation, without assuming the binary counter structure.
+///
+/// `rx` is fed by a single conjunction, which sends it a low pulse once all of its inputs
+/// have just sent high. Each input is watched for the first press on which it sends a high
+/// pulse, and assuming each repeats on that period, the answer is the LCM of those presses.
+/// Gives up after `MAX_PRESSES` presses.
+pub fn part2_simulated(input: &str) -> Result<u64, String> {
+    let mut simulation = Simulation::new(input);
+    let rx = simulation.index("rx").ok_or("no rx module")?;
+    let &[feeder] = simulation.inputs[rx].as_slice() else {
+        return Err("rx must have exactly one input".to_string());
+    };
+    if simulation.kinds[feeder] != Kind::Conjunction {
+        return Err("rx must be fed by a conjunction".to_string());
+    }
+
+    // First press on which each of the feeder's inputs sends it a high pulse
+    let mut first_high: Vec<Option<u64>> = vec![None; simulation.inputs[feeder].len()];
+    let watched = simulation.inputs[feeder].clone();
+
+    for press in 1..=MAX_PRESSES {
+        simulation.press(|from, to, pulse| {
+            if pulse && to == feeder {
+                let slot = watched.iter().position(|&input| input == from).unwrap();
+                first_high[slot].get_or_insert(press);
+            }
+        });
+
+        if first_high.iter().all(Option::is_some) {
+            return Ok(first_high.into_iter().flatten().fold(1, |acc, press| acc.lcm(&press)));
+        }
+    }
+
+    Err(format!("the inputs to rx didn't all send a high pulse within {MAX_PRESSES} presses"))
+}
+
+// The most presses `part2_simulated` tries before giving up.
+const MAX_PRESSES: u64 = 1 << 20;
+
+/// Presses the button `presses` times, propagating every pulse through the modules in order,
+/// and returns the total number of (low, high) pulses sent, including the button's.
+pub fn simulate(input: &str, presses: u64) -> (u64, u64) {
+    let mut simulation = Simulation::new(input);
+    let (mut low, mut high) = (0, 0);
+
+    for _ in 0..presses {
+        simulation.press(|_, _, pulse| {
+            if pulse {
+                high += 1;
+            } else {
+                low += 1;
+            }
+        });
+    }
+
+    (low, high)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Broadcaster,
+    FlipFlop,
+    Conjunction,
+    // A module with no outgoing connections of its own, such as `rx`
+    Output,
+}
+
+/// The module network and its state, with modules referred to by index.
+struct Simulation<'a> {
+    names: Vec<&'a str>,
+    kinds: Vec<Kind>,
+    outputs: Vec<Vec<usize>>,
+    inputs: Vec<Vec<usize>>,
+    // Whether each flip-flop is on
+    on: Vec<bool>,
+    // The last pulse each conjunction received from each of its inputs (aligned with `inputs`)
+    memory: Vec<Vec<bool>>,
+    queue: VecDeque<(usize, usize, bool)>,
+}
+
+impl<'a> Simulation<'a> {
+    fn new(input: &'a str) -> Self {
+        let mut simulation = Simulation {
+            names: Vec::new(),
+            kinds: Vec::new(),
+            outputs: Vec::new(),
+            inputs: Vec::new(),
+            on: Vec::new(),
+            memory: Vec::new(),
+            queue: VecDeque::new(),
+        };
+
+        for line in input.lines() {
+            let Some((module, destinations)) = line.trim().split_once(" -> ") else {
+                continue;
+            };
+            let (kind, name) = if let Some(name) = module.strip_prefix('%') {
+                (Kind::FlipFlop, name)
+            } else if let Some(name) = module.strip_prefix('&') {
+                (Kind::Conjunction, name)
+            } else {
+                (Kind::Broadcaster, module)
+            };
+
+            let index = simulation.index_or_insert(name);
+            simulation.kinds[index] = kind;
+            for destination in destinations.split(',').map(str::trim) {
+                let target = simulation.index_or_insert(destination);
+                simulation.outputs[index].push(target);
+                simulation.inputs[target].push(index);
+            }
+        }
+
+        simulation.on = vec![false; simulation.names.len()];
+        simulation.memory = simulation.inputs.iter().map(|inputs| vec![false; inputs.len()]).collect();
+        simulation
+    }
+
+    fn index_or_insert(&mut self, name: &'a str) -> usize {
+        self.index(name).unwrap_or_else(|| {
+            self.names.push(name);
+            self.kinds.push(Kind::Output);
+            self.outputs.push(Vec::new());
+            self.inputs.push(Vec::new());
+            self.names.len() - 1
+        })
+    }
+
+    fn index(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|&n| n == name)
+    }
+
+    /// Presses the button once, calling `observe(from, to, pulse)` for every pulse sent
+    /// (true for high). The button's own pulse comes from `usize::MAX`.
+    fn press(&mut self, mut observe: impl FnMut(usize, usize, bool)) {
+        let Some(broadcaster) = self.index("broadcaster") else {
+            return;
+        };
+        self.queue.push_back((usize::MAX, broadcaster, false));
+
+        while let Some((from, to, pulse)) = self.queue.pop_front() {
+            observe(from, to, pulse);
+
+            let output = match self.kinds[to] {
+                Kind::Broadcaster => pulse,
+                // Flip-flops ignore high pulses and toggle on low ones
+                Kind::FlipFlop if pulse => continue,
+                Kind::FlipFlop => {
+                    self.on[to] = !self.on[to];
+                    self.on[to]
+                }
+                // Conjunctions send low only once every input last sent high
+                Kind::Conjunction => {
+                    let slot = self.inputs[to].iter().position(|&input| input == from).unwrap();
+                    self.memory[to][slot] = pulse;
+                    !self.memory[to].iter().all(|&high| high)
+                }
+                Kind::Output => continue,
+            };
+
+            for &next in &self.outputs[to] {
+                self.queue.push_back((to, next, output));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Builds a network shaped like the real input: one 12-bit counter chain per threshold,
+    // each resetting through a conjunction, whose inverted outputs meet at a conjunction
+    // feeding `rx`. Thresholds need their lowest and highest bits set, as in the real input.
+    fn counter_network(thresholds: &[u32]) -> String {
+        let mut lines = Vec::new();
+        let mut starts = Vec::new();
+
+        for (chain, &threshold) in (b'a'..).zip(thresholds) {
+            let flip_flop = |bit: u32| format!("{}{}", chain as char, (b'a' + bit as u8) as char);
+            let conjunction = format!("{}x", chain as char);
+            let inverter = format!("{}y", chain as char);
+
+            for bit in 0..COUNTER_BITS {
+                let mut outputs = Vec::new();
+                if bit + 1 < COUNTER_BITS {
+                    outputs.push(flip_flop(bit + 1));
+                }
+                if threshold >> bit & 1 == 1 {
+                    outputs.push(conjunction.clone());
+                }
+                lines.push(format!("%{} -> {}", flip_flop(bit), outputs.join(", ")));
+            }
+
+            // The conjunction resets the counter by pulsing the bits that aren't part of the threshold.
+            let mut resets: Vec<String> =
+                (0..COUNTER_BITS).filter(|&bit| bit == 0 || threshold >> bit & 1 == 0).map(flip_flop).collect();
+            resets.push(inverter.clone());
+            lines.push(format!("&{conjunction} -> {}", resets.join(", ")));
+            lines.push(format!("&{inverter} -> zz"));
+
+            starts.push(flip_flop(0));
+        }
+
+        lines.push(format!("broadcaster -> {}", starts.join(", ")));
+        lines.push("&zz -> rx".to_string());
+        lines.join("\n") + "\n"
+    }
+
+    fn sorted(mut values: Vec<u32>) -> Vec<u32> {
+        values.sort_unstable();
+        values
+    }
+
+    #[test]
+    fn counters_are_read_from_any_number_of_chains() {
+        let four = counter_network(&[3733, 3911, 4019, 4091]);
+        assert_eq!(sorted(parse_input(&four).unwrap()), vec![3733, 3911, 4019, 4091]);
+        assert_eq!(part2(&four), Ok(3733 * 3911 * 4019 * 4091));
+
+        let three = counter_network(&[2053, 3001, 4093]);
+        assert_eq!(sorted(parse_input(&three).unwrap()), vec![2053, 3001, 4093]);
+        assert_eq!(part2(&three), Ok(2053 * 3001 * 4093));
+    }
+
+    #[test]
+    fn simulation_matches_the_samples_and_part1() {
+        let first = "broadcaster -> a, b, c\n%a -> b\n%b -> c\n%c -> inv\n&inv -> a\n";
+        let (low, high) = simulate(first, 1000);
+        assert_eq!((low, high), (8000, 4000));
+
+        let second = "broadcaster -> a\n%a -> inv, con\n&inv -> b\n%b -> con\n&con -> output\n";
+        assert_eq!(simulate(second, 1), (4, 4));
+        let (low, high) = simulate(second, 1000);
+        assert_eq!(low * high, 11687500);
+
+        for thresholds in [&[3733, 3911, 4019, 4091][..], &[2053, 3001, 4093], &[3583]] {
+            let network = counter_network(thresholds);
+            let (low, high) = simulate(&network, 1000);
+            assert_eq!(part1(&network), Ok(low * high), "{thresholds:?}");
+        }
+    }
+
+    #[test]
+    fn part1_counts_pulses_beyond_u32_with_many_chains() {
+        let thresholds = [2053, 2069, 2081, 2099, 2111, 2129, 2141, 2153, 2161, 2179];
+        let network = counter_network(&thresholds);
+        let (low, high) = simulate(&network, 1000);
+        assert!(low * high > u64::from(u32::MAX));
+        assert_eq!(part1(&network), Ok(low * high));
+    }
+
+    #[test]
+    fn simulated_part2_matches_the_counter_product() {
+        for thresholds in [&[3733, 3911, 4019, 4091][..], &[2053, 3001, 4093]] {
+            let network = counter_network(thresholds);
+            assert_eq!(part2_simulated(&network), part2(&network), "{thresholds:?}");
+        }
+
+        // Thresholds sharing a factor meet at their LCM rather than their product.
+        let network = counter_network(&[2049, 3075]);
+        assert_eq!(part2_simulated(&network), Ok(2049 * 3075 / 3));
+
+        assert_eq!(part2_simulated("broadcaster -> a\n%a -> b\n"), Err("no rx module".to_string()));
+        assert_eq!(part2_simulated("broadcaster -> rx\n"), Err("rx must be fed by a conjunction".to_string()));
+    }
+
+    #[test]
+    fn inputs_without_the_counter_structure_are_errors() {
+        let short = "broadcaster -> a\n%a -> b, c\n%b -> c\n&c -> a\n";
+        assert_eq!(parse_input(short), Ok(vec![3]));
+        assert_eq!(part1(short), Err("counter resets at 3, within the first 1000 presses".to_string()));
+
+        // The first sample has no counters, but simulating it still works.
+        let sample = "broadcaster -> a, b, c\n%a -> b\n%b -> c\n%c -> inv\n&inv -> a\n";
+        assert!(part1(sample).is_err());
+        assert_eq!(simulate(sample, 1000), (8000, 4000));
+
+        assert_eq!(part1(""), Err("no broadcaster module".to_string()));
+        assert_eq!(part2("broadcaster -> zz\n"), Err("zz is not a module".to_string()));
+
+        let long = counter_network(&[4095]).replace("%al -> ax", "%al -> am, ax") + "%am -> ax\n";
+        assert_eq!(part2(&long), Err("counter chain through am is longer than 12 bits".to_string()));
+    }
 }
+
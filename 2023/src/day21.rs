@@ -12,100 +12,303 @@
 // This means we can cross a tile in exactly 131 steps.
 // The reachable area forms a diamond 202300 tiles wide.
 // Due to parity (odd grid size), plots flip odd/even across tile boundaries.
+//
+// `reachable` derives the center, tile size, and diamond width from its arguments,
+// so the same calculation works on the example grid and other step counts.
+
+use std::collections::VecDeque;
 
-const CENTER: i32 = 65;
 const SIZE: i32 = 131;
+const PART1_STEPS: u64 = 64;
+const PART2_STEPS: u64 = 26501365;
 const DIRS: [(i32, i32); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
 
+/// Answers for both parts, or `None` where `reachable` couldn't determine one.
 pub struct Parsed {
-    pub part1: u64,
-    pub part2: u64,
+    pub part1: Option<u64>,
+    pub part2: Option<u64>,
 }
 
 #[aoc_generator(day21)]
 pub fn parse_input(input: &str) -> Parsed {
-    // Parse grid as flat byte vec
-    let grid: Vec<u8> = input.bytes().filter(|&b| b != b'\n').collect();
+    // Parse grid as flat byte vec, dropping line endings (including CRLF)
+    let grid: Vec<u8> = input.bytes().filter(|&b| b != b'\n' && b != b'\r').collect();
 
-    // BFS from center to categorize plots by parity and distance
-    let (even_inner, even_outer, odd_inner, odd_outer) =
-        bfs_categorize(&grid, &[(CENTER, CENTER)], 130);
+    let part1 = reachable(&grid, SIZE, PART1_STEPS);
+    let part2 = reachable(&grid, SIZE, PART2_STEPS);
+
+    Parsed { part1, part2 }
+}
+
+#[aoc(day21, part1)]
+pub fn part1(parsed: &Parsed) -> Result<u64, String> {
+    parsed.part1.ok_or_else(|| UNSETTLED.to_string())
+}
+
+#[aoc(day21, part2)]
+pub fn part2(parsed: &Parsed) -> Result<u64, String> {
+    parsed.part2.ok_or_else(|| UNSETTLED.to_string())
+}
 
-    // Part 1: even plots within 64 steps
-    let part1 = even_inner;
+const UNSETTLED: &str = "plot counts never settle into quadratic growth";
 
-    let even_full = even_inner + even_outer;
-    let odd_full = odd_inner + odd_outer;
-    let remove_corners = odd_outer;
+/// Counts the plots reachable in exactly `steps` steps on the infinitely tiled grid.
+///
+/// `grid` is a flat `size` x `size` grid (without newlines) with the start at its center.
+/// Walks that never get the chance to step off the starting tile are counted directly.
+/// Longer walks use the diamond formula, which requires `steps = center + n * size`,
+/// an odd `size`, and clear roads through the center and around the edges. Grids or step
+/// counts without that structure fall back to the much slower `reachable_quadratic`, which
+/// returns `None` if it can't find a quadratic to extrapolate.
+pub fn reachable(grid: &[u8], size: i32, steps: u64) -> Option<u64> {
+    assert_eq!(grid.len(), (size * size) as usize, "grid must be {size}x{size}");
+    let center = size / 2;
+
+    // Walks that can't leave the starting tile: plots with matching parity within range.
+    let limit = steps.min((size * size) as u64) as u32;
+    let (even, odd, leaves_tile) = bfs_count(grid, size, (center, center), limit);
+    if !leaves_tile {
+        return Some(if steps.is_multiple_of(2) { even } else { odd });
+    }
+
+    let crosses_whole_tiles = steps >= center as u64 && (steps - center as u64).is_multiple_of(size as u64);
+    if size % 2 == 0 || !crosses_whole_tiles || !has_clear_roads(grid, size) {
+        return reachable_quadratic(grid, size, steps);
+    }
+
+    // The walk reaches n tiles out in every direction.
+    let n = (steps - center as u64) / size as u64;
+
+    // BFS from center to categorize plots by parity and distance
+    let full = bfs_categorize(grid, size, &[(center, center)], (size * size) as u32);
+    let even_full = full.even_inner + full.even_outer;
+    let odd_full = full.odd_inner + full.odd_outer;
 
     // BFS from corners to find reachable edge plots
-    let corners = [(0, 0), (130, 0), (0, 130), (130, 130)];
-    let (even_corners, ..) = bfs_categorize(&grid, &corners, 64);
-    let add_corners = even_corners;
+    let far = size - 1;
+    let corners = [(0, 0), (far, 0), (0, far), (far, far)];
+    let corners = bfs_categorize(grid, size, &corners, (center - 1) as u32);
+    let even_corners = corners.even_inner + corners.even_outer;
+    let odd_corners = corners.odd_inner + corners.odd_outer;
 
-    // Calculate diamond area: n = 202300
-    let n = 202300u64;
-    let part2 = n * n * even_full
-        + (n + 1) * (n + 1) * odd_full
-        + n * add_corners
-        - (n + 1) * remove_corners;
+    // Since the size is odd, parity flips across tile boundaries. The outermost ring of tiles
+    // (and every other ring inside it) always shares the parity of the center distance,
+    // so it is the same class of plots regardless of n.
+    let (outer_full, inner_full, remove_corners, add_corners) = if center % 2 == 1 {
+        (odd_full, even_full, full.odd_outer, even_corners)
+    } else {
+        (even_full, odd_full, full.even_outer, odd_corners)
+    };
 
-    Parsed { part1, part2 }
+    // Calculate diamond area
+    Some((n + 1) * (n + 1) * outer_full + n * n * inner_full + n * add_corners - (n + 1) * remove_corners)
 }
 
-#[aoc(day21, part1)]
-pub fn part1(parsed: &Parsed) -> u64 {
-    parsed.part1
+/// Counts the plots reachable in exactly `steps` steps on the infinitely tiled grid by
+/// fitting a quadratic, without relying on any structure in the grid.
+///
+/// Writing `steps = r + n * size`, BFS over the tiled grid gives the counts for
+/// `n = 0, 1, 2, ...`. Once their second difference settles, the count grows quadratically
+/// in `n`, and the quadratic through the last three samples is extrapolated to `n`. The
+/// BFS expands a few grid widths further until that happens, up to `MAX_SAMPLES` widths.
+/// Returns `None` if the second difference still hasn't settled by then (for example when
+/// it alternates between two values), since extrapolating would give a wrong count.
+pub fn reachable_quadratic(grid: &[u8], size: i32, steps: u64) -> Option<u64> {
+    let width = size as u64;
+    let r = steps % width;
+    let n = steps / width;
+
+    let mut samples = 6;
+    loop {
+        // Small step counts are simply counted by BFS.
+        if n <= samples {
+            return Some(count_within(&distance_histogram(grid, size, steps), steps));
+        }
+
+        let histogram = distance_histogram(grid, size, r + samples * width);
+        let y: Vec<i128> = (0..=samples).map(|k| count_within(&histogram, r + k * width) as i128).collect();
+        let d2: Vec<i128> = y.windows(3).map(|w| w[2] - 2 * w[1] + w[0]).collect();
+
+        if d2[d2.len() - 3..].iter().all(|&d| d == d2[d2.len() - 1]) {
+            // Newton's forward differences from the third-to-last sample:
+            // f(k0 + m) = y0 + m * d1 + m * (m - 1) / 2 * d2
+            let k0 = samples as usize - 2;
+            let (y0, y1) = (y[k0], y[k0 + 1]);
+            let d1 = y1 - y0;
+            let d2 = d2[d2.len() - 1];
+            let m = (n - k0 as u64) as i128;
+            return Some((y0 + m * d1 + m * (m - 1) / 2 * d2) as u64);
+        }
+
+        if samples >= MAX_SAMPLES {
+            return None;
+        }
+        samples *= 2;
+    }
 }
 
-#[aoc(day21, part2)]
-pub fn part2(parsed: &Parsed) -> u64 {
-    parsed.part2
+// The most grid widths `reachable_quadratic` expands before giving up.
+const MAX_SAMPLES: u64 = 24;
+
+/// BFS over the infinitely tiled grid from the center, counting the plots at each
+/// distance up to `max_steps`.
+fn distance_histogram(grid: &[u8], size: i32, max_steps: u64) -> Vec<u64> {
+    // Every cell within `max_steps` of the start fits in a window of this side length.
+    let reach = max_steps as i32;
+    let side = 2 * reach + 1;
+    let center = size / 2;
+
+    let mut seen = vec![false; (side * side) as usize];
+    let mut queue = VecDeque::from([(reach, reach, 0u64)]);
+    seen[(reach * side + reach) as usize] = true;
+
+    let mut histogram = vec![0; max_steps as usize + 1];
+    while let Some((x, y, dist)) = queue.pop_front() {
+        histogram[dist as usize] += 1;
+        if dist == max_steps {
+            continue;
+        }
+
+        for &(dx, dy) in &DIRS {
+            let (nx, ny) = (x + dx, y + dy);
+            // Map the window position back onto the tile, keeping the start at its center.
+            let tx = (nx - reach + center).rem_euclid(size);
+            let ty = (ny - reach + center).rem_euclid(size);
+            let idx = (ny * side + nx) as usize;
+            if grid[(ty * size + tx) as usize] != b'#' && !seen[idx] {
+                seen[idx] = true;
+                queue.push_back((nx, ny, dist + 1));
+            }
+        }
+    }
+
+    histogram
+}
+
+/// Counts the plots reachable in exactly `steps` steps from a distance histogram: those
+/// at most `steps` away with the same parity, since the walk can step back and forth.
+fn count_within(histogram: &[u64], steps: u64) -> u64 {
+    histogram.iter().take(steps as usize + 1).skip(steps as usize % 2).step_by(2).sum()
+}
+
+/// Whether the center row and column and all four edges are free of rocks.
+fn has_clear_roads(grid: &[u8], size: i32) -> bool {
+    let center = size / 2;
+    let clear = |x: i32, y: i32| grid[(y * size + x) as usize] != b'#';
+    (0..size).all(|i| {
+        clear(i, center) && clear(center, i) && clear(i, 0) && clear(i, size - 1) && clear(0, i) && clear(size - 1, i)
+    })
+}
+
+/// Lists the plots reachable in exactly `steps` steps from `start` within a single (finite)
+/// `size` x `size` tile, as `(x, y)` in reading order.
+///
+/// A plot is reachable if it's at most `steps` away with the same parity, since the walk can
+/// always step back and forth.
+pub fn reachable_cells(grid: &[u8], size: i32, start: (i32, i32), steps: u32) -> Vec<(i32, i32)> {
+    let mut cells: Vec<_> = tile_distances(grid, size, start, steps)
+        .into_iter()
+        .filter(|&(_, _, dist)| dist % 2 == steps % 2)
+        .map(|(x, y, _)| (x, y))
+        .collect();
+    cells.sort_unstable_by_key(|&(x, y)| (y, x));
+    cells
+}
+
+/// BFS from a single start within one tile, counting the plots at even and odd distances up to
+/// `limit`, and whether a plot on the tile's border is reached with steps left to walk off it.
+fn bfs_count(grid: &[u8], size: i32, start: (i32, i32), limit: u32) -> (u64, u64, bool) {
+    let (mut even, mut odd, mut leaves_tile) = (0, 0, false);
+    for (x, y, dist) in tile_distances(grid, size, start, limit) {
+        if dist % 2 == 0 {
+            even += 1;
+        } else {
+            odd += 1;
+        }
+        leaves_tile |= dist < limit && (x == 0 || y == 0 || x == size - 1 || y == size - 1);
+    }
+
+    (even, odd, leaves_tile)
+}
+
+/// BFS from a single start within one tile, returning every plot within `limit` steps
+/// as `(x, y, distance)` in BFS order.
+fn tile_distances(grid: &[u8], size: i32, start: (i32, i32), limit: u32) -> Vec<(i32, i32, u32)> {
+    let mut seen = vec![false; grid.len()];
+    let mut visited = vec![(start.0, start.1, 0u32)];
+    seen[(start.1 * size + start.0) as usize] = true;
+
+    // The visited list doubles as the queue.
+    let mut head = 0;
+    while let Some(&(x, y, dist)) = visited.get(head) {
+        head += 1;
+        if dist >= limit {
+            continue;
+        }
+
+        for &(dx, dy) in &DIRS {
+            let (nx, ny) = (x + dx, y + dy);
+            if (0..size).contains(&nx) && (0..size).contains(&ny) {
+                let idx = (ny * size + nx) as usize;
+                if grid[idx] != b'#' && !seen[idx] {
+                    seen[idx] = true;
+                    visited.push((nx, ny, dist + 1));
+                }
+            }
+        }
+    }
+
+    visited
+}
+
+/// Plot counts from `bfs_categorize`.
+///
+/// Even and odd refer to the parity of a plot's BFS distance from the nearest start, i.e.
+/// whether a walk of an even or odd number of steps can end there. Inner plots lie within
+/// the diamond around the tile's center (Manhattan distance at most `size / 2`), and outer
+/// plots lie in the four corners outside it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PlotCounts {
+    pub even_inner: u64,
+    pub even_outer: u64,
+    pub odd_inner: u64,
+    pub odd_outer: u64,
 }
 
 /// BFS that categorizes plots by parity and distance from center
+///
+/// `grid` is a flat `size` x `size` tile. The search runs from every start at once and
+/// visits the plots within `limit` steps, without leaving the tile.
 #[inline(always)]
-fn bfs_categorize(
-    grid_input: &[u8],
-    starts: &[(i32, i32)],
-    limit: u32,
-) -> (u64, u64, u64, u64) {
+pub fn bfs_categorize(grid_input: &[u8], size: i32, starts: &[(i32, i32)], limit: u32) -> PlotCounts {
+    assert_eq!(grid_input.len(), (size * size) as usize, "grid must be {size}x{size}");
+    let center = size / 2;
     // Clone grid and mark visited by setting to '#'
     let mut grid = grid_input.to_vec();
-    let mut queue = [(0i32, 0i32, 0u32); 20000];
-    let mut head = 0;
-    let mut tail = 0;
+    // Growable queue, so no grid size or set of starts can overrun it.
+    let mut queue = VecDeque::with_capacity(grid.len());
 
     for &(x, y) in starts {
-        let idx = (y * SIZE + x) as usize;
-        unsafe { *grid.get_unchecked_mut(idx) = b'#' };
-        queue[tail] = (x, y, 0);
-        tail += 1;
+        let idx = (y * size + x) as usize;
+        if grid[idx] != b'#' {
+            grid[idx] = b'#';
+            queue.push_back((x, y, 0u32));
+        }
     }
 
-    let mut even_inner = 0;
-    let mut even_outer = 0;
-    let mut odd_inner = 0;
-    let mut odd_outer = 0;
-
-    while head < tail {
-        let (x, y, dist) = unsafe { *queue.get_unchecked(head) };
-        head += 1;
+    let mut counts = PlotCounts::default();
 
+    while let Some((x, y, dist)) = queue.pop_front() {
         // Manhattan distance from center
-        let manhattan = (x - CENTER).abs() + (y - CENTER).abs();
+        let manhattan = (x - center).abs() + (y - center).abs();
 
         // Categorize by parity and distance
-        if dist & 1 == 1 {
-            if manhattan <= 65 {
-                odd_inner += 1;
-            } else {
-                odd_outer += 1;
-            }
-        } else if dist <= 64 {
-            even_inner += 1;
-        } else {
-            even_outer += 1;
+        let inner = manhattan <= center;
+        match (dist & 1 == 1, inner) {
+            (true, true) => counts.odd_inner += 1,
+            (true, false) => counts.odd_outer += 1,
+            (false, true) => counts.even_inner += 1,
+            (false, false) => counts.even_outer += 1,
         }
 
         if dist < limit {
@@ -113,13 +316,13 @@ fn bfs_categorize(
                 let nx = x + dx;
                 let ny = y + dy;
 
-                if nx >= 0 && nx < SIZE && ny >= 0 && ny < SIZE {
-                    let idx = (ny * SIZE + nx) as usize;
+                if (0..size).contains(&nx) && (0..size).contains(&ny) {
+                    // In bounds: the coordinates were just checked and the grid is size x size.
+                    let idx = (ny * size + nx) as usize;
                     unsafe {
                         if *grid.get_unchecked(idx) != b'#' {
                             *grid.get_unchecked_mut(idx) = b'#';
-                            *queue.get_unchecked_mut(tail) = (nx, ny, dist + 1);
-                            tail += 1;
+                            queue.push_back((nx, ny, dist + 1));
                         }
                     }
                 }
@@ -127,5 +330,185 @@ fn bfs_categorize(
         }
     }
 
-    (even_inner, even_outer, odd_inner, odd_outer)
+    counts
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::rng;
+    use rustc_hash::FxHashSet as HashSet;
+
+    const SAMPLE: &str = "\
+...........
+.....###.#.
+.###.##..#.
+..#.#...#..
+....#.#....
+.##..S####.
+.##..#...#.
+.......##..
+.##.#.####.
+.##..##.##.
+...........
+";
+
+    fn flatten(input: &str) -> Vec<u8> {
+        input.bytes().filter(|&b| b != b'\n').collect()
+    }
+
+    // Counts plots reachable in exactly `steps` steps on the tiled grid, one plot at a time.
+    fn brute_force(grid: &[u8], size: i32, steps: u64) -> u64 {
+        let center = size / 2;
+        let mut seen = HashSet::from_iter([(center, center)]);
+        let mut queue = VecDeque::from([(center, center, 0)]);
+        let mut count = 0;
+
+        while let Some((x, y, dist)) = queue.pop_front() {
+            if dist % 2 == steps % 2 {
+                count += 1;
+            }
+            if dist == steps {
+                continue;
+            }
+            for (dx, dy) in DIRS {
+                let (nx, ny) = (x + dx, y + dy);
+                let tile = (ny.rem_euclid(size) * size + nx.rem_euclid(size)) as usize;
+                if grid[tile] != b'#' && seen.insert((nx, ny)) {
+                    queue.push_back((nx, ny, dist + 1));
+                }
+            }
+        }
+
+        count
+    }
+
+    // Builds a grid shaped like the real input: clear center roads and edges, with rocks
+    // scattered elsewhere but never touching each other, so every plot stays reachable.
+    fn clear_road_grid(size: i32, mut seed: u64) -> Vec<u8> {
+        let center = size / 2;
+        let mut grid = vec![b'.'; (size * size) as usize];
+
+        for y in 1..size - 1 {
+            for x in 1..size - 1 {
+                let roll = rng(&mut seed);
+                let crowded = (-1..=1).any(|dy| (-1..=1).any(|dx| grid[((y + dy) * size + x + dx) as usize] == b'#'));
+                if x != center && y != center && !crowded && roll.is_multiple_of(5) {
+                    grid[(y * size + x) as usize] = b'#';
+                }
+            }
+        }
+
+        grid[(center * size + center) as usize] = b'S';
+        grid
+    }
+
+    #[test]
+    fn sample_six_steps() {
+        assert_eq!(reachable(&flatten(SAMPLE), 11, 6), Some(16));
+    }
+
+    #[test]
+    fn diamond_formula_matches_brute_force() {
+        for (size, seed) in [(41, 9), (43, 11), (45, 3)] {
+            let grid = clear_road_grid(size, seed);
+            let center = size as u64 / 2;
+            assert_eq!(reachable(&grid, size, center), Some(brute_force(&grid, size, center)));
+
+            for n in 1..4 {
+                let steps = center + n * size as u64;
+                assert_eq!(reachable(&grid, size, steps), Some(brute_force(&grid, size, steps)), "{size} {n}");
+            }
+        }
+    }
+
+    #[test]
+    fn quadratic_fit_matches_the_diamond_formula() {
+        for (size, seed) in [(131, 5), (41, 9)] {
+            let grid = clear_road_grid(size, seed);
+            for steps in [26501365, size as u64 / 2 + 7 * size as u64] {
+                assert_eq!(reachable_quadratic(&grid, size, steps), reachable(&grid, size, steps), "{size} {steps}");
+            }
+        }
+
+        // The sample has no clear roads, so it always takes the quadratic fit.
+        let sample = flatten(SAMPLE);
+        for (steps, expected) in [(10, 50), (50, 1594), (100, 6536), (500, 167004), (1000, 668697), (5000, 16733044)] {
+            assert_eq!(reachable(&sample, 11, steps), Some(expected));
+        }
+        assert_eq!(reachable_quadratic(&sample, 11, 300), Some(brute_force(&sample, 11, 300)));
+    }
+
+    #[test]
+    fn unsettled_growth_is_not_extrapolated() {
+        // Parity makes this grid's second difference alternate between 2 and -2 forever.
+        let grid = flatten(".#.\n#S.\n#.#\n");
+        assert_eq!(reachable_quadratic(&grid, 3, 1000), None);
+        assert_eq!(reachable(&grid, 3, 1000), None);
+        assert_eq!(reachable(&grid, 3, 20), Some(brute_force(&grid, 3, 20)));
+
+        let parsed = Parsed { part1: reachable(&grid, 3, 20), part2: reachable(&grid, 3, 1000) };
+        assert_eq!(part2(&parsed), Err("plot counts never settle into quadratic growth".to_string()));
+    }
+
+    #[test]
+    fn crlf_input_parses_like_lf() {
+        let mut real = String::new();
+        for row in clear_road_grid(131, 5).chunks(131) {
+            real += std::str::from_utf8(row).unwrap();
+            real += "\r\n";
+        }
+        let crlf = parse_input(&real);
+        let lf = parse_input(&real.replace('\r', ""));
+        assert_eq!((crlf.part1, crlf.part2), (lf.part1, lf.part2));
+        assert!(crlf.part2.is_some());
+    }
+
+    #[test]
+    fn categorize_sample_plots() {
+        let grid = flatten(SAMPLE);
+        let counts = bfs_categorize(&grid, 11, &[(5, 5)], 6);
+        assert_eq!(counts.even_inner + counts.even_outer, 16);
+        assert_eq!(Some(counts.odd_inner + counts.odd_outer), reachable(&grid, 11, 5));
+        // Plots within five steps of the center all lie in the inner diamond.
+        let near = bfs_categorize(&grid, 11, &[(5, 5)], 5);
+        assert_eq!((near.even_outer, near.odd_outer), (0, 0));
+        assert_eq!(Some(near.odd_inner), reachable(&grid, 11, 5));
+
+        // With no limit, every plot connected to the center is counted exactly once.
+        let full = bfs_categorize(&grid, 11, &[(5, 5)], u32::MAX);
+        let total = full.even_inner + full.even_outer + full.odd_inner + full.odd_outer;
+        assert_eq!(total, tile_distances(&grid, 11, (5, 5), u32::MAX).len() as u64);
+
+        // Repeated starts are only queued once.
+        let corner = bfs_categorize(&grid, 11, &[(0, 0), (0, 0)], 0);
+        assert_eq!(corner, PlotCounts { even_outer: 1, ..PlotCounts::default() });
+    }
+
+    #[test]
+    fn reachable_cells_match_the_counts() {
+        let grid = flatten(SAMPLE);
+        let cells = reachable_cells(&grid, 11, (5, 5), 6);
+        assert_eq!(cells.len(), 16);
+        assert!(cells.contains(&(5, 5)));
+        assert_eq!(reachable_cells(&grid, 11, (5, 5), 1), vec![(5, 4), (4, 5)]);
+
+        let grid = clear_road_grid(131, 5);
+        assert_eq!(Some(reachable_cells(&grid, 131, (65, 65), 64).len() as u64), reachable(&grid, 131, 64));
+    }
+
+    #[test]
+    fn categorize_more_plots_than_the_old_queue_held() {
+        let size = 201;
+        let mut grid = vec![b'.'; (size * size) as usize];
+        grid[(100 * size + 100) as usize] = b'S';
+
+        let starts = [(0, 0), (200, 0), (0, 200), (200, 200), (100, 100)];
+        let counts = bfs_categorize(&grid, size, &starts, u32::MAX);
+        assert_eq!(counts.even_inner + counts.even_outer + counts.odd_inner + counts.odd_outer, 40401);
+
+        let steps = 100 + 201 * 2;
+        assert_eq!(reachable(&grid, size, steps), Some(brute_force(&grid, size, steps)));
+    }
+}
+
@@ -2,7 +2,7 @@
 //
 // Falling bricks simulation using graph theory.
 // Key insights:
-// - x and y coordinates are 0-9, so we use a 10x10 grid
+// - x and y coordinates are small (0-9 in the real input), so we use a flat grid sized to fit them
 // - Bricks sorted by z coordinate form a topological sort
 // - This is a graph where edges represent "supports" relationships
 //
@@ -16,7 +16,87 @@ pub struct Parsed {
 
 #[aoc_generator(day22)]
 pub fn parse_input(input: &str) -> Parsed {
-    // Parse bricks as [x1, y1, z1, x2, y2, z2]
+    let supported_by = settle(&parse_bricks(input));
+
+    // Track safe/unsafe bricks and dominator tree
+    let mut safe = vec![true; supported_by.len()];
+    let mut dominator: Vec<(u16, u16)> = Vec::with_capacity(supported_by.len());
+
+    for below in &supported_by {
+        let mut parent = 0u16;
+        let mut depth = 0u16;
+
+        for (n, &index) in below.iter().enumerate() {
+            if n == 0 {
+                (parent, depth) = dominator[index];
+            } else {
+                // Find common ancestor
+                let (mut a, mut b) = (parent, depth);
+                let (mut x, mut y) = dominator[index];
+
+                // Align depths
+                while b > y {
+                    (a, b) = dominator[a as usize];
+                }
+                while y > b {
+                    (x, y) = dominator[x as usize];
+                }
+
+                // Find common ancestor
+                while a != x {
+                    (a, b) = dominator[a as usize];
+                    (x, _) = dominator[x as usize];
+                }
+
+                (parent, depth) = (a, b);
+            }
+        }
+
+        // If only 1 supporter, mark it unsafe and increase depth
+        if let [only] = below[..] {
+            safe[only] = false;
+            parent = only as u16;
+            depth = dominator[only].1 + 1;
+        }
+
+        dominator.push((parent, depth));
+    }
+
+    let part1 = safe.iter().filter(|&&b| b).count();
+    let part2 = dominator.iter().map(|(_, d)| *d as usize).sum();
+
+    Parsed { part1, part2 }
+}
+
+/// Returns the support graph of the settled bricks, indexed in settled order (ascending by
+/// lowest z in the snapshot): for each brick, the bricks resting directly on it, and the
+/// bricks it rests directly on.
+pub fn supports(input: &str) -> (Vec<Vec<usize>>, Vec<Vec<usize>>) {
+    let supported_by = settle(&parse_bricks(input));
+
+    let mut supports = vec![Vec::new(); supported_by.len()];
+    for (i, below) in supported_by.iter().enumerate() {
+        for &j in below {
+            supports[j].push(i);
+        }
+    }
+
+    (supports, supported_by)
+}
+
+/// Returns the indices (in settled order, as in `supports`) of the bricks that can be
+/// disintegrated without any other brick falling: every brick resting on them has
+/// another supporter.
+pub fn removable_bricks(input: &str) -> Vec<usize> {
+    let (supports, supported_by) = supports(input);
+    (0..supports.len())
+        .filter(|&i| supports[i].iter().all(|&above| supported_by[above].len() > 1))
+        .collect()
+}
+
+// Parses bricks as [x1, y1, z1, x2, y2, z2] with x1 <= x2, y1 <= y2, and z1 <= z2,
+// sorted ascending by lowest z coordinate.
+fn parse_bricks(input: &str) -> Vec<[usize; 6]> {
     let mut bricks = Vec::with_capacity(1500);
     let bytes = input.as_bytes();
     let mut i = 0;
@@ -48,20 +128,27 @@ pub fn parse_input(input: &str) -> Parsed {
         }
 
         if idx == 6 {
-            bricks.push(nums);
+            // Normalize so the start is the lower end on every axis
+            let [x1, y1, z1, x2, y2, z2] = nums;
+            bricks.push([x1.min(x2), y1.min(y2), z1.min(z2), x1.max(x2), y1.max(y2), z1.max(z2)]);
         }
     }
 
-    // x and y limited to 0-9, so 10x10 grid = 100 elements
-    let mut heights = [0u16; 100];
-    let mut indices = [u16::MAX; 100];
-
-    // Track safe/unsafe bricks and dominator tree
-    let mut safe = vec![true; bricks.len()];
-    let mut dominator: Vec<(u16, u16)> = Vec::with_capacity(bricks.len());
-
     // Sort ascending by lowest z coordinate
     bricks.sort_unstable_by_key(|b| b[2]);
+    bricks
+}
+
+// Drops the bricks (in ascending z order) and returns, for each one, the distinct bricks
+// it comes to rest on. Bricks resting on the ground have none.
+fn settle(bricks: &[[usize; 6]]) -> Vec<Vec<usize>> {
+    // Size the grid to the x and y extents of the bricks (10x10 for the real input)
+    let width = bricks.iter().map(|b| b[0].max(b[3]) + 1).max().unwrap_or(0);
+    let depth = bricks.iter().map(|b| b[1].max(b[4]) + 1).max().unwrap_or(0);
+    let mut heights = vec![0u16; width * depth];
+    let mut indices = vec![u16::MAX; width * depth];
+
+    let mut supported_by = Vec::with_capacity(bricks.len());
 
     for (i, brick) in bricks.iter().enumerate() {
         let x1 = brick[0];
@@ -72,58 +159,26 @@ pub fn parse_input(input: &str) -> Parsed {
         let z2 = brick[5];
 
         // Treat 1D array as 2D grid
-        let start = 10 * y1 + x1;
-        let end = 10 * y2 + x2;
-        let step = if y2 > y1 { 10 } else { 1 };
+        let start = width * y1 + x1;
+        let end = width * y2 + x2;
+        let step = if y2 > y1 { width } else { 1 };
         let height = z2 - z1 + 1;
 
-        // Track what's underneath the brick
-        let mut top = 0u16;
-        let mut previous = u16::MAX;
-        let mut underneath = 0u16;
-        let mut parent = 0u16;
-        let mut depth = 0u16;
-
         // Find highest z coordinate underneath brick
+        let mut top = 0u16;
         let mut j = start;
         while j <= end {
             top = top.max(heights[j]);
             j += step;
         }
 
-        // Check which bricks support this one
+        // Check which bricks support this one (the ground has no index)
+        let mut below = Vec::new();
         let mut j = start;
         while j <= end {
-            if heights[j] == top {
-                let index = indices[j];
-                if index != previous {
-                    previous = index;
-                    underneath += 1;
-
-                    if underneath == 1 {
-                        (parent, depth) = dominator[previous as usize];
-                    } else {
-                        // Find common ancestor
-                        let (mut a, mut b) = (parent, depth);
-                        let (mut x, mut y) = dominator[previous as usize];
-
-                        // Align depths
-                        while b > y {
-                            (a, b) = dominator[a as usize];
-                        }
-                        while y > b {
-                            (x, y) = dominator[x as usize];
-                        }
-
-                        // Find common ancestor
-                        while a != x {
-                            (a, b) = dominator[a as usize];
-                            (x, _) = dominator[x as usize];
-                        }
-
-                        (parent, depth) = (a, b);
-                    }
-                }
+            let index = indices[j];
+            if heights[j] == top && index != u16::MAX && !below.contains(&(index as usize)) {
+                below.push(index as usize);
             }
 
             // Update grid with new height and index
@@ -132,20 +187,10 @@ pub fn parse_input(input: &str) -> Parsed {
             j += step;
         }
 
-        // If only 1 supporter, mark it unsafe and increase depth
-        if underneath == 1 {
-            safe[previous as usize] = false;
-            parent = previous;
-            depth = dominator[previous as usize].1 + 1;
-        }
-
-        dominator.push((parent, depth));
+        supported_by.push(below);
     }
 
-    let part1 = safe.iter().filter(|&&b| b).count();
-    let part2 = dominator.iter().map(|(_, d)| *d as usize).sum();
-
-    Parsed { part1, part2 }
+    supported_by
 }
 
 #[aoc(day22, part1)]
@@ -157,3 +202,154 @@ pub fn part1(parsed: &Parsed) -> usize {
 pub fn part2(parsed: &Parsed) -> usize {
     parsed.part2
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::rng;
+    use std::collections::{HashMap, HashSet};
+
+    const SAMPLE: &str = "\
+1,0,1~1,2,1
+0,0,2~2,0,2
+0,2,3~2,2,3
+0,0,4~0,2,4
+2,0,5~2,2,5
+0,1,6~2,1,6
+1,1,8~1,1,9
+";
+
+    // Drops the bricks one cube at a time, returning each brick's supporters (sorted)
+    // and both answers, with chain reactions found by removing each brick in turn.
+    fn brute_force(input: &str) -> (Vec<Vec<usize>>, usize, usize) {
+        let bricks = parse_bricks(input);
+        let mut occupied: HashMap<(usize, usize, usize), usize> = HashMap::new();
+        let mut supported_by = Vec::new();
+
+        for (i, &[x1, y1, z1, x2, y2, z2]) in bricks.iter().enumerate() {
+            let columns: Vec<_> = (x1..=x2).flat_map(|x| (y1..=y2).map(move |y| (x, y))).collect();
+            let mut z = z1;
+            while z > 1 && columns.iter().all(|&(x, y)| !occupied.contains_key(&(x, y, z - 1))) {
+                z -= 1;
+            }
+
+            let mut below: Vec<usize> =
+                columns.iter().filter_map(|&(x, y)| occupied.get(&(x, y, z.wrapping_sub(1))).copied()).collect();
+            below.sort_unstable();
+            below.dedup();
+            supported_by.push(below);
+
+            for &(x, y) in &columns {
+                for cube in z..=z + z2 - z1 {
+                    occupied.insert((x, y, cube), i);
+                }
+            }
+        }
+
+        let n = supported_by.len();
+        let safe = (0..n).filter(|&i| supported_by.iter().all(|below| below != &[i])).count();
+        let falls = (0..n)
+            .map(|i| {
+                let mut fallen = HashSet::from([i]);
+                for (j, below) in supported_by.iter().enumerate().skip(i + 1) {
+                    if !below.is_empty() && below.iter().all(|k| fallen.contains(k)) {
+                        fallen.insert(j);
+                    }
+                }
+                fallen.len() - 1
+            })
+            .sum();
+
+        (supported_by, safe, falls)
+    }
+
+    // Builds `count` random bricks of up to 4 cubes within a `width` x `width` footprint,
+    // spread out in z so they settle in interesting ways.
+    fn random_bricks(mut seed: u64, count: usize, width: usize) -> String {
+        let mut next = |below: usize| rng(&mut seed) as usize % below;
+
+        let mut lines = Vec::new();
+        for i in 0..count {
+            let (length, axis, x, y, z) = (next(4), next(3), next(width), next(width), 2 + i * 5);
+            let (x2, y2, z2) = match axis {
+                0 => ((x + length).min(width - 1), y, z),
+                1 => (x, (y + length).min(width - 1), z),
+                _ => (x, y, z + length),
+            };
+            lines.push(format!("{x},{y},{z}~{x2},{y2},{z2}"));
+        }
+        lines.join("\n") + "\n"
+    }
+
+    #[test]
+    fn sample_answers() {
+        let parsed = parse_input(SAMPLE);
+        assert_eq!((part1(&parsed), part2(&parsed)), (5, 7));
+    }
+
+    #[test]
+    fn coordinates_past_nine() {
+        // A long brick along x, one along y resting on its far end, and a tower on top of that.
+        let input = "0,0,1~20,0,1\n20,0,3~20,20,3\n20,15,5~20,15,9\n";
+        assert_eq!(supports(input).1, vec![vec![], vec![0], vec![1]]);
+        let parsed = parse_input(input);
+        assert_eq!((part1(&parsed), part2(&parsed)), (1, 3));
+
+        for seed in 1..150 {
+            let input = random_bricks(seed, 40, [5, 12, 21][seed as usize % 3]);
+            let (_, safe, falls) = brute_force(&input);
+            let parsed = parse_input(&input);
+            assert_eq!((part1(&parsed), part2(&parsed)), (safe, falls), "{input}");
+        }
+    }
+
+    #[test]
+    fn support_graph_of_the_sample() {
+        let (above, supported_by) = supports(SAMPLE);
+        assert_eq!(supported_by, vec![vec![], vec![0], vec![0], vec![1, 2], vec![1, 2], vec![3, 4], vec![5]]);
+        assert_eq!(above, vec![vec![1, 2], vec![3, 4], vec![3, 4], vec![5], vec![5], vec![6], vec![]]);
+
+        for seed in 1..100 {
+            let input = random_bricks(seed, 40, 12);
+            let (above, supported_by) = supports(&input);
+
+            let mut sorted = supported_by.clone();
+            sorted.iter_mut().for_each(|below| below.sort_unstable());
+            assert_eq!(sorted, brute_force(&input).0, "{input}");
+
+            // The two directions mirror each other, so a lone supporter lists its brick exactly once.
+            for (i, below) in supported_by.iter().enumerate() {
+                for &j in below {
+                    assert_eq!(above[j].iter().filter(|&&k| k == i).count(), 1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn removable_bricks_of_the_sample() {
+        // Every brick but A (0) and F (5), whose removal drops the bricks above them.
+        assert_eq!(removable_bricks(SAMPLE), vec![1, 2, 3, 4, 6]);
+
+        for seed in 1..100 {
+            let input = random_bricks(seed, 40, 8);
+            assert_eq!(removable_bricks(&input).len(), part1(&parse_input(&input)), "{input}");
+        }
+    }
+
+    #[test]
+    fn reversed_coordinates_and_single_bricks() {
+        let reversed = "1,2,1~1,0,1\n2,0,2~0,0,2\n0,2,3~2,2,3\n0,2,4~0,0,4\n2,0,5~2,2,5\n0,1,6~2,1,6\n1,1,9~1,1,8\n";
+        let parsed = parse_input(reversed);
+        assert_eq!((part1(&parsed), part2(&parsed)), (5, 7));
+        assert_eq!(parse_bricks("3,3,5~3,1,4\n"), vec![[3, 1, 4, 3, 3, 5]]);
+
+        let parsed = parse_input("1,1,5~1,1,9");
+        assert_eq!((part1(&parsed), part2(&parsed)), (1, 0));
+        assert_eq!(supports("3,3,5~3,1,5\n"), (vec![vec![]], vec![vec![]]));
+
+        let parsed = parse_input("");
+        assert_eq!((part1(&parsed), part2(&parsed)), (0, 0));
+    }
+}
+
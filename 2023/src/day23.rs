@@ -6,18 +6,19 @@
 // 2. Grid Conversion: Transform graph into 6x6 grid representation
 // 3. Row Deduplication: DP approach that deduplicates states at each row
 //
-// Part 1: Simple DP (directed graph, only right/down allowed)
+// Mazes whose junctions don't form the 6x6 grid fall back to a DFS over the graph.
+//
+// Part 1: Longest path in the DAG formed by following slopes downhill
 // Part 2: Complex DP with row-by-row state exploration
 
 use aoc_runner_derive::{aoc, aoc_generator};
 use std::collections::VecDeque;
 use rustc_hash::{FxHashMap as HashMap, FxHashSet as HashSet};
 
-
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-struct Point {
-    x: i32,
-    y: i32,
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
 }
 
 impl Point {
@@ -48,12 +49,17 @@ struct Grid {
 }
 
 impl Grid {
+    /// Parses the maze, ignoring trailing blank lines and padding short rows with walls
+    /// so every row has the width of the longest one.
     fn parse(input: &str) -> Self {
-        let lines: Vec<&str> = input.lines().collect();
+        let lines: Vec<&str> = input.trim_end().lines().map(str::trim_end).collect();
         let height = lines.len() as i32;
-        let width = lines[0].len() as i32;
-        let bytes: Vec<u8> = lines.iter().flat_map(|line| line.bytes()).collect();
-        Grid { bytes, width, height }
+        let width = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+        let bytes: Vec<u8> = lines
+            .iter()
+            .flat_map(|line| line.bytes().chain(std::iter::repeat_n(b'#', width - line.len())))
+            .collect();
+        Grid { bytes, width: width as i32, height }
     }
 
     fn get(&self, p: Point) -> u8 {
@@ -74,21 +80,33 @@ impl Grid {
 /// We only use 6 elements but use 8 for alignment.
 type Row = [u8; 8];
 
-/// Undirected weighted graph representing the compressed maze.
-struct Graph {
-    start: Point,
-    end: Point,
-    edges: HashMap<Point, Vec<Point>>,
-    weight: HashMap<(Point, Point), u32>,
+/// Weighted graph representing the compressed maze.
+///
+/// Nodes are the start, the end and every junction. `start` and `end` sit one step inside
+/// the maze's entrance and exit, so a full hike is 2 steps longer than its path in the graph.
+/// Graphs from `compress` are undirected (every edge is stored both ways), while graphs from
+/// `compress_directed` only contain edges that can be walked without climbing a slope.
+pub struct Graph {
+    pub start: Point,
+    pub end: Point,
+    pub edges: HashMap<Point, Vec<Point>>,
+    pub weight: HashMap<(Point, Point), u32>,
 }
 
 /// Distilled two dimensional array of only weights.
-pub struct Input {
+struct Distilled {
     extra: u32,
     horizontal: [[u32; 6]; 6],
     vertical: [[u32; 6]; 6],
 }
 
+/// Compressed maze with and without slopes, plus its 6x6 distillation when the junctions form that grid.
+pub struct Input {
+    graph: Graph,
+    directed: Graph,
+    distilled: Option<Distilled>,
+}
+
 /// Helper for iterating set bits
 struct BitIterator(u32);
 
@@ -115,32 +133,31 @@ impl Biterator for u32 {
     }
 }
 
-/// Parse and compress input into 6x6 grid.
+/// Parse and compress input, distilling it into a 6x6 grid when possible.
 #[aoc_generator(day23)]
 pub fn parse_input(input: &str) -> Input {
     let graph = compress(input);
-    graph_to_grid(&graph)
+    let directed = compress_directed(input);
+    let distilled = graph_to_grid(&graph);
+    Input { graph, directed, distilled }
 }
 
-/// Part 1: Directed graph (only right/down) with DP.
+/// Part 1: Slopes make the graph a DAG, so the longest path is a simple DP in topological order.
 #[aoc(day23, part1)]
 pub fn part1(input: &Input) -> u32 {
-    let mut total = [[0; 6]; 6];
-
-    for y in 0..6 {
-        for x in 0..6 {
-            let left = if x > 0 { total[y][x - 1] + input.horizontal[y][x - 1] } else { 0 };
-            let above = if y > 0 { total[y - 1][x] + input.vertical[y - 1][x] } else { 0 };
-            total[y][x] = left.max(above);
-        }
-    }
-
-    input.extra + total[5][5]
+    // Add 2 for the entrance and exit steps trimmed during compression.
+    2 + longest_path_dag(&input.directed)
 }
 
 /// Part 2: Undirected graph, complex DP with row deduplication.
 #[aoc(day23, part2)]
 pub fn part2(input: &Input) -> u32 {
+    let Some(input) = &input.distilled else {
+        // Junctions don't form the 6x6 grid, so search the graph directly.
+        // Add 2 for the entrance and exit steps trimmed during compression.
+        return 2 + longest_path(&input.graph);
+    };
+
     let start = [b'S', 0, 0, 0, 0, 0, 0, 0];
     let end = [0, 0, 0, 0, 0, b'S', 0, 0];
 
@@ -153,8 +170,7 @@ pub fn part2(input: &Input) -> u32 {
     seen.insert(start);
 
     while let Some(row) = todo.pop_front() {
-        let mut neighbors = Vec::new();
-        dfs(&mut neighbors, row, [0; 8], 0, false, 0, 0);
+        let neighbors = dfs(row);
 
         for &(next, ..) in &neighbors {
             if seen.insert(next) {
@@ -197,7 +213,7 @@ pub fn part2(input: &Input) -> u32 {
 }
 
 /// Convert maze to undirected graph.
-fn compress(input: &str) -> Graph {
+pub fn compress(input: &str) -> Graph {
     let mut grid = Grid::parse(input);
     let width = grid.width;
     let height = grid.height;
@@ -227,11 +243,17 @@ fn compress(input: &str) -> Graph {
             if grid.get(from + direction) != b'#' {
                 let mut to = from + direction;
                 let mut cost = 1;
+                let mut dead_end = false;
 
                 while grid.get(to) != b'P' {
                     let neighbors: Vec<Point> =
                         ORTHOGONAL.iter().map(|&o| to + o).filter(|&n| grid.get(n) != b'#').collect();
-                    let next = neighbors[0];
+
+                    // Corridor leads nowhere, so there's no edge to add.
+                    let Some(&next) = neighbors.first() else {
+                        dead_end = true;
+                        break;
+                    };
 
                     // More than 1 neighbor means we've reached a junction.
                     if neighbors.len() > 1 {
@@ -245,9 +267,13 @@ fn compress(input: &str) -> Graph {
                     cost += 1;
                 }
 
+                if dead_end {
+                    continue;
+                }
+
                 // Graph is undirected so add both edges.
-                edges.entry(from).or_insert_with(Vec::new).push(to);
-                edges.entry(to).or_insert_with(Vec::new).push(from);
+                edges.entry(from).or_default().push(to);
+                edges.entry(to).or_default().push(from);
                 weight.insert((from, to), cost);
                 weight.insert((to, from), cost);
 
@@ -262,17 +288,115 @@ fn compress(input: &str) -> Graph {
     Graph { start, end, edges, weight }
 }
 
+/// Convert maze to directed graph, only following slopes downhill.
+pub fn compress_directed(input: &str) -> Graph {
+    let mut grid = Grid::parse(input);
+    let width = grid.width;
+    let height = grid.height;
+
+    // Move start and end away from edge, matching `compress`.
+    let start = Point::new(1, 1);
+    let end = Point::new(width - 2, height - 2);
+
+    grid.set(start + UP, b'#');
+    grid.set(end + DOWN, b'#');
+
+    let is_poi = |p: Point| {
+        p == start || p == end || ORTHOGONAL.iter().filter(|&&o| grid.get(p + o) != b'#').count() > 2
+    };
+
+    // BFS over POIs, walking each corridor to the next POI.
+    let mut poi = VecDeque::new();
+    let mut seen = HashSet::default();
+    let mut edges: HashMap<Point, Vec<Point>> = HashMap::default();
+    let mut weight: HashMap<(Point, Point), u32> = HashMap::default();
+
+    poi.push_back(start);
+    seen.insert(start);
+    edges.insert(start, Vec::new());
+
+    while let Some(from) = poi.pop_front() {
+        // Reaching the end finishes the hike.
+        if from == end {
+            continue;
+        }
+
+        for direction in ORTHOGONAL {
+            let mut previous = from;
+            let mut to = from + direction;
+            let mut step = direction;
+            let mut cost = 1;
+
+            if grid.get(to) == b'#' {
+                continue;
+            }
+
+            // Follow the corridor, abandoning it if it climbs a slope or dead ends.
+            let walkable = loop {
+                if !downhill(grid.get(previous), step) {
+                    break false;
+                }
+                if is_poi(to) {
+                    break true;
+                }
+
+                let Some(&next) = ORTHOGONAL.iter().find(|&&o| to + o != previous && grid.get(to + o) != b'#') else {
+                    break false;
+                };
+
+                previous = to;
+                to = to + next;
+                step = next;
+                cost += 1;
+            };
+
+            // The hike never returns to the start, so skip edges leading back into it.
+            if walkable && to != start {
+                edges.entry(from).or_default().push(to);
+                edges.entry(to).or_default();
+                weight.insert((from, to), cost);
+
+                if seen.insert(to) {
+                    poi.push_back(to);
+                }
+            }
+        }
+    }
+
+    Graph { start, end, edges, weight }
+}
+
+/// Whether a step in `direction` off `tile` is allowed (slopes can only be left downhill).
+fn downhill(tile: u8, direction: Point) -> bool {
+    match tile {
+        b'^' => direction == UP,
+        b'v' => direction == DOWN,
+        b'<' => direction == LEFT,
+        b'>' => direction == RIGHT,
+        _ => true,
+    }
+}
+
 /// Convert graph to 6x6 grid representation.
-fn graph_to_grid(graph: &Graph) -> Input {
+///
+/// Returns `None` if the junctions don't form the expected 6x6 layout
+/// (34 junctions plus start and end, with 3-way junctions around the perimeter).
+fn graph_to_grid(graph: &Graph) -> Option<Distilled> {
     let Graph { start, end, edges, weight } = graph;
 
+    if edges.len() != 36 {
+        return None;
+    }
+
     // Extra steps for start and end (always taken).
-    let extra = 2 + weight[&(*start, edges[start][0])] + weight[&(*end, edges[end][0])];
+    let start_next = *edges.get(start)?.first()?;
+    let end_next = *edges.get(end)?.first()?;
+    let extra = 2 + weight[&(*start, start_next)] + weight[&(*end, end_next)];
 
     // Helper to find next perimeter node.
     let mut seen = HashSet::default();
     let mut next_perimeter = |point: &Point| {
-        *edges[point].iter().find(|&&next| edges[&next].len() == 3 && seen.insert(next)).unwrap()
+        edges[point].iter().find(|&&next| edges[&next].len() == 3 && seen.insert(next)).copied()
     };
 
     let mut grid = [[ORIGIN; 6]; 6];
@@ -280,15 +404,15 @@ fn graph_to_grid(graph: &Graph) -> Input {
     let mut vertical = [[0; 6]; 6];
 
     // Place start in top left.
-    grid[0][0] = next_perimeter(start);
+    grid[0][0] = next_perimeter(start)?;
 
     // Fill out top edge and left edge.
     for i in 1..5 {
         let left = grid[0][i - 1];
         let above = grid[i - 1][0];
 
-        let next_left = next_perimeter(&left);
-        let next_above = next_perimeter(&above);
+        let next_left = next_perimeter(&left)?;
+        let next_above = next_perimeter(&above)?;
 
         grid[0][i] = next_left;
         grid[i][0] = next_above;
@@ -308,8 +432,7 @@ fn graph_to_grid(graph: &Graph) -> Input {
 
             let (&next, _) = edges
                 .iter()
-                .find(|&(&k, v)| v.contains(&above) && v.contains(&left) && seen.insert(k))
-                .unwrap();
+                .find(|&(&k, v)| v.contains(&above) && v.contains(&left) && seen.insert(k))?;
 
             grid[y][x] = next;
             horizontal[y][x - 1] = weight[&(left, next)];
@@ -317,121 +440,538 @@ fn graph_to_grid(graph: &Graph) -> Input {
         }
     }
 
-    Input { extra, horizontal, vertical }
+    Some(Distilled { extra, horizontal, vertical })
 }
 
-/// Modified DFS that only allows rows that skip one node.
-#[allow(clippy::too_many_arguments)]
-fn dfs(
-    result: &mut Vec<(Row, bool, u32, u32)>,
-    previous: Row,
-    current: Row,
+/// Longest simple path from start to end over the compressed graph.
+///
+/// Plain DFS tracking visited junctions in a bitset sized to the graph, so any number of
+/// junctions is supported (although the search is exponential in the worst case).
+/// Returns the summed edge weights, or 0 if the end is unreachable. Add 2 to get the length
+/// of the hike through the original maze, as `part2` does.
+pub fn longest_path(graph: &Graph) -> u32 {
+    let Some(indexed) = index_graph(graph) else {
+        return 0;
+    };
+
+    let mut visited = Visited::new(indexed.nodes.len());
+    visited.insert(indexed.start);
+
+    longest_from(&indexed.adjacency, indexed.start, indexed.end, &mut visited).unwrap_or(0)
+}
+
+/// Junctions visited by the longest simple path, in order from start to end.
+///
+/// Consecutive points are connected in `graph.edges`, and their weights sum to `longest_path`.
+/// Returns an empty route if the end is unreachable.
+pub fn longest_path_route(graph: &Graph) -> Vec<Point> {
+    let Some(indexed) = index_graph(graph) else {
+        return Vec::new();
+    };
+
+    let mut route = vec![indexed.start];
+    let mut best = (0, Vec::new());
+    let mut visited = Visited::new(indexed.nodes.len());
+    visited.insert(indexed.start);
+    longest_route(&indexed.adjacency, indexed.end, &mut visited, 0, &mut route, &mut best);
+
+    best.1.into_iter().map(|i| indexed.nodes[i]).collect()
+}
+
+/// Graph nodes numbered for bitset searches.
+struct Indexed {
+    nodes: Vec<Point>,
+    adjacency: Vec<Vec<(usize, u32)>>,
     start: usize,
-    gap: bool,
-    horizontal: u32,
-    vertical: u32,
+    end: usize,
+}
+
+/// Numbers the graph's nodes and builds weighted adjacency lists over those numbers.
+/// Returns `None` if the start or end has no edges.
+fn index_graph(graph: &Graph) -> Option<Indexed> {
+    let nodes: Vec<Point> = graph.edges.keys().copied().collect();
+
+    let index: HashMap<Point, usize> = nodes.iter().enumerate().map(|(i, &p)| (p, i)).collect();
+    let adjacency: Vec<Vec<(usize, u32)>> = nodes
+        .iter()
+        .map(|from| {
+            graph.edges[from].iter().map(|to| (index[to], graph.weight[&(*from, *to)])).collect()
+        })
+        .collect();
+
+    let start = *index.get(&graph.start)?;
+    let end = *index.get(&graph.end)?;
+
+    Some(Indexed { nodes, adjacency, start, end })
+}
+
+/// Set of visited node numbers, one bit per node.
+struct Visited(Vec<u64>);
+
+impl Visited {
+    fn new(len: usize) -> Self {
+        Visited(vec![0; len.div_ceil(64)])
+    }
+
+    /// Marks `node` as visited, returning `false` if it already was.
+    fn insert(&mut self, node: usize) -> bool {
+        let (word, bit) = (node / 64, 1 << (node % 64));
+        let fresh = self.0[word] & bit == 0;
+        self.0[word] |= bit;
+        fresh
+    }
+
+    fn remove(&mut self, node: usize) {
+        self.0[node / 64] &= !(1 << (node % 64));
+    }
+}
+
+/// Longest path from start to end in a directed acyclic graph, such as from `compress_directed`.
+///
+/// Processes nodes in topological order (Kahn's algorithm), keeping the longest distance from
+/// the start to each node. Returns the summed edge weights, or 0 if the end is unreachable
+/// (including when a cycle blocks it).
+fn longest_path_dag(graph: &Graph) -> u32 {
+    let mut indegree: HashMap<Point, usize> = graph.edges.keys().map(|&p| (p, 0)).collect();
+    for to in graph.edges.values().flatten() {
+        *indegree.entry(*to).or_default() += 1;
+    }
+
+    let mut todo: VecDeque<Point> = indegree.iter().filter(|&(_, &d)| d == 0).map(|(&p, _)| p).collect();
+    let mut distance: HashMap<Point, u32> = HashMap::default();
+    distance.insert(graph.start, 0);
+
+    while let Some(from) = todo.pop_front() {
+        let current = distance.get(&from).copied();
+
+        for &to in graph.edges.get(&from).into_iter().flatten() {
+            // Only extend paths that actually start at the start node.
+            if let Some(current) = current {
+                let next = current + graph.weight[&(from, to)];
+                let e = distance.entry(to).or_insert(next);
+                *e = (*e).max(next);
+            }
+
+            let d = indegree.get_mut(&to).unwrap();
+            *d -= 1;
+            if *d == 0 {
+                todo.push_back(to);
+            }
+        }
+    }
+
+    distance.get(&graph.end).copied().unwrap_or(0)
+}
+
+/// Longest remaining distance from `node` to `end` avoiding the `visited` junctions.
+fn longest_from(adjacency: &[Vec<(usize, u32)>], node: usize, end: usize, visited: &mut Visited) -> Option<u32> {
+    if node == end {
+        return Some(0);
+    }
+
+    let mut best = None;
+
+    for &(next, cost) in &adjacency[node] {
+        if visited.insert(next) {
+            let steps = longest_from(adjacency, next, end, visited).map(|steps| steps + cost);
+            best = best.max(steps);
+            visited.remove(next);
+        }
+    }
+
+    best
+}
+
+/// DFS like `longest_from` that also tracks the current route, copying it into `best`
+/// whenever reaching `end` beats the longest distance found so far.
+fn longest_route(
+    adjacency: &[Vec<(usize, u32)>],
+    end: usize,
+    visited: &mut Visited,
+    steps: u32,
+    route: &mut Vec<usize>,
+    best: &mut (u32, Vec<usize>),
 ) {
-    // We're done, push the result.
-    if start == 6 {
-        result.push((current, gap, horizontal, vertical));
+    let node = route[route.len() - 1];
+
+    if node == end {
+        if best.1.is_empty() || steps > best.0 {
+            *best = (steps, route.clone());
+        }
         return;
     }
 
-    // Previous row has no vertical descending path.
-    if previous[start] == 0 {
-        // Skip at most 1 column per row.
-        if !gap {
-            dfs(result, previous, current, start + 1, true, horizontal, vertical);
+    for &(next, cost) in &adjacency[node] {
+        if visited.insert(next) {
+            route.push(next);
+            longest_route(adjacency, end, visited, steps + cost, route, best);
+            route.pop();
+            visited.remove(next);
         }
+    }
+}
 
-        let mut horizontal = horizontal;
+/// Modified DFS that only allows rows that skip one node.
+///
+/// Uses an explicit stack of partially built rows instead of recursion. Each entry holds
+/// the previous row (possibly modified by a merge), the row built so far, the next column
+/// to fill, whether a column has been skipped, and the horizontal/vertical edge masks.
+fn dfs(previous: Row) -> Vec<(Row, bool, u32, u32)> {
+    let mut result = Vec::new();
+    let mut stack = vec![(previous, [0; 8], 0, false, 0, 0)];
+
+    while let Some((previous, current, start, gap, horizontal, vertical)) = stack.pop() {
+        // We're done, push the result.
+        if start == 6 {
+            result.push((current, gap, horizontal, vertical));
+            continue;
+        }
 
-        for end in (start + 1)..6 {
-            horizontal |= 1 << (end - 1);
+        // Previous row has no vertical descending path.
+        if previous[start] == 0 {
+            // Skip at most 1 column per row.
+            if !gap {
+                stack.push((previous, current, start + 1, true, horizontal, vertical));
+            }
 
-            if previous[end] == 0 {
-                // Start a new path pair.
-                let mut next = current;
-                next[start] = b'S';
-                next[end] = b'E';
+            let mut horizontal = horizontal;
 
-                let vertical = vertical | (1 << start) | (1 << end);
+            for end in (start + 1)..6 {
+                horizontal |= 1 << (end - 1);
 
-                dfs(result, previous, next, end + 1, gap, horizontal, vertical);
-            } else {
-                // Move an existing path.
-                let mut next = current;
-                next[start] = previous[end];
+                if previous[end] == 0 {
+                    // Start a new path pair.
+                    let mut next = current;
+                    next[start] = b'S';
+                    next[end] = b'E';
 
-                let vertical = vertical | (1 << start);
+                    let vertical = vertical | (1 << start) | (1 << end);
 
-                dfs(result, previous, next, end + 1, gap, horizontal, vertical);
-                break;
+                    stack.push((previous, next, end + 1, gap, horizontal, vertical));
+                } else {
+                    // Move an existing path.
+                    let mut next = current;
+                    next[start] = previous[end];
+
+                    let vertical = vertical | (1 << start);
+
+                    stack.push((previous, next, end + 1, gap, horizontal, vertical));
+                    break;
+                }
             }
-        }
-    } else {
-        // Continue vertical path straight down.
-        let mut next = current;
-        next[start] = previous[start];
-        dfs(result, previous, next, start + 1, gap, horizontal, vertical | (1 << start));
-
-        let mut horizontal = horizontal;
-
-        for end in (start + 1)..6 {
-            horizontal |= 1 << (end - 1);
-
-            if previous[end] == 0 {
-                // Move existing path.
-                let mut next = current;
-                next[end] = previous[start];
-
-                let vertical = vertical | (1 << end);
-
-                dfs(result, previous, next, end + 1, gap, horizontal, vertical);
-            } else {
-                // Merge two path segments.
-                match (previous[start], previous[end]) {
-                    // No other changes needed.
-                    (b'E', b'S') => {
-                        dfs(result, previous, current, end + 1, gap, horizontal, vertical);
-                    }
-                    // Convert previous S to E.
-                    (b'E', b'E') => {
-                        let mut next = current;
-
-                        for i in (0..start).rev() {
-                            if current[i] == b'S' {
-                                next[i] = b'E';
-                                break;
-                            }
+        } else {
+            // Continue vertical path straight down.
+            let mut next = current;
+            next[start] = previous[start];
+            stack.push((previous, next, start + 1, gap, horizontal, vertical | (1 << start)));
+
+            let mut horizontal = horizontal;
+
+            for end in (start + 1)..6 {
+                horizontal |= 1 << (end - 1);
+
+                if previous[end] == 0 {
+                    // Move existing path.
+                    let mut next = current;
+                    next[end] = previous[start];
+
+                    let vertical = vertical | (1 << end);
+
+                    stack.push((previous, next, end + 1, gap, horizontal, vertical));
+                } else {
+                    // Merge two path segments.
+                    match (previous[start], previous[end]) {
+                        // No other changes needed.
+                        (b'E', b'S') => {
+                            stack.push((previous, current, end + 1, gap, horizontal, vertical));
                         }
+                        // Convert previous S to E.
+                        (b'E', b'E') => {
+                            let mut next = current;
 
-                        dfs(result, previous, next, end + 1, gap, horizontal, vertical);
-                    }
-                    // Convert next E to S.
-                    (b'S', b'S') => {
-                        let mut modified = previous;
-                        let mut level = 0;
-
-                        for i in (end + 1)..6 {
-                            if previous[i] == b'S' {
-                                level += 1;
-                            }
-                            if previous[i] == b'E' {
-                                if level == 0 {
-                                    modified[i] = b'S';
+                            for i in (0..start).rev() {
+                                if current[i] == b'S' {
+                                    next[i] = b'E';
                                     break;
                                 }
-                                level -= 1;
                             }
+
+                            stack.push((previous, next, end + 1, gap, horizontal, vertical));
                         }
+                        // Convert next E to S.
+                        (b'S', b'S') => {
+                            let mut modified = previous;
+                            let mut level = 0;
+
+                            for i in (end + 1)..6 {
+                                if previous[i] == b'S' {
+                                    level += 1;
+                                }
+                                if previous[i] == b'E' {
+                                    if level == 0 {
+                                        modified[i] = b'S';
+                                        break;
+                                    }
+                                    level -= 1;
+                                }
+                            }
 
-                        dfs(result, modified, current, end + 1, gap, horizontal, vertical);
+                            stack.push((modified, current, end + 1, gap, horizontal, vertical));
+                        }
+                        _ => (), // (S, E) not allowed
                     }
-                    _ => (), // (S, E) not allowed
+                    break;
+                }
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::rng;
+
+    const SAMPLE: &str = "\
+#.#####################
+#.......#########...###
+#######.#########.#.###
+###.....#.>.>.###.#.###
+###v#####.#v#.###.#.###
+###.>...#.#.#.....#...#
+###v###.#.#.#########.#
+###...#.#.#.......#...#
+#####.#.#.#######.#.###
+#.....#.#.#.......#...#
+#.#####.#.#.#########v#
+#.#...#...#...###...>.#
+#.#.#v#######v###.###v#
+#...#.>.#...>.>.#.###.#
+#####v#.#.###v#.#.###.#
+#.....#...#...#.#.#...#
+#.#########.###.#.#.###
+#...###...#...#...#.###
+###.###.#.###v#####v###
+#...#...#.#.>.>.#.>.###
+#.###.###.#.###.#.#v###
+#.....###...###...#...#
+#####################.#
+";
+
+    // Builds a 47x47 maze shaped like the real inputs: junctions on a 6x6 lattice (except the
+    // top right and bottom left corners) joined by corridors that wiggle by up to one tile and
+    // have slopes pointing right or down.
+    fn generate_maze(seed: &mut u64) -> String {
+        let mut grid = vec![vec![b'#'; 47]; 47];
+        let mut wiggle = || rng(seed) as usize % 3;
+        let open = |grid: &mut Vec<Vec<u8>>, x: usize, y: usize| {
+            if grid[y][x] == b'#' {
+                grid[y][x] = b'.';
+            }
+        };
+        let junction = |i: usize| 3 + 8 * i;
+
+        // Entrance leading to the top left junction.
+        for y in 0..=3 {
+            open(&mut grid, 1, y);
+        }
+        open(&mut grid, 2, 3);
+
+        for row in 0..6 {
+            for col in 0..6 {
+                if (row, col) == (0, 5) || (row, col) == (5, 0) {
+                    continue;
+                }
+                let (x, y) = (junction(col), junction(row));
+                open(&mut grid, x, y);
+
+                if (row, col) == (0, 4) {
+                    // Bend around the missing top right corner.
+                    (x..=junction(5)).for_each(|x| open(&mut grid, x, y));
+                    (y..=junction(1)).for_each(|y| open(&mut grid, junction(5), y));
+                    grid[y][x + 1] = b'>';
+                } else if col < 5 {
+                    let shifted = y + wiggle() - 1;
+                    grid[y][x + 1] = b'>';
+                    open(&mut grid, x + 2, y);
+                    (x + 2..=x + 6).for_each(|x| open(&mut grid, x, shifted));
+                    open(&mut grid, x + 6, y);
+                    open(&mut grid, x + 7, y);
+                }
+
+                if (row, col) == (4, 0) {
+                    // Bend around the missing bottom left corner.
+                    (y..=junction(5)).for_each(|y| open(&mut grid, x, y));
+                    (x..=junction(1)).for_each(|x| open(&mut grid, x, junction(5)));
+                    grid[y + 1][x] = b'v';
+                } else if row < 5 {
+                    let shifted = x + wiggle() - 1;
+                    grid[y + 1][x] = b'v';
+                    open(&mut grid, x, y + 2);
+                    (y + 2..=y + 6).for_each(|y| open(&mut grid, shifted, y));
+                    open(&mut grid, x, y + 6);
+                    open(&mut grid, x, y + 7);
                 }
-                break;
             }
         }
+
+        // Exit leaving the bottom right junction.
+        open(&mut grid, 44, 43);
+        (43..47).for_each(|y| open(&mut grid, 45, y));
+
+        grid.into_iter().map(|row| String::from_utf8(row).unwrap() + "\n").collect()
+    }
+
+    #[test]
+    fn sample_answers() {
+        let input = parse_input(SAMPLE);
+        assert_eq!(part1(&input), 94);
+        assert_eq!(part2(&input), 154);
+    }
+
+    #[test]
+    fn fallback_handles_small_mazes() {
+        // Two junctions joined to each other and to both ends. The long way round goes
+        // down the left side, up through the middle and down the right side.
+        let maze = "\
+#.#####
+#.....#
+#.#.#.#
+#.....#
+#####.#
+";
+        let input = parse_input(maze);
+        assert!(input.distilled.is_none());
+        assert_eq!(part2(&input), 12);
+    }
+
+    #[test]
+    fn longest_path_handles_more_than_64_junctions() {
+        // A single chain of 100 junctions, too many for a u64 bitmask.
+        let points: Vec<Point> = (0..100).map(|x| Point::new(x, 0)).collect();
+        let mut edges: HashMap<Point, Vec<Point>> = HashMap::default();
+        let mut weight = HashMap::default();
+        for pair in points.windows(2) {
+            edges.entry(pair[0]).or_default().push(pair[1]);
+            edges.entry(pair[1]).or_default().push(pair[0]);
+            weight.insert((pair[0], pair[1]), 2);
+            weight.insert((pair[1], pair[0]), 2);
+        }
+
+        let graph = Graph { start: points[0], end: points[99], edges, weight };
+        assert_eq!(longest_path(&graph), 2 * 99);
+    }
+
+    #[test]
+    fn fallback_handles_more_than_64_junctions() {
+        // A corridor along the top with a dead end stub below every other tile, which
+        // compresses to one junction per stub.
+        let width = 150;
+        let stubs: String = (1..width - 1).map(|x| if x % 2 == 0 { '.' } else { '#' }).collect();
+        let maze = format!(
+            "#.{}\n#{}#\n#{}#\n{}.#\n",
+            "#".repeat(width - 2),
+            ".".repeat(width - 2),
+            stubs,
+            "#".repeat(width - 2),
+        );
+
+        let input = parse_input(&maze);
+        assert!(input.graph.edges.len() > 64);
+        assert_eq!(part2(&input), width as u32);
+    }
+
+    #[test]
+    fn grid_dp_matches_fallback_search() {
+        let maze = generate_maze(&mut 5);
+        let input = parse_input(&maze);
+        assert!(input.distilled.is_some(), "{maze}");
+        assert_eq!(part2(&input), 2 + longest_path(&input.graph), "{maze}");
+    }
+
+    #[test]
+    fn longest_path_matches_part2() {
+        assert_eq!(2 + longest_path(&compress(SAMPLE)), part2(&parse_input(SAMPLE)));
+        assert_eq!(longest_path(&compress(SAMPLE)), 152);
+    }
+
+    #[test]
+    fn directed_graph_follows_slopes() {
+        let undirected = compress(SAMPLE);
+        let directed = compress_directed(SAMPLE);
+        assert_eq!(longest_path_dag(&directed), 92);
+
+        // Every slope-respecting edge is one of the maze's corridors, walked in one direction only.
+        for (&from, tos) in &directed.edges {
+            for &to in tos {
+                assert_eq!(directed.weight[&(from, to)], undirected.weight[&(from, to)]);
+                assert!(!directed.edges[&to].contains(&from));
+            }
+        }
+    }
+
+    #[test]
+    fn parsing_tolerates_blank_lines_and_short_rows() {
+        let trailing = format!("{SAMPLE}\n\n");
+        let input = parse_input(&trailing);
+        assert_eq!((part1(&input), part2(&input)), (94, 154));
+
+        let crlf = SAMPLE.replace('\n', "\r\n");
+        let input = parse_input(&crlf);
+        assert_eq!((part1(&input), part2(&input)), (94, 154));
+
+        // Trimming the walls off the end of a row pads it back out rather than shifting later rows.
+        let short: String = SAMPLE
+            .lines()
+            .enumerate()
+            .map(|(y, line)| if y == 6 { line.trim_end_matches('#') } else { line })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let grid = Grid::parse(&short);
+        assert_eq!((grid.width, grid.height), (23, 23));
+        assert_eq!(grid.get(Point::new(22, 6)), b'#');
+        assert_eq!(grid.get(Point::new(21, 7)), b'.');
+
+        let input = parse_input(&short);
+        assert_eq!((part1(&input), part2(&input)), (94, 154));
+    }
+
+    #[test]
+    fn row_dfs_reaches_every_row_and_matches_fallback() {
+        let mut todo = vec![[b'S', 0, 0, 0, 0, 0, 0, 0]];
+        let mut seen = HashSet::default();
+        while let Some(row) = todo.pop() {
+            if seen.insert(row) {
+                todo.extend(dfs(row).into_iter().map(|(next, ..)| next));
+            }
+        }
+        assert_eq!(seen.len(), 76);
+
+        // The row DP is only as good as the rows `dfs` builds, so check it against the
+        // graph search on another maze.
+        let maze = generate_maze(&mut 11);
+        let input = parse_input(&maze);
+        assert_eq!(part2(&input), 2 + longest_path(&input.graph), "{maze}");
+    }
+
+    #[test]
+    fn route_follows_edges_and_sums_to_part2() {
+        let graph = compress(SAMPLE);
+        let route = longest_path_route(&graph);
+        assert_eq!(route.first(), Some(&graph.start));
+        assert_eq!(route.last(), Some(&graph.end));
+
+        let mut steps = 0;
+        for pair in route.windows(2) {
+            assert!(graph.edges[&pair[0]].contains(&pair[1]));
+            steps += graph.weight[&(pair[0], pair[1])];
+        }
+        assert_eq!(2 + steps, part2(&parse_input(SAMPLE)));
+
+        // No route at all when the exit can't be reached.
+        assert!(longest_path_route(&compress("#.###\n#.#.#\n###.#\n")).is_empty());
     }
 }
+
@@ -4,58 +4,17 @@
 // Part 2: Find a rock trajectory that will hit all hailstones
 //
 // Uses 2D line intersection (part 1) and 3D vector geometry (part 2)
+//
+// Part 1 works in exact integer arithmetic: each intersection is kept as a fraction over
+// the determinant and compared against the range by cross-multiplying, so points right at
+// the edge of the huge range aren't misclassified by floating point rounding.
 
-use num_integer::Integer;
+use crate::vec3::Vector;
 use rayon::prelude::*;
 
 const RANGE_MIN: f64 = 200_000_000_000_000.0;
 const RANGE_MAX: f64 = 400_000_000_000_000.0;
 
-#[derive(Clone, Copy)]
-struct Vector {
-    x: i128,
-    y: i128,
-    z: i128,
-}
-
-impl Vector {
-    fn from_i64(x: i64, y: i64, z: i64) -> Self {
-        Vector {
-            x: x as i128,
-            y: y as i128,
-            z: z as i128,
-        }
-    }
-
-    fn cross(self, other: Self) -> Self {
-        Vector {
-            x: self.y * other.z - self.z * other.y,
-            y: self.z * other.x - self.x * other.z,
-            z: self.x * other.y - self.y * other.x,
-        }
-    }
-
-    fn gcd(self) -> Self {
-        let gcd = self.x.gcd(&self.y).gcd(&self.z);
-        Vector {
-            x: self.x / gcd,
-            y: self.y / gcd,
-            z: self.z / gcd,
-        }
-    }
-}
-
-impl std::ops::Sub for Vector {
-    type Output = Vector;
-    fn sub(self, other: Vector) -> Vector {
-        Vector {
-            x: self.x - other.x,
-            y: self.y - other.y,
-            z: self.z - other.z,
-        }
-    }
-}
-
 #[aoc_generator(day24)]
 pub fn parse_input(input: &str) -> Vec<[i64; 6]> {
     input
@@ -72,30 +31,46 @@ pub fn parse_input(input: &str) -> Vec<[i64; 6]> {
 
 #[aoc(day24, part1)]
 pub fn part1(hail: &[[i64; 6]]) -> usize {
+    part1_in_range(hail, RANGE_MIN, RANGE_MAX)
+}
+
+/// Counts pairs of hailstones whose future XY paths cross inside the square `lo..=hi`.
+///
+/// The comparison is exact for whole-number bounds; fractional bounds are narrowed to the
+/// whole numbers inside them (`lo` rounds up, `hi` rounds down).
+pub fn part1_in_range(hail: &[[i64; 6]], lo: f64, hi: f64) -> usize {
+    let (lo, hi) = (lo.ceil() as i128, hi.floor() as i128);
+
     (0..hail.len())
         .into_par_iter()
         .map(|i| {
             let mut count = 0;
             for j in (i + 1)..hail.len() {
-                let [a, b, _, c, d, _] = hail[i].map(|v| v as f64);
-                let [e, f, _, g, h, _] = hail[j].map(|v| v as f64);
+                let [a, b, _, c, d, _] = hail[i].map(|v| v as i128);
+                let [e, f, _, g, h, _] = hail[j].map(|v| v as i128);
 
                 let determinant = d * g - c * h;
-                if determinant == 0.0 {
+                if determinant == 0 {
                     continue;
                 }
 
-                let t = (g * (f - b) - h * (e - a)) / determinant;
-                let u = (c * (f - b) - d * (e - a)) / determinant;
+                // t = t_num / determinant and u = u_num / determinant. Flip signs so the
+                // determinant is positive, then the signs of the numerators are the signs of t and u.
+                let sign = determinant.signum();
+                let determinant = determinant * sign;
+                let t_num = (g * (f - b) - h * (e - a)) * sign;
+                let u_num = (c * (f - b) - d * (e - a)) * sign;
 
-                if t < 0.0 || u < 0.0 {
+                if t_num < 0 || u_num < 0 {
                     continue;
                 }
 
-                let x = a + t * c;
-                let y = b + t * d;
+                // x = a + t * c and y = b + t * d, scaled up by the determinant.
+                let x = a * determinant + t_num * c;
+                let y = b * determinant + t_num * d;
+                let range = lo * determinant..=hi * determinant;
 
-                if x >= RANGE_MIN && x <= RANGE_MAX && y >= RANGE_MIN && y <= RANGE_MAX {
+                if range.contains(&x) && range.contains(&y) {
                     count += 1;
                 }
             }
@@ -105,11 +80,29 @@ pub fn part1(hail: &[[i64; 6]]) -> usize {
 }
 
 #[aoc(day24, part2)]
-pub fn part2(hail: &[[i64; 6]]) -> i128 {
-    let h0 = hail[0];
-    let h1 = hail[1];
-    let h2 = hail[2];
+pub fn part2(hail: &[[i64; 6]]) -> Result<i128, String> {
+    let (x, y, z, ..) = rock_state(hail).ok_or("no three hailstones determine the rock's trajectory")?;
+    Ok(x + y + z)
+}
+
+/// Finds the rock's starting position and velocity as `(x, y, z, vx, vy, vz)`.
+///
+/// The rock collides with every hailstone at some non-negative integer time.
+/// Three hailstones pin down the rock unless their paths are degenerate (e.g. parallel
+/// relative velocities), so triples are tried in order until one gives a consistent answer.
+/// Returns `None` if no triple does.
+pub fn rock_state(hail: &[[i64; 6]]) -> Option<(i128, i128, i128, i128, i128, i128)> {
+    let n = hail.len();
+    let (position, velocity) = (0..n)
+        .flat_map(|i| (i + 1..n).flat_map(move |j| (j + 1..n).map(move |k| (i, j, k))))
+        .find_map(|(i, j, k)| solve_triple(hail[i], hail[j], hail[k]))?;
+
+    Some((position.x, position.y, position.z, velocity.x, velocity.y, velocity.z))
+}
 
+// Solves for the rock's position and velocity from three hailstones,
+// returning None if they don't determine a unique integer trajectory.
+fn solve_triple(h0: [i64; 6], h1: [i64; 6], h2: [i64; 6]) -> Option<(Vector, Vector)> {
     let p0 = Vector::from_i64(h0[0], h0[1], h0[2]);
     let v0 = Vector::from_i64(h0[3], h0[4], h0[5]);
 
@@ -125,19 +118,140 @@ pub fn part2(hail: &[[i64; 6]]) -> i128 {
     let p4 = p2 - p0;
     let v4 = v2 - v0;
 
-    // Find rock direction via plane intersection
+    // Find rock direction via plane intersection.
+    // Parallel planes (or a hailstone whose path passes through the origin) leave it undetermined.
     let n3 = v3.cross(p3);
     let n4 = v4.cross(p4);
-    let s = n3.cross(n4).gcd();
+    let s = n3.cross(n4);
+    if s.is_zero() {
+        return None;
+    }
+    let s = s.gcd();
 
     // Find collision times
-    let t = (p3.y * s.x - p3.x * s.y) / (v3.x * s.y - v3.y * s.x);
-    let u = (p4.y * s.x - p4.x * s.y) / (v4.x * s.y - v4.y * s.x);
+    let t = collision_time(p3, v3, s)?;
+    let u = collision_time(p4, v4, s)?;
+    if t == u {
+        return None;
+    }
 
-    // Calculate rock's initial position
-    let a = h1[0] as i128 + h1[1] as i128 + h1[2] as i128;
-    let b = h2[0] as i128 + h2[1] as i128 + h2[2] as i128;
-    let c = (v3 - v4).x + (v3 - v4).y + (v3 - v4).z;
+    // The rock travels between the two collision points in u - t time,
+    // then backtrack from the first collision to find its initial position
+    let c1 = p1 + v1 * t;
+    let c2 = p2 + v2 * u;
+    let d = c2 - c1;
+    let dt = u - t;
+    if d.x % dt != 0 || d.y % dt != 0 || d.z % dt != 0 {
+        return None;
+    }
+    let velocity = d / dt;
+    let position = c1 - velocity * t;
+
+    [(p0, v0), (p1, v1), (p2, v2)]
+        .iter()
+        .all(|&(p, v)| hits(position, velocity, p, v))
+        .then_some((position, velocity))
+}
+
+// Finds the time at which a hailstone (relative to the first one) lies on the rock's line
+// through the origin with direction `s`, i.e. when p + t * v is parallel to s.
+fn collision_time(p: Vector, v: Vector, s: Vector) -> Option<i128> {
+    let (a, b) = (p.cross(s), v.cross(s));
+    [(a.x, b.x), (a.y, b.y), (a.z, b.z)]
+        .into_iter()
+        .find(|&(_, b)| b != 0)
+        .map(|(a, b)| -a / b)
+}
+
+// Checks that a rock and a hailstone meet at some non-negative integer time.
+fn hits(rock_position: Vector, rock_velocity: Vector, position: Vector, velocity: Vector) -> bool {
+    let offset = position - rock_position;
+    let closing = rock_velocity - velocity;
+    if closing.is_zero() {
+        return offset.is_zero();
+    }
+
+    // The offset must be a non-negative integer multiple of the closing velocity.
+    let time = [(offset.x, closing.x), (offset.y, closing.y), (offset.z, closing.z)]
+        .into_iter()
+        .find(|&(_, c)| c != 0)
+        .map(|(o, c)| o / c)
+        .unwrap_or(0);
+    time >= 0 && closing * time == offset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\
+19, 13, 30 @ -2,  1, -2
+18, 19, 22 @ -1, -1, -2
+20, 25, 34 @ -2, -2, -4
+12, 31, 28 @ -1, -2, -1
+20, 19, 15 @  1, -5, -3
+";
+
+    #[test]
+    fn sample_answers() {
+        let hail = parse_input(SAMPLE);
+        assert_eq!(part1(&hail), 0);
+        assert_eq!(part2(&hail), Ok(47));
+    }
 
-    (u * a - t * b + u * t * c) / (u - t)
+    #[test]
+    fn intersections_just_past_the_range_edge() {
+        // The paths cross at x = 4e14 + 0.001, which f64 rounds to 4e14 (inside the range).
+        let hail = [
+            [399_999_999_999_999, 300_000_000_000_000, 0, 1, 0, 0],
+            [400_000_000_000_000, 299_999_999_999_999, 0, 1, 1000, 0],
+        ];
+        assert_eq!(part1(&hail), 0);
+
+        // Shifting the second stone left by one moves the crossing to x = 4e14 - 0.999.
+        let hail = [
+            [399_999_999_999_999, 300_000_000_000_000, 0, 1, 0, 0],
+            [399_999_999_999_999, 299_999_999_999_999, 0, 1, 1000, 0],
+        ];
+        assert_eq!(part1(&hail), 1);
+    }
+
+    #[test]
+    fn rock_hits_the_first_three_hailstones() {
+        let hail = parse_input(SAMPLE);
+        let rock = rock_state(&hail).unwrap();
+        assert_eq!(rock, (24, 13, 10, -3, 1, 2));
+
+        let (x, y, z, vx, vy, vz) = rock;
+        for stone in &hail[..3] {
+            let [hx, hy, hz, hvx, hvy, hvz] = stone.map(|v| v as i128);
+            let t = (hx - x) / (vx - hvx);
+            assert!(t >= 0);
+            assert_eq!((x + t * vx, y + t * vy, z + t * vz), (hx + t * hvx, hy + t * hvy, hz + t * hvz));
+        }
+    }
+
+    #[test]
+    fn degenerate_first_stones_fall_through_to_later_triples() {
+        // The second stone now moves parallel to the first (meeting the rock at t = 3 as before),
+        // so any triple containing both leaves the rock's direction undetermined.
+        let mut hail = parse_input(SAMPLE);
+        hail[1] = [21, 13, 22, -2, 1, -2];
+        assert_eq!(rock_state(&hail), Some((24, 13, 10, -3, 1, 2)));
+        assert_eq!(part2(&hail), Ok(47));
+
+        // Two stones can't pin the rock down at all.
+        assert_eq!(rock_state(&hail[..2]), None);
+        assert_eq!(part2(&hail[..2]), Err("no three hailstones determine the rock's trajectory".to_string()));
+    }
+
+    #[test]
+    fn sample_range_from_the_puzzle() {
+        let hail = parse_input(SAMPLE);
+        assert_eq!(part1_in_range(&hail, 7.0, 27.0), 2);
+
+        // Fractional bounds narrow to the whole numbers inside them.
+        assert_eq!(part1_in_range(&hail, 6.5, 27.5), 2);
+    }
 }
+
@@ -6,11 +6,14 @@
 // Strategy: Use edge betweenness - edges connecting the two groups will be
 // on many shortest paths. Count how often each edge is used in shortest paths
 // between random pairs, then remove the top 3.
+//
+// If those 3 edges don't split the graph, fall back to a deterministic cut: with unit edge
+// capacities, a max flow of exactly 3 from a fixed source to some sink identifies the cut.
 
 use std::collections::VecDeque;
-use rustc_hash::{FxHashMap as HashMap, FxHashSet as HashSet};
+use rustc_hash::FxHashMap as HashMap;
 
-type Graph = HashMap<String, Vec<String>>;
+pub type Graph = HashMap<String, Vec<String>>;
 
 #[aoc_generator(day25)]
 pub fn parse_input(input: &str) -> Graph {
@@ -22,45 +25,62 @@ pub fn parse_input(input: &str) -> Graph {
 
         // Add edges in both directions (undirected graph)
         for neighbor in neighbors {
-            graph.entry(left.to_string())
-                .or_insert_with(Vec::new)
-                .push(neighbor.to_string());
-            graph.entry(neighbor.to_string())
-                .or_insert_with(Vec::new)
-                .push(left.to_string());
+            add_edge(&mut graph, left, neighbor);
+            add_edge(&mut graph, neighbor, left);
         }
     }
 
     graph
 }
 
-fn bfs_path(graph: &Graph, start: &str, end: &str) -> Option<Vec<String>> {
+/// Adds `to` to the neighbors of `from`, so a connection listed more than once (in either
+/// direction) is still a single edge.
+fn add_edge(graph: &mut Graph, from: &str, to: &str) {
+    let neighbors = graph.entry(from.to_string()).or_default();
+    if !neighbors.iter().any(|n| n == to) {
+        neighbors.push(to.to_string());
+    }
+}
+
+/// Assigns each node an id in sorted name order, returning the names by id and the graph's
+/// adjacency lists over those ids.
+fn intern(graph: &Graph) -> (Vec<&str>, Vec<Vec<u16>>) {
+    let mut names: Vec<&str> = graph.keys().map(String::as_str).collect();
+    names.sort_unstable();
+    assert!(names.len() <= 1 << 16, "too many components to number with u16 ids");
+
+    let ids: HashMap<&str, u16> = names.iter().enumerate().map(|(id, &name)| (name, id as u16)).collect();
+    let adjacency = names.iter().map(|&name| graph[name].iter().map(|n| ids[n.as_str()]).collect()).collect();
+
+    (names, adjacency)
+}
+
+fn bfs_path(adjacency: &[Vec<u16>], start: u16, end: u16) -> Option<Vec<u16>> {
     let mut queue = VecDeque::new();
-    let mut visited = HashSet::default();
-    let mut parent: HashMap<String, String> = HashMap::default();
+    let mut parent: Vec<Option<u16>> = vec![None; adjacency.len()];
+    let mut visited = vec![false; adjacency.len()];
 
-    queue.push_back(start.to_string());
-    visited.insert(start.to_string());
+    queue.push_back(start);
+    visited[start as usize] = true;
 
     while let Some(node) = queue.pop_front() {
         if node == end {
             // Reconstruct path
-            let mut path = vec![node.clone()];
+            let mut path = vec![node];
             let mut current = node;
-            while let Some(p) = parent.get(&current) {
-                path.push(p.clone());
-                current = p.clone();
+            while let Some(p) = parent[current as usize] {
+                path.push(p);
+                current = p;
             }
             path.reverse();
             return Some(path);
         }
 
-        if let Some(neighbors) = graph.get(&node) {
-            for neighbor in neighbors {
-                if visited.insert(neighbor.clone()) {
-                    parent.insert(neighbor.clone(), node.clone());
-                    queue.push_back(neighbor.clone());
-                }
+        for &neighbor in &adjacency[node as usize] {
+            if !visited[neighbor as usize] {
+                visited[neighbor as usize] = true;
+                parent[neighbor as usize] = Some(node);
+                queue.push_back(neighbor);
             }
         }
     }
@@ -68,72 +88,352 @@ fn bfs_path(graph: &Graph, start: &str, end: &str) -> Option<Vec<String>> {
     None
 }
 
-fn count_component_size(graph: &Graph, start: &str) -> usize {
-    let mut visited = HashSet::default();
-    let mut queue = VecDeque::new();
+/// Returns the size of every connected component once the `removed` edges are cut, each
+/// given as `(smaller id, larger id)`. Components are ordered by their smallest id.
+fn component_sizes(adjacency: &[Vec<u16>], removed: &[(u16, u16)]) -> Vec<usize> {
+    let mut visited = vec![false; adjacency.len()];
+    let mut sizes = Vec::new();
 
-    queue.push_back(start);
-    visited.insert(start);
+    for start in 0..adjacency.len() as u16 {
+        if visited[start as usize] {
+            continue;
+        }
 
-    while let Some(node) = queue.pop_front() {
-        if let Some(neighbors) = graph.get(node) {
-            for neighbor in neighbors {
-                if visited.insert(neighbor.as_str()) {
+        let mut queue = VecDeque::from([start]);
+        let mut size = 1;
+        visited[start as usize] = true;
+
+        while let Some(node) = queue.pop_front() {
+            for &neighbor in &adjacency[node as usize] {
+                let edge = (node.min(neighbor), node.max(neighbor));
+                if !visited[neighbor as usize] && !removed.contains(&edge) {
+                    visited[neighbor as usize] = true;
+                    size += 1;
                     queue.push_back(neighbor);
                 }
             }
         }
+
+        sizes.push(size);
     }
 
-    visited.len()
+    sizes
 }
 
-#[aoc(day25, part1)]
-pub fn part1(graph: &Graph) -> usize {
-    let mut nodes: Vec<_> = graph.keys().cloned().collect();
-    nodes.sort();  // Ensure deterministic node ordering
-    let mut edge_counts: HashMap<(String, String), usize> = HashMap::default();
+/// Finds the 3 edges whose removal splits the graph in two, each as `(smaller id, larger id)`.
+fn find_cut(adjacency: &[Vec<u16>]) -> Result<Vec<(u16, u16)>, String> {
+    let mut edge_counts: HashMap<(u16, u16), usize> = HashMap::default();
 
     // Sample shortest paths to count edge usage
     // The 3 edges connecting the two components will have highest betweenness
-    let sample_size = nodes.len().min(35);
+    let sample_size = adjacency.len().min(35) as u16;
     for i in 0..sample_size {
         for j in (i + 1)..sample_size {
-            if let Some(path) = bfs_path(graph, &nodes[i], &nodes[j]) {
+            if let Some(path) = bfs_path(adjacency, i, j) {
                 for window in path.windows(2) {
-                    let (a, b) = (&window[0], &window[1]);
-                    let edge = if a < b {
-                        (a.clone(), b.clone())
-                    } else {
-                        (b.clone(), a.clone())
-                    };
-                    *edge_counts.entry(edge).or_insert(0) += 1;
+                    let (a, b) = (window[0], window[1]);
+                    *edge_counts.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+
+    // Find top 3 edges by usage count. Ids follow sorted names, so breaking ties by id
+    // breaks them by name and the choice doesn't depend on hash map iteration order
+    let mut edges: Vec<_> = edge_counts.into_iter().collect();
+    edges.sort_unstable_by_key(|&(edge, count)| (std::cmp::Reverse(count), edge));
+
+    let to_remove: Vec<_> = edges.iter().take(3).map(|&(edge, _)| edge).collect();
+    if component_sizes(adjacency, &to_remove).len() == 2 {
+        return Ok(to_remove);
+    }
+
+    // The heuristic picked the wrong edges, so find the cut exactly
+    let side = min_cut_by_flow(adjacency, 3).ok_or("no 3-edge cut splits the graph")?;
+    let mut cut: Vec<_> = (0..adjacency.len() as u16)
+        .filter(|&a| side[a as usize])
+        .flat_map(|a| adjacency[a as usize].iter().filter(|&&b| !side[b as usize]).map(move |&b| (a.min(b), a.max(b))))
+        .collect();
+    cut.sort_unstable();
+    cut.dedup();
+    Ok(cut)
+}
+
+/// Splits the graph by cutting 3 wires, returning the size of the group containing the
+/// alphabetically first component, the size of the other group, and the wires cut.
+///
+/// Each wire is given with its alphabetically smaller end first. Panics if no 3 wires split
+/// the graph into exactly two groups.
+pub fn min_cut(graph: &Graph) -> (usize, usize, [(String, String); 3]) {
+    try_min_cut(graph).unwrap_or_else(|err| panic!("{err}"))
+}
+
+/// The two group sizes and the 3 wires cut, as returned by `min_cut`.
+type Cut = (usize, usize, [(String, String); 3]);
+
+/// `min_cut`, returning an error instead of panicking.
+fn try_min_cut(graph: &Graph) -> Result<Cut, String> {
+    let (names, adjacency) = intern(graph);  // Ids follow sorted names for deterministic sampling
+    let cut = find_cut(&adjacency)?;
+
+    let sizes = component_sizes(&adjacency, &cut);
+    let &[size1, size2] = sizes.as_slice() else {
+        return Err(format!("cutting 3 wires left {} groups instead of 2", sizes.len()));
+    };
+
+    let wires: Vec<_> = cut.iter().map(|&(a, b)| (names[a as usize].to_string(), names[b as usize].to_string())).collect();
+    let wires = wires.try_into().map_err(|wires: Vec<_>| format!("the cut has {} wires instead of 3", wires.len()))?;
+
+    Ok((size1, size2, wires))
+}
+
+#[aoc(day25, part1)]
+pub fn part1(graph: &Graph) -> Result<usize, String> {
+    let (size1, size2, _) = try_min_cut(graph)?;
+    Ok(size1 * size2)
+}
+
+/// Finds a cut of exactly `size` edges by max flow with unit edge capacities, returning
+/// which nodes are on node 0's side.
+///
+/// Node 0 is on one side of any cut, so some sink on the other side has a max flow equal to
+/// the cut size. After that flow, the nodes still reachable from node 0 through edges with
+/// spare capacity form its side. Each sink only needs `size + 1` augmenting paths to rule out.
+fn min_cut_by_flow(adjacency: &[Vec<u16>], size: usize) -> Option<Vec<bool>> {
+    // Number each undirected edge's two directions as arcs 2e and 2e + 1, so arc ^ 1 is its reverse
+    let mut arcs: Vec<Vec<(usize, usize)>> = vec![Vec::new(); adjacency.len()];
+    let mut edge_count = 0;
+    for (a, neighbors) in adjacency.iter().enumerate() {
+        for b in neighbors.iter().map(|&b| b as usize).filter(|&b| a < b) {
+            arcs[a].push((b, 2 * edge_count));
+            arcs[b].push((a, 2 * edge_count + 1));
+            edge_count += 1;
+        }
+    }
+
+    for sink in 1..adjacency.len() {
+        // Flow along each arc; an undirected edge carries at most one unit either way
+        let mut flow = vec![0i8; 2 * edge_count];
+        let mut paths = 0;
+
+        let side = loop {
+            // BFS for an augmenting path through arcs with spare capacity
+            let mut parent: Vec<Option<usize>> = vec![None; adjacency.len()];
+            let mut seen = vec![false; adjacency.len()];
+            let mut queue = VecDeque::from([0]);
+            seen[0] = true;
+
+            while let Some(node) = queue.pop_front() {
+                for &(next, arc) in &arcs[node] {
+                    if !seen[next] && flow[arc] < 1 {
+                        seen[next] = true;
+                        parent[next] = Some(arc);
+                        queue.push_back(next);
+                    }
                 }
             }
+
+            if !seen[sink] || paths > size {
+                break seen;
+            }
+
+            // Push one unit back along the path from the sink
+            let mut node = sink;
+            while let Some(arc) = parent[node] {
+                flow[arc] += 1;
+                flow[arc ^ 1] -= 1;
+                node = arcs[node].iter().find(|&&(_, a)| a == arc ^ 1).unwrap().0;
+            }
+            paths += 1;
+        };
+
+        if paths == size {
+            return Some(side);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::rng;
+
+    const SAMPLE: &str = "\
+jqt: rhn xhk nvd
+rsh: frs pzl lsr
+xhk: hfx
+cmg: qnr nvd lhk bvb
+rhn: xhk bvb hfx
+bvb: xhk hfx
+pzl: lsr hfx nvd
+qnr: nvd
+ntq: jqt hfx bvb xhk
+nvd: lhk
+lsr: lhk
+rzs: qnr cmg lsr rsh
+frs: qnr lhk lsr
+";
+
+    /// Two random groups of `a_size` and `b_size` components, each a ring with random chords,
+    /// joined by 3 wires.
+    fn two_groups(mut seed: u64, a_size: usize, b_size: usize) -> String {
+        let mut lines = Vec::new();
+        for (prefix, size) in [("a", a_size), ("b", b_size)] {
+            for i in 0..size {
+                let mut neighbors = vec![(i + 1) % size];
+                neighbors.extend((0..6).map(|_| rng(&mut seed) as usize % size).filter(|&j| j != i));
+                let neighbors: Vec<_> = neighbors.iter().map(|j| format!("{prefix}{j:04}")).collect();
+                lines.push(format!("{prefix}{i:04}: {}", neighbors.join(" ")));
+            }
         }
+        for k in 0..3 {
+            lines.push(format!("a{:04}: b{:04}", k * 7 % a_size, k * 5 % b_size));
+        }
+        lines.join("\n")
     }
 
-    // Find top 3 edges by usage count
-    let mut edges: Vec<_> = edge_counts.iter().collect();
-    edges.sort_by_key(|(_, &count)| std::cmp::Reverse(count));
+    #[test]
+    fn sample_answer() {
+        assert_eq!(part1(&parse_input(SAMPLE)), Ok(54));
+    }
 
-    let to_remove: Vec<_> = edges.iter().take(3).map(|(edge, _)| (*edge).clone()).collect();
+    #[test]
+    fn flow_cut_finds_the_three_wires() {
+        let graph = parse_input(SAMPLE);
+        let (names, adjacency) = intern(&graph);
+        let side = min_cut_by_flow(&adjacency, 3).unwrap();
+        let mut group: Vec<_> = names.iter().zip(&side).filter(|&(_, &on)| on).map(|(&name, _)| name).collect();
+        group.sort_unstable();
+        assert_eq!(group, ["bvb", "hfx", "jqt", "ntq", "rhn", "xhk"]);
+        assert_eq!(min_cut_by_flow(&adjacency, 2), None);
 
-    // Create new graph without these 3 edges
-    let mut new_graph = graph.clone();
-    for (a, b) in &to_remove {
-        if let Some(neighbors) = new_graph.get_mut(a) {
-            neighbors.retain(|n| n != b);
+        for seed in 1..20 {
+            let (a_size, b_size) = (40 + seed * 3, 30 + seed);
+            let graph = parse_input(&two_groups(seed as u64 * 7919, a_size, b_size));
+            let side = min_cut_by_flow(&intern(&graph).1, 3).unwrap();
+            assert_eq!(side.iter().filter(|&&on| on).count(), a_size, "seed {seed}");
+            assert_eq!(part1(&graph), Ok(a_size * b_size), "seed {seed}");
         }
-        if let Some(neighbors) = new_graph.get_mut(b) {
-            neighbors.retain(|n| n != a);
+    }
+
+    /// Flood fills the string graph with the `removed` wires cut, as before interning.
+    fn string_component_sizes(graph: &Graph, removed: &[(&str, &str)]) -> Vec<usize> {
+        let mut names: Vec<&String> = graph.keys().collect();
+        names.sort_unstable();
+        let mut seen: Vec<&String> = Vec::new();
+        let mut sizes = Vec::new();
+        for start in names {
+            if seen.contains(&start) {
+                continue;
+            }
+            let mut stack = vec![start];
+            seen.push(start);
+            let mut size = 0;
+            while let Some(node) = stack.pop() {
+                size += 1;
+                for neighbor in &graph[node] {
+                    let cut = removed.iter().any(|&(a, b)| (a, b) == (node, neighbor) || (b, a) == (node, neighbor));
+                    if !cut && !seen.contains(&neighbor) {
+                        seen.push(neighbor);
+                        stack.push(neighbor);
+                    }
+                }
+            }
+            sizes.push(size);
         }
+        sizes
     }
 
-    // Count size of one component
-    let start = nodes[0].as_str();
-    let size1 = count_component_size(&new_graph, start);
-    let size2 = nodes.len() - size1;
+    #[test]
+    fn interned_ids_match_the_string_graph() {
+        let graph = parse_input(SAMPLE);
+        let (names, adjacency) = intern(&graph);
+        assert!(names.windows(2).all(|pair| pair[0] < pair[1]));
+        for (id, neighbors) in adjacency.iter().enumerate() {
+            let neighbors: Vec<_> = neighbors.iter().map(|&n| names[n as usize]).collect();
+            assert_eq!(neighbors, graph[names[id]]);
+        }
 
-    size1 * size2
+        let wires = [("hfx", "pzl"), ("bvb", "cmg"), ("jqt", "nvd")];
+        let id = |name| names.iter().position(|&n| n == name).unwrap() as u16;
+        let cut: Vec<_> = wires.iter().map(|&(a, b)| (id(a).min(id(b)), id(a).max(id(b)))).collect();
+        assert_eq!(component_sizes(&adjacency, &cut), [6, 9]);
+        assert_eq!(component_sizes(&adjacency, &cut), string_component_sizes(&graph, &wires));
+        assert_eq!(component_sizes(&adjacency, &[]), string_component_sizes(&graph, &[]));
+    }
+
+    #[test]
+    fn min_cut_returns_existing_wires() {
+        let graph = parse_input(SAMPLE);
+        let (size1, size2, wires) = min_cut(&graph);
+        assert_eq!((size1, size2), (6, 9));
+        assert_eq!(part1(&graph), Ok(size1 * size2));
+        let mut sorted = wires.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted.map(|(a, b)| format!("{a}/{b}")), ["bvb/cmg", "hfx/pzl", "jqt/nvd"]);
+
+        for seed in 1..10 {
+            let (a_size, b_size) = (40 + seed * 3, 30 + seed);
+            let graph = parse_input(&two_groups(seed as u64 * 7919, a_size, b_size));
+            let (size1, size2, wires) = min_cut(&graph);
+            assert_eq!((size1, size2), (a_size, b_size), "seed {seed}");
+            for (a, b) in &wires {
+                assert!(a < b && graph[a].contains(b) && graph[b].contains(a));
+                assert!(a.starts_with('a') && b.starts_with('b'));
+            }
+        }
+    }
+
+    #[test]
+    fn cut_selection_is_stable() {
+        let graph = parse_input(SAMPLE);
+        let (_, adjacency) = intern(&graph);
+        let first = find_cut(&adjacency).unwrap();
+        let cut = min_cut(&graph);
+
+        for shift in 0..graph.len() {
+            // Rebuild the map from rotated entries so its iteration order can differ
+            let mut entries: Vec<_> = graph.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+            entries.rotate_left(shift);
+            let rebuilt: Graph = entries.into_iter().collect();
+
+            assert_eq!(find_cut(&intern(&rebuilt).1).unwrap(), first);
+            assert_eq!(min_cut(&rebuilt), cut);
+        }
+    }
+
+    #[test]
+    fn duplicated_connections_are_one_wire() {
+        let graph = parse_input(&format!("{SAMPLE}jqt: rhn\nrhn: jqt\nhfx: pzl xhk xhk\n"));
+        assert_eq!(graph, parse_input(SAMPLE));
+        for neighbors in graph.values() {
+            let mut unique = neighbors.clone();
+            unique.sort_unstable();
+            unique.dedup();
+            assert_eq!(unique.len(), neighbors.len());
+        }
+        assert_eq!(part1(&graph), Ok(54));
+    }
+
+    #[test]
+    fn component_sizes_and_bad_cuts() {
+        let (_, adjacency) = intern(&parse_input(SAMPLE));
+        assert_eq!(component_sizes(&adjacency, &[]), [15]);
+        assert_eq!(component_sizes(&adjacency, &find_cut(&adjacency).unwrap()), [6, 9]);
+
+        // Three cliques joined in a triangle: no 3 wires leave exactly two groups
+        let mut lines = Vec::new();
+        for prefix in ["a", "b", "c"] {
+            for i in 0..5 {
+                for j in i + 1..5 {
+                    lines.push(format!("{prefix}{i}: {prefix}{j}"));
+                }
+            }
+        }
+        lines.extend(["a0: b0", "b1: c1", "c2: a2"].map(String::from));
+        assert!(part1(&parse_input(&lines.join("\n"))).is_err());
+    }
 }
+
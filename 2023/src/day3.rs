@@ -3,6 +3,8 @@
 // Find numbers in an engine schematic that are adjacent to symbols (including diagonally).
 // Part 1: Sum all "part numbers" (numbers adjacent to any symbol except '.')
 // Part 2: Find gears ('*' adjacent to exactly 2 numbers) and sum their ratios
+//
+// `sum_adjacent` generalizes part 1 to any set of symbols, with or without diagonal adjacency.
 
 use std::collections::VecDeque;
 
@@ -11,8 +13,11 @@ mod part1_impl {
 
     // Extracts numbers and symbols from a line of the schematic.
     // Numbers are stored as tuples with start index, end index, and value.
-    // Symbols are stored as their indices.
-    pub fn extract_numbers_and_symbols(line: &str) -> (Vec<(usize, usize, u32)>, Vec<usize>) {
+    // Symbols are the non-digit characters matching `is_symbol`, stored as their indices.
+    pub fn extract_numbers_and_symbols(
+        line: &str,
+        is_symbol: impl Fn(char) -> bool,
+    ) -> (Vec<(usize, usize, u32)>, Vec<usize>) {
         let mut numbers = Vec::new();
         let mut symbols = Vec::new();
         let mut start_index = None;
@@ -28,8 +33,8 @@ mod part1_impl {
                     numbers.push((start, i - 1, number));
                     start_index = None;
                 }
-                // Record symbols other than '.' by their indices.
-                if ch != '.' {
+                // Record symbols by their indices.
+                if is_symbol(ch) {
                     symbols.push(i);
                 }
             }
@@ -44,82 +49,103 @@ mod part1_impl {
         (numbers, symbols)
     }
 
-    // Sums the values of numbers that are adjacent to symbols.
-    pub fn sum_numbers_with_adjacent_symbols(
+    // Collects the numbers that are adjacent to symbols.
+    pub fn numbers_with_adjacent_symbols(
         numbers: &[(usize, usize, u32)], // List of numbers in the current line with their start and end indices.
         prev_symbols: &[usize],          // List of symbol indices in the previous line.
         current_symbols: &[usize],       // List of symbol indices in the current line.
         next_symbols: &[usize],          // List of symbol indices in the next line.
-    ) -> u32 {
-        let mut sum = 0;
-
-        // Indices to keep track of our position in the symbol arrays as we iterate.
-        let mut prev_index = 0;
-        let mut curr_index = 0;
-        let mut next_index = 0;
+        include_diagonals: bool,         // Whether symbols diagonal to a number count as adjacent.
+    ) -> Vec<(usize, usize, u32)> {
+        let mut part_numbers = Vec::new();
 
         // Iterate through each number in the current line.
         for &(start, end, number) in numbers {
-            // Define the range to check for adjacent symbols, including diagonals.
+            // Define the range to check for adjacent symbols on either side of the number.
             let range_start = start.saturating_sub(1);
             let range_end = end + 1;
 
+            // Above and below, the range only widens past the number's digits to reach the diagonals.
+            let (vertical_start, vertical_end) = if include_diagonals {
+                (range_start, range_end)
+            } else {
+                (start, end)
+            };
+
             // Check if there are adjacent symbols in any of the three lines.
-            let found_adjacent =
-                check_symbols_in_range(prev_symbols, range_start, range_end, &mut prev_index)
-                    || check_symbols_in_range(
-                        current_symbols,
-                        range_start,
-                        range_end,
-                        &mut curr_index,
-                    )
-                    || check_symbols_in_range(
-                        next_symbols,
-                        range_start,
-                        range_end,
-                        &mut next_index,
-                    );
-
-            // Add to the sum if an adjacent symbol was found.
+            let found_adjacent = check_symbols_in_range(prev_symbols, vertical_start, vertical_end)
+                || check_symbols_in_range(current_symbols, range_start, range_end)
+                || check_symbols_in_range(next_symbols, vertical_start, vertical_end);
+
+            // Keep the number if an adjacent symbol was found.
             if found_adjacent {
-                sum += number;
+                part_numbers.push((start, end, number));
             }
         }
 
-        sum
+        part_numbers
     }
 
     // Checks if there are any symbols within a specific range in a line.
-    fn check_symbols_in_range(
-        symbols: &[usize],
-        range_start: usize,
-        range_end: usize,
-        index: &mut usize,
-    ) -> bool {
-        // Advance the index to skip symbols that are before the range.
-        while *index < symbols.len() && symbols[*index] < range_start {
-            *index += 1;
-        }
+    // Each query binary searches the sorted symbol indices, so adjacent numbers
+    // can share a symbol without depending on the order they're checked in.
+    fn check_symbols_in_range(symbols: &[usize], range_start: usize, range_end: usize) -> bool {
+        // Find the first symbol that isn't before the range.
+        let index = symbols.partition_point(|&symbol| symbol < range_start);
+
+        // Check if that symbol falls within the range.
+        symbols.get(index).is_some_and(|&symbol| symbol <= range_end)
+    }
+}
 
-        // Check if the current symbol falls within the range.
-        if *index < symbols.len() && symbols[*index] <= range_end {
-            return true; // Found an adjacent symbol
-        }
+// Yields the schematic's lines without any '\r', followed by an empty line so the last real
+// line gets processed. An empty input or a single line then works like any other schematic,
+// with empty lines standing in for the missing neighbors.
+fn schematic_lines(input: &str) -> impl Iterator<Item = &str> {
+    input
+        .lines()
+        .map(|line| line.trim_end_matches('\r'))
+        .chain(std::iter::once(""))
+}
 
-        false
-    }
+// The symbols used by the puzzle: anything other than '.' and digits.
+fn is_default_symbol(ch: char) -> bool {
+    ch != '.' && !ch.is_ascii_digit()
 }
 
 #[aoc(day3, part1)]
 pub fn part1(input: &str) -> u32 {
+    sum_adjacent(input, is_default_symbol, true)
+}
+
+/// Returns every part number as its line, starting column, and value, in reading order.
+pub fn part_numbers(input: &str) -> Vec<(usize, usize, u32)> {
+    find_part_numbers(input, is_default_symbol, true)
+}
+
+/// Sums the numbers adjacent to a character matching `is_symbol`.
+///
+/// With `include_diagonals` off, only symbols directly above, below, or beside a number count.
+/// Part 1 is `sum_adjacent(input, |c| c != '.' && !c.is_ascii_digit(), true)`.
+pub fn sum_adjacent(input: &str, is_symbol: impl Fn(char) -> bool, include_diagonals: bool) -> u32 {
+    find_part_numbers(input, is_symbol, include_diagonals).iter().map(|&(_, _, number)| number).sum()
+}
+
+// Finds the numbers adjacent to symbols as (line, starting column, value), in reading order.
+fn find_part_numbers(
+    input: &str,
+    is_symbol: impl Fn(char) -> bool,
+    include_diagonals: bool,
+) -> Vec<(usize, usize, u32)> {
     let mut lines = VecDeque::new();
-    lines.push_back(part1_impl::extract_numbers_and_symbols("")); // Dummy line for the start
+    lines.push_back(part1_impl::extract_numbers_and_symbols("", &is_symbol)); // Dummy line for the start
 
-    let mut total_sum = 0;
+    let mut part_numbers = Vec::new();
 
     // Iterate over each line and the dummy line at the end.
-    for line in input.lines().chain(std::iter::once("")) {
-        let (current_numbers, current_symbols) = part1_impl::extract_numbers_and_symbols(line);
+    // The line being processed is the one before the line just read.
+    for (row, line) in schematic_lines(input).enumerate() {
+        let (current_numbers, current_symbols) = part1_impl::extract_numbers_and_symbols(line, &is_symbol);
         lines.push_back((current_numbers, current_symbols));
 
         // Once we have three lines (previous, current, next), process the current lines numbers against symbols in each line.
@@ -128,56 +154,26 @@ pub fn part1(input: &str) -> u32 {
             let (current_numbers, current_symbols) = &lines[1];
             let (_, next_symbols) = &lines[2];
 
-            // Sum numbers adjacent to symbols and add to the total sum.
-            total_sum += part1_impl::sum_numbers_with_adjacent_symbols(
+            // Record numbers adjacent to symbols along with their position.
+            let adjacent = part1_impl::numbers_with_adjacent_symbols(
                 current_numbers,
                 prev_symbols,
                 current_symbols,
                 next_symbols,
+                include_diagonals,
             );
+            part_numbers.extend(adjacent.into_iter().map(|(start, _, number)| (row - 1, start, number)));
 
             // Remove the oldest line to make room for the next line.
             lines.pop_front();
         }
     }
 
-    total_sum
+    part_numbers
 }
 
 // Part 2 helper functions
 mod part2_impl {
-    pub fn extract_numbers_and_gears(line: &str) -> (Vec<(usize, usize, u32)>, Vec<usize>) {
-        let mut numbers = Vec::new();
-        let mut gears = Vec::new();
-        let mut start_index = None;
-
-        for (i, ch) in line.chars().enumerate() {
-            if ch.is_ascii_digit() {
-                // Start recording a number if not already started.
-                start_index.get_or_insert(i);
-            } else {
-                if let Some(start) = start_index {
-                    // If a number was being recorded, end and save it.
-                    let number: u32 = line[start..=i - 1].parse().unwrap();
-                    numbers.push((start, i - 1, number));
-                    start_index = None;
-                }
-                // Record gears by their indices.
-                if ch == '*' {
-                    gears.push(i);
-                }
-            }
-        }
-
-        // Handle the last number if the line ends with a number.
-        if let Some(start) = start_index {
-            let number: u32 = line[start..].parse().unwrap();
-            numbers.push((start, line.len() - 1, number));
-        }
-
-        (numbers, gears)
-    }
-
     // Function to sum gear ratios
     pub fn sum_gear_ratios(
         gears: &[usize],                         // Gears in the current line
@@ -187,93 +183,53 @@ mod part2_impl {
     ) -> u32 {
         let mut sum = 0;
 
-        // Indices to keep track of our position in the symbol arrays as we iterate.
-        let mut prev_index = 0;
-        let mut curr_index = 0;
-        let mut next_index = 0;
-
         for &gear_index in gears {
-            // Gather adjacent numbers to this gear
-            let mut adjacent_numbers = Vec::new();
-            adjacent_numbers.extend(find_adjacent_numbers(
-                gear_index,
-                prev_numbers,
-                &mut prev_index,
-            ));
-            adjacent_numbers.extend(find_adjacent_numbers(
-                gear_index,
-                next_numbers,
-                &mut next_index,
-            ));
-            if adjacent_numbers.len() <= 2 {
-                adjacent_numbers.extend(find_adjacent_numbers(
-                    gear_index,
-                    current_numbers,
-                    &mut curr_index,
-                ));
-            }
+            // Gather every number adjacent to this gear across all three lines
+            // before applying the exactly-two rule.
+            let adjacent_numbers: Vec<_> = [prev_numbers, current_numbers, next_numbers]
+                .into_iter()
+                .flat_map(|numbers| find_adjacent_numbers(gear_index, numbers))
+                .collect();
 
             // Calculate gear ratio if exactly two numbers are found
-            if adjacent_numbers.len() == 2 {
-                sum += adjacent_numbers[0].2 * adjacent_numbers[1].2;
+            if let [(_, _, first), (_, _, second)] = adjacent_numbers[..] {
+                sum += first * second;
             }
         }
 
         sum
     }
 
-    // Helper function to find adjacent numbers to a given index
+    // Helper function to find the numbers in a line adjacent to a given index
     fn find_adjacent_numbers(
         gear_index: usize,
         numbers: &[(usize, usize, u32)],
-        index: &mut usize,
-    ) -> Vec<(usize, usize, u32)> {
-        let mut adjacent_numbers = Vec::new();
-
-        // Advance the index to the first number that could be adjacent to the gear.
-        while *index < numbers.len() && numbers[*index].1 < gear_index.saturating_sub(1) {
-            *index += 1;
-        }
-
-        // Check numbers in the range of this gear.
-        while *index < numbers.len() {
-            let (start, end, value) = numbers[*index];
-            if end < gear_index.saturating_sub(1) {
-                // If the end index is before the gear, continue to the next number.
-                *index += 1;
-                continue;
-            }
-
-            if start > gear_index + 1 {
-                // If the start index is past the gear, stop checking further numbers.
-                break;
-            }
-
-            if (start..=end).any(|i| (i as i32 - gear_index as i32).abs() <= 1) {
-                // If the number is adjacent to the gear, add it to the list.
-                adjacent_numbers.push((start, end, value));
-                if adjacent_numbers.len() == 2 {
-                    // Only need two adjacent numbers for a gear.
-                    break;
-                }
-            }
-
-            *index += 1;
-        }
-
-        adjacent_numbers
+    ) -> impl Iterator<Item = (usize, usize, u32)> + '_ {
+        // Skip to the first number that doesn't end before the gear's column range.
+        let first = numbers.partition_point(|&(_, end, _)| end < gear_index.saturating_sub(1));
+
+        // Numbers are sorted, so take them until one starts past the gear.
+        numbers[first..]
+            .iter()
+            .copied()
+            .take_while(move |&(start, _, _)| start <= gear_index + 1)
     }
 }
 
+// Extracts numbers and gear ('*') indices from a line of the schematic.
+fn extract_numbers_and_gears(line: &str) -> (Vec<(usize, usize, u32)>, Vec<usize>) {
+    part1_impl::extract_numbers_and_symbols(line, |ch| ch == '*')
+}
+
 #[aoc(day3, part2)]
 pub fn part2(input: &str) -> u32 {
     let mut lines = VecDeque::new();
-    lines.push_back(part2_impl::extract_numbers_and_gears(""));
+    lines.push_back(extract_numbers_and_gears(""));
 
     let mut total_sum = 0;
 
-    for line in input.lines().chain(std::iter::once("")) {
-        let (current_numbers, current_gears) = part2_impl::extract_numbers_and_gears(line);
+    for line in schematic_lines(input) {
+        let (current_numbers, current_gears) = extract_numbers_and_gears(line);
         lines.push_back((current_numbers, current_gears));
 
         if lines.len() == 3 {
@@ -289,3 +245,87 @@ pub fn part2(input: &str) -> u32 {
 
     total_sum
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\
+467..114..
+...*......
+..35..633.
+......#...
+617*......
+.....+.58.
+..592.....
+......755.
+...$.*....
+.664.598..
+";
+
+    #[test]
+    fn sample_answers() {
+        assert_eq!(part1(SAMPLE), 4361);
+        assert_eq!(part2(SAMPLE), 467835);
+    }
+
+    #[test]
+    fn adjacent_numbers_share_a_symbol() {
+        assert_eq!(part1("12*34\n"), 46);
+        assert_eq!(part1("12.34\n..*..\n"), 46);
+        assert_eq!(part1("..*..\n12.34\n"), 46);
+
+        // Later numbers still see symbols checked for earlier ones.
+        assert_eq!(part1("1.2.3\n.#.#.\n"), 6);
+    }
+
+    #[test]
+    fn part_numbers_carry_their_positions() {
+        let numbers = part_numbers(SAMPLE);
+        assert_eq!(numbers.iter().map(|&(_, _, number)| number).sum::<u32>(), part1(SAMPLE));
+        assert_eq!(
+            numbers,
+            vec![(0, 0, 467), (2, 2, 35), (2, 6, 633), (4, 0, 617), (6, 2, 592), (7, 6, 755), (9, 1, 664), (9, 5, 598)]
+        );
+    }
+
+    #[test]
+    fn gears_need_exactly_two_numbers_across_all_rows() {
+        // One number above, one below and one beside disqualifies the gear.
+        assert_eq!(part2(".2.\n3*.\n.4.\n"), 0);
+        assert_eq!(part2("1.1\n.*.\n1.1\n"), 0);
+
+        assert_eq!(part2(".2.\n.*.\n.4.\n"), 8);
+        assert_eq!(part2("2.3\n.*.\n"), 6);
+        assert_eq!(part2("22*33\n"), 726);
+        assert_eq!(part2("22..33\n..*...\n"), 0);
+    }
+
+    #[test]
+    fn custom_symbols_and_orthogonal_adjacency() {
+        assert_eq!(sum_adjacent(SAMPLE, is_default_symbol, true), part1(SAMPLE));
+        // Without diagonals, 467, 592 and 755 no longer qualify.
+        assert_eq!(sum_adjacent(SAMPLE, is_default_symbol, false), 4361 - 467 - 592 - 755);
+
+        let input = ".1.\n2*.\n..3\n";
+        assert_eq!(sum_adjacent(input, |ch| ch == '*', true), 6);
+        assert_eq!(sum_adjacent(input, |ch| ch == '*', false), 3);
+        assert_eq!(sum_adjacent("1#2*3\n", |ch| ch == '#', true), 3);
+    }
+
+    #[test]
+    fn empty_single_line_and_crlf_schematics() {
+        assert_eq!(part1(""), 0);
+        assert_eq!(part2(""), 0);
+
+        assert_eq!(part1("12*3..4\n"), 15);
+        assert_eq!(part1("12*3..4"), 15);
+        assert_eq!(part2("12*3..4"), 36);
+
+        let crlf = SAMPLE.replace('\n', "\r\n");
+        assert_eq!(part1(&crlf), 4361);
+        assert_eq!(part2(&crlf), 467835);
+        assert_eq!(part_numbers(&crlf), part_numbers(SAMPLE));
+    }
+}
+
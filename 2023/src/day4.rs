@@ -4,9 +4,7 @@
 // Part 1: First match = 1 point, each subsequent match doubles the value
 // Part 2: Winning cards spawn copies of following cards
 
-const MAX_NUMBER: usize = 100; // Maximum expected number
-
-// Custom parser for small numbers (less than 100)
+// Custom parser for the card numbers, which are mostly one or two digits
 fn parse_small_number(s: &str) -> Option<usize> {
     let bytes = s.as_bytes();
     if bytes.is_empty() {
@@ -31,47 +29,57 @@ fn parse_small_number(s: &str) -> Option<usize> {
             if digit > 9 {
                 return None;
             }
-            result = result * 10 + digit as usize;
+            result = result.checked_mul(10)?.checked_add(digit as usize)?;
         }
     }
 
     Some(result)
 }
 
+// Winning numbers below this go in a bitset; past it, the bitset would get too large.
+const BITSET_LIMIT: usize = 4096;
+
+// Counts how many of the player's numbers (after the `|`) are winning numbers (before it).
+// Winning numbers below `BITSET_LIMIT` go in a bitset sized to the largest of them, reusing
+// `winning` between cards. Cards with larger numbers are matched by binary search instead.
+fn count_matches(numbers_part: &str, winning: &mut Vec<u64>) -> usize {
+    let (winning_part, player_part) = numbers_part.split_once('|').unwrap_or((numbers_part, ""));
+    let mut winning_numbers: Vec<usize> = winning_part.split_whitespace().filter_map(parse_small_number).collect();
+    let Some(&max_number) = winning_numbers.iter().max() else {
+        return 0;
+    };
+    let player_numbers = player_part.split_whitespace().filter_map(parse_small_number);
+
+    if max_number >= BITSET_LIMIT {
+        winning_numbers.sort_unstable();
+        return player_numbers.filter(|number| winning_numbers.binary_search(number).is_ok()).count();
+    }
+
+    winning.clear();
+    winning.resize(max_number / 64 + 1, 0);
+    for number in winning_numbers {
+        winning[number / 64] |= 1 << (number % 64);
+    }
+
+    // Player numbers above the largest winning number fall outside the bitset and can't match
+    player_numbers
+        .filter(|&number| winning.get(number / 64).is_some_and(|bits| bits & (1 << (number % 64)) != 0))
+        .count()
+}
+
 #[aoc(day4, part1)]
 pub fn part1(input: &str) -> u32 {
+    let mut winning = Vec::new();
     input.lines()
         .map(|line| {
-            let mut score = 0;
-            let mut winning_numbers = [false; MAX_NUMBER];
-            let mut player_numbers_started = false;
-
-            // Skip the "Card X: " part
-            let numbers_part = &line[9..];
-
-            for word in numbers_part.split_whitespace() {
-                // Detect when player numbers start
-                if word == "|" {
-                    player_numbers_started = true;
-                    continue;
-                }
-
-                // Parse the number using custom lightweight parser
-                let Some(number) = parse_small_number(word) else { continue };
-                if number >= MAX_NUMBER {
-                    continue; // Skip numbers that are too large
-                }
-
-                if player_numbers_started {
-                    if winning_numbers[number] {
-                        score = if score == 0 { 1 } else { score * 2 };
-                    }
-                } else {
-                    winning_numbers[number] = true;
-                }
-            }
+            // Skip the "Card X:" part, whatever the width of X
+            let numbers_part = line.split_once(':').map_or(line, |(_, numbers)| numbers);
 
-            score
+            // First match = 1 point, each subsequent match doubles the value
+            match count_matches(numbers_part, &mut winning) {
+                0 => 0,
+                matches => 1 << (matches - 1),
+            }
         })
         .sum()
 }
@@ -80,41 +88,81 @@ pub fn part1(input: &str) -> u32 {
 pub fn part2(input: &str) -> u32 {
     let lines: Vec<&str> = input.lines().collect();
     let mut card_counts = vec![1u32; lines.len()]; // Initialize with 1 for each card
+    let mut winning = Vec::new();
 
     for (card_index, line) in lines.iter().enumerate() {
-        let mut winning_numbers = [false; MAX_NUMBER];
-        let mut player_numbers_started = false;
-        let mut match_count = 0;
-        // Skip the "Card X: " part
-        let numbers_part = &line[9..];
-
-        for word in numbers_part.split_whitespace() {
-            // Detect when player numbers start
-            if word == "|" {
-                player_numbers_started = true;
-                continue;
-            }
-
-            // Parse the number using custom lightweight parser
-            let Some(number) = parse_small_number(word) else { continue };
-            if number >= MAX_NUMBER {
-                continue; // Skip numbers that are too large
-            }
-
-            if player_numbers_started {
-                if winning_numbers[number] {
-                    // Add copies for each subsequent card equal to the number of matches
-                    match_count += 1;
-                    let target_index = card_index + match_count;
-                    if target_index < card_counts.len() {
-                        card_counts[target_index] += card_counts[card_index];
-                    }
-                }
-            } else {
-                winning_numbers[number] = true;
+        // Skip the "Card X:" part, whatever the width of X
+        let numbers_part = line.split_once(':').map_or(*line, |(_, numbers)| numbers);
+        let match_count = count_matches(numbers_part, &mut winning);
+
+        // Add copies for each subsequent card equal to the number of matches
+        for target_index in card_index + 1..=card_index + match_count {
+            if target_index < card_counts.len() {
+                card_counts[target_index] += card_counts[card_index];
             }
         }
     }
 
     card_counts.iter().sum() // Sum up the total number of cards
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\
+Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
+Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19
+Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1
+Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83
+Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36
+Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11
+";
+
+    #[test]
+    fn sample_answers() {
+        assert_eq!(part1(SAMPLE), 13);
+        assert_eq!(part2(SAMPLE), 30);
+    }
+
+    #[test]
+    fn numbers_of_100_and_above() {
+        // The first card matches 100, 250 and 999999, and the second matches 64 and 128,
+        // all past the first word of the bitset
+        let input = "\
+Card   1: 100 250 7 999999 | 999999 250 8 100 64 63
+Card   2: 63 64 128 | 64 65 127 128 129
+Card   3: 5 | 6
+";
+        assert_eq!(part1(input), 4 + 2);
+        assert_eq!(part2(input), 1 + 2 + 4);
+        assert_eq!(parse_small_number("999999"), Some(999999));
+        assert_eq!(parse_small_number("1x0"), None);
+    }
+
+    #[test]
+    fn card_ids_of_any_width() {
+        let padded = SAMPLE.replace("Card ", "Card   ");
+        assert_eq!(part1(&padded), 13);
+        assert_eq!(part2(&padded), 30);
+
+        let mixed = "Card 9: 1 2 | 1 2\nCard 10: 3 4 | 3 5\nCard 100: 5 | 5\nCard   1000: 1 | 2\n";
+        assert_eq!(part1(mixed), 2 + 1 + 1);
+        // The first card copies the next two, and every copy of the second and third copies
+        // the card after it
+        assert_eq!(part2(mixed), 1 + 2 + 4 + 5);
+    }
+
+    #[test]
+    fn numbers_too_large_for_a_bitset() {
+        // The first two cards are matched by binary search, and the third by a bitset again
+        let input = "\
+Card 1: 40000000000 3 | 40000000000 3 4 39999999999
+Card 2: 4096 4095 | 4095 4096 4097
+Card 3: 1 2 | 2 1
+";
+        assert_eq!(parse_small_number("40000000000"), Some(40000000000));
+        assert_eq!(part1(input), 2 + 2 + 2);
+        assert_eq!(part2(input), 1 + 2 + 4);
+    }
+}
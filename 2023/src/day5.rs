@@ -5,218 +5,214 @@
 //
 // Part 1: Individual seeds
 // Part 2: Seed ranges (requires interval arithmetic)
+//
+// Both parts share the interval remapping: a single seed is just a range of length one.
+// Category values are u64s. Interval ends are exclusive, so they're held as u128s internally
+// to keep `start + length` from overflowing for ranges that reach u64::MAX.
 
-// Part 1 implementation
-mod part1_impl {
-    use std::collections::BTreeMap;
-
-    // Define the structure `Almanac` to hold the mappings.
-    pub struct Almanac {
-        // An array of BTreeMaps, each representing a different category of mapping.
-        // BTreeMap's range() method finds overlapping mappings.
-        mappings: [BTreeMap<u64, (u64, u64)>; 7],
-    }
-
-    impl Almanac {
-        // Constructs a new `Almanac` from a string input.
-        pub fn new(input: &str) -> Almanac {
-            let lines = input.lines(); // Split the input into lines.
-                                       // Initialize an array of BTreeMaps, one for each category.
-            let mut mappings = [
-                BTreeMap::new(),
-                BTreeMap::new(),
-                BTreeMap::new(),
-                BTreeMap::new(),
-                BTreeMap::new(),
-                BTreeMap::new(),
-                BTreeMap::new(),
-            ];
-            let mut current_map = 0; // Index to track the current map being processed.
-
-            // Iterate through each line in the input.
-            for line in lines {
-                if line.contains("map:") {
-                    // Increment the index when a new category is encountered.
-                    current_map += 1;
-                } else if !line.is_empty() {
-                    // Parse the mapping line into a vector of parts.
-                    let parts: Vec<u64> = line
-                        .split_whitespace()
-                        .filter_map(|s| s.parse().ok())
-                        .collect();
-                    // Insert the mapping into the appropriate BTreeMap.
-                    if parts.len() == 3 {
-                        mappings[current_map - 1].insert(parts[1], (parts[0], parts[2]));
-                    }
-                }
+use std::collections::BTreeMap;
+use std::ops::Bound::{Excluded, Unbounded};
+use rustc_hash::FxHashMap as HashMap;
+
+// A category mapping keyed on source start, holding (destination start, length).
+type Mapping = BTreeMap<u64, (u64, u64)>;
+
+// A half-open (start, end) interval of category values.
+type Interval = (u128, u128);
+
+// Exclusive upper bound of category values (u64::MAX + 1).
+const LIMIT: u128 = 1 << 64;
+
+// Parses every `X-to-Y map:` block and orders them by following categories from seed to location.
+fn parse_mappings(input: &str) -> Result<Vec<Mapping>, String> {
+    // Maps keyed on their source category, along with their destination category.
+    let mut by_source: HashMap<&str, (&str, Mapping)> = HashMap::default();
+    let mut current = None; // Source category of the map being processed.
+
+    // Iterate through each line in the input.
+    for line in input.lines() {
+        if let Some(header) = line.trim_end().strip_suffix(" map:") {
+            // Start a new map when a category header is encountered.
+            let (source, destination) = header
+                .split_once("-to-")
+                .ok_or_else(|| format!("malformed map header: {line}"))?;
+            if by_source.insert(source, (destination, BTreeMap::new())).is_some() {
+                return Err(format!("duplicate map from {source}"));
+            }
+            current = Some(source);
+        } else if let Some(source) = current {
+            // Parse the mapping line into a vector of parts.
+            let parts: Vec<u64> = line
+                .split_whitespace()
+                .filter_map(|s| s.parse().ok())
+                .collect();
+            // Insert the mapping into the appropriate BTreeMap.
+            if parts.len() == 3 {
+                by_source.get_mut(source).unwrap().1.insert(parts[1], (parts[0], parts[2]));
             }
+        }
+    }
+
+    // Follow the chain of categories. Removing each map as it's used also catches cycles.
+    let mut mappings = Vec::new();
+    let mut category = "seed";
+    while category != "location" {
+        let (destination, map) = by_source
+            .remove(category)
+            .ok_or_else(|| format!("no map from {category}, so the chain to location is broken"))?;
+        mappings.push(map);
+        category = destination;
+    }
 
-            Almanac { mappings }
+    Ok(mappings)
+}
+
+// Remaps a single value through every mapping in order.
+// A value that a mapping would push past u64::MAX saturates to u64::MAX.
+fn remap_point(mappings: &[Mapping], value: u64) -> u64 {
+    remap_interval(mappings, (value as u128, value as u128 + 1))
+        .first()
+        .map_or(u64::MAX, |&(start, _)| start as u64)
+}
+
+// Remaps a half-open (start, end) interval through every mapping in order, returning the
+// resulting intervals as sorted, non-overlapping half-open (start, end) pairs.
+// Any part of a range past u64::MAX, before or after a mapping, is dropped.
+fn remap_interval(mappings: &[Mapping], (start, end): Interval) -> Vec<Interval> {
+    // Start with the initial range
+    let mut ranges = vec![(start, end.min(LIMIT))];
+    // Apply each mapping to the range
+    for map in mappings {
+        // Each range is potentially split or merged during the mapping
+        // flat_map is used to flatten the result into a single vector of ranges
+        ranges = ranges
+            .into_iter()
+            .flat_map(|range| remap_range(range, map))
+            .filter_map(|(start, end)| (start < LIMIT).then_some((start, end.min(LIMIT))))
+            .collect();
+    }
+
+    // Different values can land on overlapping ranges, so sort and merge them
+    ranges.sort_unstable();
+    let mut merged: Vec<Interval> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
         }
+    }
+    merged
+}
 
-        // Processes a single seed through all the mappings to determine its final value.
-        pub fn process_seed(&self, seed: u64) -> u64 {
-            // Iterate over each mapping category in order, applying the mapping to the seed.
-            self.mappings.iter().fold(seed, |acc, map| {
-                // For each category, find the appropriate range and compute the mapped value.
-                map.range(..=acc)
-                    .next_back()
-                    .map_or(acc, |(&src_start, &(dest_start, length))| {
-                        if acc < src_start + length {
-                            // If the seed is within the range, calculate the mapped value.
-                            dest_start + (acc - src_start)
-                        } else {
-                            // If not within any range, return the seed as is.
-                            acc
-                        }
-                    })
-            })
+// Applies a given mapping to a range and returns the resulting ranges
+fn remap_range(range: Interval, map: &Mapping) -> Vec<Interval> {
+    let (start, end) = range;
+    let mut result = Vec::new();
+    let mut current_start = start;
+
+    // Iterate over the range, applying the mapping to each part
+    while current_start < end {
+        // Find the mapping that applies to the current start of the range, if any covers it
+        // (current_start < end <= LIMIT, so it always fits in a u64)
+        let covering = map
+            .range(..=current_start as u64)
+            .next_back()
+            .map(|(&src_start, &(dest_start, range_len))| (src_start as u128, dest_start as u128, range_len as u128))
+            .filter(|&(src_start, _, range_len)| current_start < src_start + range_len);
+
+        if let Some((src_start, dest_start, range_len)) = covering {
+            // The current start is within a mapped range, calculate the new range
+            let src_end = src_start + range_len;
+            let new_start = dest_start + (current_start - src_start);
+            let new_end = dest_start + (src_end.min(end) - src_start);
+            result.push((new_start, new_end)); // Push the newly calculated range
+            current_start = src_end; // Update the current start for the next iteration
+        } else {
+            // The current start is in a gap, so values map to themselves until the next mapping begins
+            let gap_end = map
+                .range((Excluded(current_start as u64), Unbounded))
+                .next()
+                .map_or(end, |(&next_start, _)| end.min(next_start as u128));
+            result.push((current_start, gap_end));
+            current_start = gap_end;
         }
     }
+
+    result // Return the vector of resulting ranges
 }
 
-#[aoc(day5, part1)]
-pub fn part1(input: &str) -> u64 {
-    let mut lines = input.lines(); // Split the input into lines.
-                                   // Parse the first line to extract the seed values.
-    let seeds_line = lines.next().unwrap_or("");
-    let seeds: Vec<u64> = seeds_line
+// Parses the numbers on the first `seeds:` line.
+fn parse_seeds(input: &str) -> Vec<u64> {
+    input
+        .lines()
+        .next()
+        .unwrap_or("")
         .split(": ")
         .nth(1)
         .unwrap_or("")
         .split_whitespace()
         .filter_map(|s| s.parse().ok())
-        .collect();
-
-    // Create an Almanac from the input.
-    let almanac = part1_impl::Almanac::new(input);
-
-    // Iterate over the seeds, process each through the Almanac, and find the minimum.
-    seeds
-        .into_iter()
-        .map(|seed| almanac.process_seed(seed))
-        .min()
-        .unwrap_or(u64::MAX)
+        .collect()
 }
 
-// Part 2 implementation
-mod part2_impl {
-    use std::collections::BTreeMap;
-
-    // Define the structure `Almanac` to hold the mappings.
-    pub struct Almanac {
-        // An array of BTreeMaps, each representing a different category of mapping.
-        // BTreeMap's range() method finds overlapping mappings.
-        mappings: [BTreeMap<u64, (u64, u64)>; 7],
-    }
-
-    impl Almanac {
-        // Constructs a new `Almanac` from a string input.
-        pub fn new(input: &str) -> Almanac {
-            let lines = input.lines(); // Split the input into lines.
-                                       // Initialize an array of BTreeMaps, one for each category.
-            let mut mappings = [
-                BTreeMap::new(),
-                BTreeMap::new(),
-                BTreeMap::new(),
-                BTreeMap::new(),
-                BTreeMap::new(),
-                BTreeMap::new(),
-                BTreeMap::new(),
-            ];
-            let mut current_map = 0; // Index to track the current map being processed.
-
-            // Iterate through each line in the input.
-            for line in lines {
-                if line.contains("map:") {
-                    // Increment the index when a new category is encountered.
-                    current_map += 1;
-                } else if !line.is_empty() {
-                    // Parse the mapping line into a vector of parts.
-                    let parts: Vec<u64> = line
-                        .split_whitespace()
-                        .filter_map(|s| s.parse().ok())
-                        .collect();
-                    // Insert the mapping into the appropriate BTreeMap.
-                    if parts.len() == 3 {
-                        mappings[current_map - 1].insert(parts[1], (parts[0], parts[2]));
-                    }
-                }
-            }
+// Define the structure `Almanac` to hold the mappings.
+pub struct Almanac {
+    // BTreeMaps for each category of mapping, in order from seed to location.
+    // BTreeMap's range() method finds overlapping mappings.
+    mappings: Vec<Mapping>,
+}
 
-            Almanac { mappings }
-        }
+impl Almanac {
+    // Constructs a new `Almanac` from a string input.
+    pub fn new(input: &str) -> Result<Almanac, String> {
+        let mappings = parse_mappings(input)?;
+        Ok(Almanac { mappings })
+    }
 
-        // Processes a given range of seed numbers through all mappings
-        pub fn process_seed_range(&self, start: u64, length: u64) -> u64 {
-            // Start with the initial seed range
-            let mut ranges = vec![(start, start + length)];
-            // Apply each mapping to the range
-            for map in self.mappings.iter() {
-                // Each range is potentially split or merged during the mapping
-                // flat_map is used to flatten the result into a single vector of ranges
-                ranges = ranges
-                    .into_iter()
-                    .flat_map(|range| Self::remap_range(range, map))
-                    .collect();
-            }
-            // The smallest value in the initial range may not remain the smallest after the mappings
-            // Check all ranges to find the true minimum start value
-            ranges
-                .into_iter()
-                .map(|(start, _)| start)
-                .min()
-                .unwrap_or(u64::MAX)
-        }
+    // Processes a single seed through all the mappings to determine its final value.
+    pub fn process_seed(&self, seed: u64) -> u64 {
+        remap_point(&self.mappings, seed)
+    }
 
-        // Applies a given mapping to a range and returns the resulting ranges
-        fn remap_range(range: (u64, u64), map: &BTreeMap<u64, (u64, u64)>) -> Vec<(u64, u64)> {
-            let (start, end) = range;
-            let mut result = Vec::new();
-            let mut current_start = start;
-
-            // Iterate over the range, applying the mapping to each part
-            while current_start < end {
-                // Find the mapping that applies to the current start of the range
-                if let Some((&src_start, &(dest_start, range_len))) =
-                    map.range(..=current_start).next_back()
-                {
-                    let src_end = src_start + range_len;
-                    // If the current start is within a mapped range, calculate the new range
-                    if current_start < src_end {
-                        let new_start = dest_start + (current_start - src_start);
-                        let new_end = dest_start + (src_end.min(end) - src_start);
-                        result.push((new_start, new_end)); // Push the newly calculated range
-                        current_start = src_end; // Update the current start for the next iteration
-                    } else {
-                        // No overlap with the current range, push the original range and break
-                        result.push((current_start, end));
-                        break;
-                    }
-                } else {
-                    // No mapping for this range, push the original range and break
-                    result.push((current_start, end));
-                    break;
-                }
-            }
+    // Processes a given range of seed numbers through all mappings, returning the lowest location
+    pub fn process_seed_range(&self, start: u64, length: u64) -> u64 {
+        // The intervals are sorted, so the first one holds the true minimum
+        self.process_seed_range_all(start, length)
+            .first()
+            .map_or(u64::MAX, |&(start, _)| start)
+    }
 
-            result // Return the vector of resulting ranges
-        }
+    // Processes a given range of seed numbers through all mappings, returning every resulting
+    // location interval as sorted, non-overlapping inclusive (first, last) pairs.
+    // Inclusive ends let an interval reaching u64::MAX be represented exactly.
+    pub fn process_seed_range_all(&self, start: u64, length: u64) -> Vec<(u64, u64)> {
+        remap_interval(&self.mappings, (start as u128, start as u128 + length as u128))
+            .into_iter()
+            .map(|(start, end)| (start as u64, (end - 1) as u64))
+            .collect()
     }
 }
 
+#[aoc(day5, part1)]
+pub fn part1(input: &str) -> Result<u64, String> {
+    // Parse the first line to extract the seed values.
+    let seeds = parse_seeds(input);
+
+    // Create an Almanac from the input.
+    let almanac = Almanac::new(input)?;
+
+    // Iterate over the seeds, process each through the Almanac, and find the minimum.
+    Ok(seeds
+        .into_iter()
+        .map(|seed| almanac.process_seed(seed))
+        .min()
+        .unwrap_or(u64::MAX))
+}
+
 #[aoc(day5, part2)]
-pub fn part2(input: &str) -> u64 {
-    let mut lines = input.lines();
+pub fn part2(input: &str) -> Result<u64, String> {
     // Parse the initial seed ranges from the first line of the input.
-    let seeds_line = lines.next().unwrap_or("");
-    let seed_ranges: Vec<(u64, u64)> = seeds_line
-        .split(": ")
-        .nth(1)
-        .unwrap_or("")
-        .split_whitespace()
-        .filter_map(|s| s.parse().ok())
-        .collect::<Vec<u64>>()
+    let seed_ranges: Vec<(u64, u64)> = parse_seeds(input)
         .chunks(2)
         .filter_map(|chunk| {
             if chunk.len() == 2 {
@@ -227,12 +223,203 @@ pub fn part2(input: &str) -> u64 {
         })
         .collect();
 
-    let almanac = part2_impl::Almanac::new(input);
+    let almanac = Almanac::new(input)?;
 
     // Process each seed range through the almanac and find the minimum result.
-    seed_ranges
+    Ok(seed_ranges
         .into_iter()
         .map(|(start, length)| almanac.process_seed_range(start, length))
         .min()
-        .unwrap_or(u64::MAX)
+        .unwrap_or(u64::MAX))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::rng;
+
+    const SAMPLE: &str = "\
+seeds: 79 14 55 13
+
+seed-to-soil map:
+50 98 2
+52 50 48
+
+soil-to-fertilizer map:
+0 15 37
+37 52 2
+39 0 15
+
+fertilizer-to-water map:
+49 53 8
+0 11 42
+42 0 7
+57 7 4
+
+water-to-light map:
+88 18 7
+18 25 70
+
+light-to-temperature map:
+45 77 23
+81 45 19
+68 64 13
+
+temperature-to-humidity map:
+0 69 1
+1 0 69
+
+humidity-to-location map:
+60 56 37
+56 93 4
+";
+
+    // Builds an almanac with three small seed ranges and up to three rules per map.
+    fn random_almanac(seed: &mut u64) -> String {
+        let mut next = || rng(seed);
+        let categories = ["seed", "soil", "fertilizer", "water", "light", "temperature", "humidity", "location"];
+
+        let mut almanac = String::from("seeds:");
+        for _ in 0..3 {
+            almanac += &format!(" {} {}", next() % 100, next() % 30 + 1);
+        }
+        almanac += "\n";
+
+        for pair in categories.windows(2) {
+            almanac += &format!("\n{}-to-{} map:\n", pair[0], pair[1]);
+            let mut source = next() % 10;
+            for _ in 0..next() % 4 {
+                let length = next() % 20 + 1;
+                almanac += &format!("{} {source} {length}\n", next() % 120);
+                source += length + next() % 10;
+            }
+        }
+        almanac
+    }
+
+    // Maps every seed in every range one value at a time.
+    fn brute_force(input: &str) -> u64 {
+        let seeds = parse_seeds(input);
+        let mut maps: Vec<Vec<[u64; 3]>> = Vec::new();
+        for line in input.lines().skip(1) {
+            if line.ends_with("map:") {
+                maps.push(Vec::new());
+            } else if let Ok(rule) = line.split_whitespace().map(str::parse).collect::<Result<Vec<_>, _>>() {
+                if let [destination, source, length] = rule[..] {
+                    maps.last_mut().unwrap().push([destination, source, length]);
+                }
+            }
+        }
+
+        let locate = |seed: u64| {
+            maps.iter().fold(seed, |value, map| {
+                map.iter()
+                    .find(|&&[_, source, length]| (source..source + length).contains(&value))
+                    .map_or(value, |&[destination, source, _]| destination + value - source)
+            })
+        };
+        seeds.chunks(2).flat_map(|pair| pair[0]..pair[0] + pair[1]).map(locate).min().unwrap()
+    }
+
+    #[test]
+    fn sample_answers() {
+        assert_eq!(part1(SAMPLE), Ok(35));
+        assert_eq!(part2(SAMPLE), Ok(46));
+    }
+
+    #[test]
+    fn range_spanning_a_gap_and_a_mapping() {
+        // Seeds 5..10 fall in a gap, while 10..15 map down to 0..5.
+        let input = "seeds: 5 10\n\nseed-to-location map:\n0 10 5\n";
+        assert_eq!(part2(input), Ok(0));
+
+        let map = Mapping::from([(10, (0, 5)), (20, (100, 5))]);
+        assert_eq!(remap_range((5, 30), &map), vec![(5, 10), (0, 5), (15, 20), (100, 105), (25, 30)]);
+    }
+
+    #[test]
+    fn part2_matches_brute_force() {
+        let mut seed = 11;
+        for _ in 0..300 {
+            let almanac = random_almanac(&mut seed);
+            assert_eq!(part2(&almanac), Ok(brute_force(&almanac)), "{almanac}");
+        }
+    }
+
+    #[test]
+    fn all_ranges_are_sorted_and_start_at_the_minimum() {
+        let almanac = Almanac::new(SAMPLE).unwrap();
+        for (start, length) in [(79, 14), (55, 13)] {
+            let ranges = almanac.process_seed_range_all(start, length);
+            assert_eq!(ranges[0].0, almanac.process_seed_range(start, length));
+        }
+
+        let mut seed = 5;
+        for _ in 0..300 {
+            let almanac = Almanac::new(&random_almanac(&mut seed)).unwrap();
+            let ranges = almanac.process_seed_range_all(10, 50);
+            assert!(ranges.iter().all(|&(first, last)| first <= last));
+            assert!(ranges.windows(2).all(|pair| pair[0].1 < pair[1].0));
+            assert!(ranges.iter().map(|&(first, last)| last - first + 1).sum::<u64>() <= 50);
+        }
+
+        // Inclusive ends can hold the very last value.
+        let input = format!("seeds: 0 1\n\nseed-to-location map:\n{} 0 5\n", u64::MAX - 4);
+        let almanac = Almanac::new(&input).unwrap();
+        assert_eq!(almanac.process_seed_range_all(0, 5), vec![(u64::MAX - 4, u64::MAX)]);
+        assert_eq!(almanac.process_seed_range_all(3, 4), vec![(5, 6), (u64::MAX - 1, u64::MAX)]);
+    }
+
+    #[test]
+    fn maps_follow_category_names_not_file_order() {
+        let blocks: Vec<&str> = SAMPLE.split("\n\n").collect();
+        let reversed: Vec<&str> = blocks[..1].iter().chain(blocks[1..].iter().rev()).copied().collect();
+        let reversed = reversed.join("\n\n");
+        assert_eq!(part1(&reversed), Ok(35));
+        assert_eq!(part2(&reversed), Ok(46));
+
+        let mut missing = blocks.clone();
+        missing.remove(3);
+        let broken = Err("no map from fertilizer, so the chain to location is broken".to_string());
+        assert_eq!(part1(&missing.join("\n\n")), broken);
+
+        let duplicate = format!("{SAMPLE}\nseed-to-soil map:\n1 2 3\n");
+        assert_eq!(part2(&duplicate), Err("duplicate map from seed".to_string()));
+    }
+
+    #[test]
+    fn points_are_ranges_of_length_one() {
+        let almanac = Almanac::new(SAMPLE).unwrap();
+        let locations: Vec<u64> = [79, 14, 55, 13].into_iter().map(|seed| almanac.process_seed(seed)).collect();
+        assert_eq!(locations, vec![82, 43, 86, 35]);
+
+        let mut seed = 17;
+        for _ in 0..100 {
+            let almanac = Almanac::new(&random_almanac(&mut seed)).unwrap();
+            for value in 0..150 {
+                assert_eq!(almanac.process_seed(value), almanac.process_seed_range(value, 1));
+            }
+        }
+    }
+
+    #[test]
+    fn seed_ranges_near_u64_max() {
+        // The first range runs past u64::MAX and its last three seeds map to 0..3.
+        let input = format!(
+            "seeds: {} 10 5 3\n\nseed-to-soil map:\n0 {} 3\n\nsoil-to-location map:\n100 0 2\n",
+            u64::MAX - 5,
+            u64::MAX - 2
+        );
+        assert_eq!(part2(&input), Ok(2));
+
+        let input = format!("seeds: {} 6\n\nseed-to-location map:\n0 {} 3\n", u64::MAX - 5, u64::MAX - 2);
+        assert_eq!(part1(&input), Ok(6));
+        assert_eq!(part2(&input), Ok(0));
+
+        // Locations pushed past u64::MAX saturate.
+        let input = format!("seeds: 5 1\n\nseed-to-location map:\n{} 0 10\n", u64::MAX - 1);
+        assert_eq!(part1(&input), Ok(u64::MAX));
+        assert_eq!(part2(&input), Ok(u64::MAX));
+    }
 }
+
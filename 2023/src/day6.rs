@@ -2,7 +2,7 @@
 //
 // Calculate how many ways to win toy boat races by holding the button for different durations.
 // Distance = (total_time - hold_time) * hold_time
-// Uses quadratic formula to find the winning range.
+// Uses the quadratic formula to find the winning range, checking its ends with integer math.
 
 // Function to parse concatenated numbers from a string line
 fn parse_concatenated_numbers(line: &str) -> u64 {
@@ -15,35 +15,64 @@ fn parse_concatenated_numbers(line: &str) -> u64 {
         })
 }
 
+/// Counts the hold times that beat the record distance in a race lasting `time` milliseconds.
+///
+/// A hold that only ties the record is not counted, so a race whose record can't be beaten
+/// has 0 ways to win.
+pub fn ways_to_win(time: u64, record: u64) -> u64 {
+    win_window(time, record).map_or(0, |(min, max)| max - min + 1)
+}
+
+/// The shortest and longest hold times that beat the record, inclusive, or `None` if no hold
+/// time does.
+pub fn win_window(time: u64, record: u64) -> Option<(u64, u64)> {
+    let shortest = shortest_winning_hold(u128::from(time), u128::from(record))? as u64;
+    // The winning holds are symmetric around half the race.
+    Some((shortest, time - shortest))
+}
+
+// Finds the shortest hold that beats the record, or `None` if even the best hold, half the
+// race, doesn't.
+//
+// Holding for `hold` ms travels `hold * (time - hold)`, so the quadratic formula puts the
+// boundary at `(time - sqrt(time^2 - 4 * record)) / 2`. The integer square root lands within
+// a step of it, and the endpoint is then settled with exact integer checks, so neither
+// rounding nor a tie with the record can throw the count off.
+fn shortest_winning_hold(time: u128, record: u128) -> Option<u128> {
+    let beats_record = |hold: u128| hold * (time - hold) > record;
+    let discriminant = (time * time).checked_sub(4 * record)?;
+
+    let mut hold = (time - discriminant.isqrt()) / 2;
+    while hold > 0 && beats_record(hold - 1) {
+        hold -= 1;
+    }
+    while hold <= time / 2 && !beats_record(hold) {
+        hold += 1;
+    }
+    (hold <= time / 2).then_some(hold)
+}
+
 #[aoc(day6, part1)]
-pub fn part1(input: &str) -> u32 {
+pub fn part1(input: &str) -> u64 {
     // Split input into lines for processing
     let mut lines = input.lines();
 
-    // Extract times from the first line, skipping the label and parsing as floats
+    // Extract times from the first line, skipping the label
     let times = lines.next().unwrap_or("")
                       .split_whitespace()
                       .skip(1)
-                      .filter_map(|s| s.parse::<f32>().ok());
+                      .filter_map(|s| s.parse::<u64>().ok());
 
     // Extract distances from the second line in a similar way
     let distances = lines.next().unwrap_or("")
                          .split_whitespace()
                          .skip(1)
-                         .filter_map(|s| s.parse::<f32>().ok());
+                         .filter_map(|s| s.parse::<u64>().ok());
 
-    // Zip times and distances, processing each race
+    // Zip times and distances, counting the winning hold times of each race
     times.zip(distances)
-         .map(|(time, distance)| {
-             // Apply quadratic formula to find valid hold times
-             let delta_sqrt = (time * time - 4.0 * distance).sqrt();
-             let x1 = ((time + delta_sqrt) / 2.0 - 1.0).ceil();  // Upper bound
-             let x2 = ((time - delta_sqrt) / 2.0 + 1.0).floor(); // Lower bound
-
-             // Count of winning hold times for each race
-             (x1 - x2 + 1.0) as u32
-         })
-         .product() // Product of counts across all races
+         .map(|(time, distance)| ways_to_win(time, distance))
+         .product() // Product of counts across all races, 0 if any race can't be won
 }
 
 #[aoc(day6, part2)]
@@ -55,13 +84,91 @@ pub fn part2(input: &str) -> u64 {
     let time = parse_concatenated_numbers(lines.next().unwrap_or(""));
     let distance = parse_concatenated_numbers(lines.next().unwrap_or(""));
 
-    // Apply the quadratic formula to calculate valid hold times
-    let time = time as f64;
-    let distance = distance as f64;
-    let delta_sqrt = (time.powi(2) - 4.0 * distance).sqrt();
-    let x1 = ((time + delta_sqrt) / 2.0 - 1.0).ceil();  // Upper bound
-    let x2 = ((time - delta_sqrt) / 2.0 + 1.0).floor(); // Lower bound
-
     // Return the number of valid hold times for the race
-    ((x1 - x2 + 1.0) as u64).max(0)
+    ways_to_win(time, distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\
+Time:      7  15   30
+Distance:  9  40  200
+";
+
+    /// Counts the winning hold times by trying every one.
+    fn brute_force(time: u64, record: u64) -> u64 {
+        (0..=time).filter(|&hold| hold * (time - hold) > record).count() as u64
+    }
+
+    #[test]
+    fn sample_answers() {
+        assert_eq!(part1(SAMPLE), 288);
+        assert_eq!(part2(SAMPLE), 71503);
+    }
+
+    #[test]
+    fn ways_to_win_with_ties() {
+        assert_eq!(ways_to_win(7, 9), 4);
+        assert_eq!(ways_to_win(15, 40), 8);
+        // Holding for 10 or 20 ties the record of 200, which doesn't count
+        assert_eq!(ways_to_win(30, 200), 9);
+        assert_eq!(ways_to_win(30, 199), 11);
+
+        for time in 0..80 {
+            for record in 0..time * time / 4 + 3 {
+                assert_eq!(ways_to_win(time, record), brute_force(time, record), "{time} {record}");
+            }
+        }
+    }
+
+    #[test]
+    fn real_size_races_are_exact() {
+        assert_eq!(part2("Time:        44     89     96     91\nDistance:   277   1136   1890   1768\n"), 30125202);
+        assert_eq!(part2("Time:        56     97     77     93\nDistance:   499   2210   1097   1440\n"), 35349468);
+        assert_eq!(part2("Time:        62     73     75     65\nDistance:   644   1023   1240   1023\n"), 36872656);
+        assert_eq!(ways_to_win(u64::MAX, u64::MAX), u64::MAX - 3);
+
+        // Records that some hold ties exactly, where a rounded square root is most likely off
+        for (time, hold) in [(100_000_000u64, 12_345_678u64), (99_999_999, 1), (4_000_000_001, 3_000_000_000)] {
+            let record = hold * (time - hold);
+            let shortest = hold.min(time - hold);
+            assert_eq!(ways_to_win(time, record), time - 2 * (shortest + 1) + 1);
+            assert_eq!(ways_to_win(time, record - 1), time - 2 * shortest + 1);
+        }
+    }
+
+    #[test]
+    fn win_windows_of_the_sample_races() {
+        assert_eq!(win_window(7, 9), Some((2, 5)));
+        assert_eq!(win_window(15, 40), Some((4, 11)));
+        assert_eq!(win_window(30, 200), Some((11, 19)));
+        assert_eq!(win_window(71530, 940200), Some((14, 71516)));
+        assert_eq!(win_window(2, 0), Some((1, 1)));
+        assert_eq!(win_window(4, 4), None);
+        assert_eq!(win_window(0, 0), None);
+
+        for time in 0..60 {
+            for record in 0..time * time / 4 + 3 {
+                let window = win_window(time, record);
+                assert_eq!(window.map_or(0, |(min, max)| max - min + 1), ways_to_win(time, record));
+                if let Some((min, max)) = window {
+                    assert!(min * (time - min) > record && max * (time - max) > record);
+                    assert!(min == 0 || (min - 1) * (time - min + 1) <= record);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unwinnable_races_count_zero() {
+        // Holding for 5 of 10 ms goes exactly 25, the farthest possible
+        assert_eq!(ways_to_win(10, 25), 0);
+        assert_eq!(ways_to_win(10, 24), 1);
+        assert_eq!(ways_to_win(3, 100), 0);
+        assert_eq!(part1("Time:      7  10\nDistance:  9  25\n"), 0);
+        assert_eq!(part2("Time:      1 0\nDistance:  2 500\n"), 0);
+    }
 }
+
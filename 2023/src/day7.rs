@@ -3,158 +3,234 @@
 // Rank poker-like hands by strength and calculate total winnings.
 // Part 1: Standard card rankings
 // Part 2: Jokers (J) are wild cards that maximize hand strength
+//
+// Both parts share one classifier and ranking, differing only in whether J is a joker.
+// The card ranking is a parameter, so variant orderings can be scored too.
+
+/// The standard card ranking, from weakest to strongest.
+pub const ORDER: [char; 13] = ['2', '3', '4', '5', '6', '7', '8', '9', 'T', 'J', 'Q', 'K', 'A'];
+
+/// The type of a hand, ordered from weakest to strongest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HandType {
+    HighCard,
+    OnePair,
+    TwoPair,
+    ThreeOfAKind,
+    FullHouse,
+    FourOfAKind,
+    FiveOfAKind,
+}
 
-// Part 1 structures and functions
-mod part1_impl {
-    pub struct Hand {
-        pub bid: u32,
-        pub strength: u32,
-    }
-
-    impl Hand {
-        #[inline]
-        pub fn new(cards_str: &str, bid: u32) -> Self {
-            let mut card_strength: u32 = 0;
-            let mut cards: [u32; 13] = [0; 13];
-
-            for (i, card) in (0_u32..).zip(cards_str.chars()) {
-                let val = match card {
-                    'A' => 12,
-                    'K' => 11,
-                    'Q' => 10,
-                    'J' => 9,
-                    'T' => 8,
-                    n => n.to_digit(10).unwrap() - 2,
-                };
-
-                cards[val as usize] += 1;
-                card_strength += val * (1 << ((4 - i) * 4));
-            }
-
-            let mut hand_type = 0;
-            let mut pair_count = 0;
-            let mut three_of_a_kind = false;
-
-            for &count in &cards {
-                match count {
-                    5 => hand_type = 6, // Five of a kind
-                    4 => hand_type = 5,
-                    3 => {
-                        if pair_count > 0 {
-                            hand_type = 4;
-                        } else {
-                            three_of_a_kind = true;
-                        }
-                    },
-                    2 => {
-                        pair_count += 1;
-                        if pair_count == 2 {
-                            hand_type = 2;
-                        } else if three_of_a_kind {
-                            hand_type = 4;
-                        } else {
-                            hand_type = 1;
-                        }
-                    },
-                    _ => {}
-                }
-            }
-
-            if hand_type == 0 && three_of_a_kind {
-                hand_type = 3;
+impl HandType {
+    /// Determines the hand type from how many of each (non-joker) card the hand holds,
+    /// in any order, and how many jokers it has. Jokers join whichever card is most common.
+    pub fn from_counts(counts: &[u32], jokers: u32) -> Self {
+        let (mut first, mut second) = (0, 0);
+        for &count in counts {
+            if count > first {
+                (first, second) = (count, first);
+            } else if count > second {
+                second = count;
             }
+        }
 
-            let strength = (hand_type << 20) + card_strength;
+        match (first + jokers, second) {
+            (5, _) => HandType::FiveOfAKind,
+            (4, _) => HandType::FourOfAKind,
+            (3, 2) => HandType::FullHouse,
+            (3, _) => HandType::ThreeOfAKind,
+            (2, 2) => HandType::TwoPair,
+            (2, _) => HandType::OnePair,
+            _ => HandType::HighCard,
+        }
+    }
+}
 
-            Hand { bid, strength }
+/// Classifies a hand of cards ranked by `order`. With `joker`, each J is wild.
+/// Fails on a card that isn't in `order`.
+pub fn classify(cards: &str, order: &[char], joker: bool) -> Result<HandType, String> {
+    let mut counts = [0u32; 16];
+    let mut jokers = 0;
+
+    for card in cards.chars() {
+        if joker && card == 'J' {
+            jokers += 1;
+        } else {
+            counts[card_value(card, order, false)? as usize] += 1;
         }
     }
+
+    Ok(HandType::from_counts(&counts, jokers))
 }
 
-#[aoc(day7, part1)]
-pub fn part1(input: &str) -> usize {
-    let mut hands: Vec<part1_impl::Hand> = Vec::with_capacity(input.lines().count());
-
-    for line in input.lines() {
-        let mut split = line.split_whitespace();
-        let cards_str = split.next().unwrap();
-        let bid: u32 = split.next().unwrap().parse().unwrap();
-        hands.push(part1_impl::Hand::new(cards_str, bid));
+/// The value of a card for breaking ties between hands of the same type: its position in
+/// `order`, counting from 1. As a joker, J is the weakest card at 0.
+/// Fails on a card that isn't among the first 15 in `order`, since values must fit in 4 bits.
+#[inline]
+fn card_value(card: char, order: &[char], joker: bool) -> Result<u32, String> {
+    if joker && card == 'J' {
+        return Ok(0);
     }
+    match order.iter().position(|&c| c == card) {
+        Some(position) if position < 15 => Ok(position as u32 + 1),
+        Some(_) => Err(format!("card {card:?} is past the 15 cards that can be ranked")),
+        None => Err(format!("unknown card {card:?}")),
+    }
+}
 
-    hands.sort_unstable_by_key(|hand| hand.strength);
+/// Packs the hand type above the five card values, one per 4 bits, so that comparing
+/// strengths compares type first and then each card in order.
+fn strength(cards: &str, order: &[char], joker: bool) -> Result<u32, String> {
+    let card_strength = cards
+        .chars()
+        .try_fold(0, |acc, card| Ok::<_, String>((acc << 4) | card_value(card, order, joker)?))?;
+    Ok(((classify(cards, order, joker)? as u32) << 20) | card_strength)
+}
 
-    hands
-        .iter()
-        .enumerate()
-        .fold(0, |acc, (i, hand)| acc + ((i + 1) * hand.bid as usize))
+/// Ranks every hand from weakest (rank 1) to strongest, returning each hand's cards, bid,
+/// and rank in rank order. With `joker`, J is a wild card as in part 2.
+pub fn rank_hands(input: &str, joker: bool) -> Result<Vec<(&str, u32, u32)>, String> {
+    rank_hands_by(input, &ORDER, joker)
 }
 
-// Part 2 structures and functions
-mod part2_impl {
-    pub struct Hand {
-        pub bid: u32,
-        pub strength: u32,
+/// Total winnings when cards rank by `order` (weakest first, at most 15 cards).
+/// `total_winnings(input, &ORDER, false)` is part 1, and with `joker` it's part 2.
+pub fn total_winnings(input: &str, order: &[char], joker: bool) -> Result<usize, String> {
+    Ok(winnings(&rank_hands_by(input, order, joker)?))
+}
+
+// Ranks every hand as in `rank_hands`, with cards ranked by `order`.
+fn rank_hands_by<'a>(input: &'a str, order: &[char], joker: bool) -> Result<Vec<(&'a str, u32, u32)>, String> {
+    let mut hands: Vec<(u32, &str, u32)> = Vec::with_capacity(input.lines().count());
+
+    for line in input.lines().filter(|line| !line.trim().is_empty()) {
+        let (cards_str, bid) = parse_line(line)?;
+        hands.push((strength(cards_str, order, joker)?, cards_str, bid));
     }
 
-    impl Hand {
-        #[inline]
-        pub fn new(cards_str: &str, bid: u32) -> Self {
-            let mut strength: u32 = 0;
-            let mut jokers = 0;
-            let mut cards: [u32; 13] = [0; 13];
-
-            cards_str.chars().enumerate().for_each(|(i, card)| {
-                let val = match card {
-                    'A' => 12,
-                    'K' => 11,
-                    'Q' => 10,
-                    'J' => { jokers += 1; 0 },
-                    'T' => 9,
-                    n => n.to_digit(10).unwrap() - 1,
-                };
-
-                if val != 0 {
-                    cards[val as usize] += 1;
-                }
-
-                strength |= val << ((4 - i) * 4);
-            });
-
-            cards.sort_unstable();
-
-            let hand_type = match cards[12] + jokers {
-                5 => 6,
-                4 => 5,
-                3 if cards[11] == 2 => 4,
-                3 => 3,
-                2 if cards[11] == 2 => 2,
-                2 => 1,
-                _ => 0,
-            };
-
-            strength |= hand_type << 20;
-
-            Hand { bid, strength }
-        }
+    hands.sort_unstable_by_key(|&(strength, ..)| strength);
+
+    Ok((1..)
+        .zip(hands)
+        .map(|(rank, (_, cards, bid))| (cards, bid, rank))
+        .collect())
+}
+
+/// Parses a `<five cards> <bid>` line, with any whitespace between the columns.
+fn parse_line(line: &str) -> Result<(&str, u32), String> {
+    let mut split = line.split_whitespace();
+    let (Some(cards_str), Some(bid), None) = (split.next(), split.next(), split.next()) else {
+        return Err(format!("expected a hand and a bid: {line:?}"));
+    };
+    if cards_str.chars().count() != 5 {
+        return Err(format!("hand {cards_str:?} doesn't have five cards"));
     }
+    let bid = bid.parse().map_err(|_| format!("invalid bid {bid:?}"))?;
+    Ok((cards_str, bid))
+}
+
+// Sums each hand's bid multiplied by its rank.
+fn winnings(hands: &[(&str, u32, u32)]) -> usize {
+    hands.iter().map(|&(_, bid, rank)| bid as usize * rank as usize).sum()
+}
+
+#[aoc(day7, part1)]
+pub fn part1(input: &str) -> Result<usize, String> {
+    total_winnings(input, &ORDER, false)
 }
 
 #[aoc(day7, part2)]
-pub fn part2(input: &str) -> usize {
-    let mut hands: Vec<part2_impl::Hand> = Vec::with_capacity(input.lines().count());
-
-    for line in input.lines() {
-        let mut split = line.split_whitespace();
-        let cards_str = split.next().unwrap();
-        let bid: u32 = split.next().unwrap().parse().unwrap();
-        hands.push(part2_impl::Hand::new(cards_str, bid));
+pub fn part2(input: &str) -> Result<usize, String> {
+    total_winnings(input, &ORDER, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\
+32T3K 765
+T55J5 684
+KK677 28
+KTJJT 220
+QQQJA 483
+";
+
+    #[test]
+    fn sample_answers() {
+        assert_eq!(part1(SAMPLE), Ok(6440));
+        assert_eq!(part2(SAMPLE), Ok(5905));
+    }
+
+    #[test]
+    fn sample_hands_are_classified_and_ranked() {
+        use HandType::*;
+
+        let hands = ["32T3K", "T55J5", "KK677", "KTJJT", "QQQJA"];
+        let types: Vec<_> = hands.iter().map(|hand| classify(hand, &ORDER, false).unwrap()).collect();
+        assert_eq!(types, [OnePair, ThreeOfAKind, TwoPair, TwoPair, ThreeOfAKind]);
+        let types: Vec<_> = hands.iter().map(|hand| classify(hand, &ORDER, true).unwrap()).collect();
+        assert_eq!(types, [OnePair, FourOfAKind, TwoPair, FourOfAKind, FourOfAKind]);
+        assert_eq!(classify("JJJJJ", &ORDER, true), Ok(FiveOfAKind));
+
+        let ranked = rank_hands(SAMPLE, false).unwrap();
+        assert_eq!(
+            ranked,
+            [("32T3K", 765, 1), ("KTJJT", 220, 2), ("KK677", 28, 3), ("T55J5", 684, 4), ("QQQJA", 483, 5)]
+        );
+        let order: Vec<_> = rank_hands(SAMPLE, true).unwrap().iter().map(|&(cards, ..)| cards).collect();
+        assert_eq!(order, ["32T3K", "KK677", "T55J5", "QQQJA", "KTJJT"]);
     }
 
-    hands.sort_unstable_by_key(|hand| hand.strength);
+    #[test]
+    fn custom_orderings_change_tie_breaks() {
+        assert_eq!(total_winnings(SAMPLE, &ORDER, false), Ok(6440));
+        assert_eq!(total_winnings(SAMPLE, &ORDER, true), Ok(5905));
 
-    hands
-        .iter()
-        .enumerate()
-        .fold(0, |acc, (i, hand)| acc + ((i + 1) * hand.bid as usize))
+        // With T and K swapped, KTJJT beats KK677 and T55J5 beats QQQJA.
+        let mut order = ORDER;
+        order.swap(8, 11);
+        assert_eq!(total_winnings(SAMPLE, &order, false), Ok(765 + 28 * 2 + 220 * 3 + 483 * 4 + 684 * 5));
+    }
+
+    #[test]
+    fn blank_lines_and_tabs_are_tolerated() {
+        assert_eq!(part1(&format!("{SAMPLE}\n\n")), Ok(6440));
+        assert_eq!(part1(&SAMPLE.replace(' ', "\t")), Ok(6440));
+        assert_eq!(part2(&SAMPLE.replace(' ', "  \t ")), Ok(5905));
+        assert_eq!(part1(""), Ok(0));
+
+        assert!(part1("32T3 765\n").is_err());
+        assert!(part1("32T3K x\n").is_err());
+        assert!(part1("32T3K\n").is_err());
+        assert!(part1("32T3K 1 2\n").is_err());
+    }
+
+    #[test]
+    fn hand_types_rank_weakest_to_strongest() {
+        use HandType::*;
+
+        let ranking = [HighCard, OnePair, TwoPair, ThreeOfAKind, FullHouse, FourOfAKind, FiveOfAKind];
+        assert!(ranking.windows(2).all(|pair| pair[0] < pair[1]));
+
+        assert_eq!(HandType::from_counts(&[1, 1, 1, 1, 1], 0), HighCard);
+        assert_eq!(HandType::from_counts(&[1, 2, 2], 0), TwoPair);
+        assert_eq!(HandType::from_counts(&[2, 1, 2], 1), FullHouse);
+        assert_eq!(HandType::from_counts(&[3, 0, 2], 0), FullHouse);
+        assert_eq!(HandType::from_counts(&[1, 1], 3), FourOfAKind);
+        assert_eq!(HandType::from_counts(&[], 5), FiveOfAKind);
+    }
+
+    #[test]
+    fn unknown_cards_are_errors() {
+        assert_eq!(part1("32X3K 765\n"), Err("unknown card 'X'".to_string()));
+        assert!(part2("1234J 5\n").is_err());
+        assert!(classify("32T3k", &ORDER, false).is_err());
+
+        // A joker needs no place in the ordering, but every other card does.
+        assert!(total_winnings(SAMPLE, &['2', '3'], false).is_err());
+        assert_eq!(total_winnings("JJJJJ 3\n", &['Q'], true), Ok(3));
+    }
 }
+
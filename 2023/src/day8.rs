@@ -2,140 +2,540 @@
 //
 // Follow left/right instructions through a network of nodes.
 // Part 1: Count steps from AAA to ZZZ
-// Part 2: Multiple starting nodes (**A), find LCM of cycle lengths to **Z
+// Part 2: Multiple starting nodes (**A), combine each ghost's Z cycle with the CRT
+//
+// Three-letter node names are packed into 15 bits and looked up in a flat array.
+// Inputs with any other node names fall back to a hash map keyed on the name.
+
+use num_integer::{ExtendedGcd, Integer};
+use rustc_hash::FxHashMap as HashMap;
+use std::hash::Hash;
+
+/// A parsed mapping line: the node name and its left and right neighbors.
+type Mapping<'a> = (&'a str, &'a str, &'a str);
+
+/// A network of nodes that can be walked by following L/R instructions.
+trait Network {
+    type Node: Copy + Eq + Hash;
+
+    /// Looks up a node by name, returning `None` if it has no mapping.
+    fn node(&self, name: &str) -> Option<Self::Node>;
+
+    /// Follows a single `L` or `R` instruction from a node.
+    fn next(&self, node: Self::Node, direction: u8) -> Self::Node;
+
+    /// Whether the node's name ends with the given letter.
+    fn ends_with(&self, node: Self::Node, letter: u8) -> bool;
+}
+
+/// Fast path: nodes encoded with `enc` index a flat array storing the left node in the
+/// lower 16 bits and the right node in the upper 16 bits.
+struct Packed {
+    map: Vec<u32>,
+    // Tracks which nodes have a mapping, since an encoded 0 is also the valid node 'AAA'.
+    defined: Vec<bool>,
+}
+
+impl Packed {
+    // The size is based on encoding 3 characters into a unique u32.
+    // Each character is encoded into 5 bits, so 3 characters need 3*5 = 15 bits.
+    const SIZE: usize = 0b11001_11001_11001 + 1; // +1 because array indexing starts at 0
+
+    fn new(mappings: &[Mapping]) -> Self {
+        let mut map = vec![0u32; Self::SIZE];
+        let mut defined = vec![false; Self::SIZE];
+
+        for &(node, left, right) in mappings {
+            let encoded_node = enc(node.as_bytes());
+            map[encoded_node as usize] = enc(left.as_bytes()) | (enc(right.as_bytes()) << 16);
+            defined[encoded_node as usize] = true;
+        }
+
+        Packed { map, defined }
+    }
+
+    /// Whether every name in the mappings is exactly three uppercase letters.
+    fn fits(mappings: &[Mapping]) -> bool {
+        let is_packable = |name: &str| name.len() == 3 && name.bytes().all(|b| b.is_ascii_uppercase());
+        mappings.iter().all(|&(node, left, right)| is_packable(node) && is_packable(left) && is_packable(right))
+    }
+}
+
+impl Network for Packed {
+    type Node = u32;
+
+    fn node(&self, name: &str) -> Option<u32> {
+        let encoded = enc(name.as_bytes());
+        self.defined[encoded as usize].then_some(encoded)
+    }
+
+    #[inline]
+    fn next(&self, node: u32, direction: u8) -> u32 {
+        if direction == b'L' {
+            self.map[node as usize] & u16::MAX as u32 // Left node is in the lower 16 bits
+        } else {
+            self.map[node as usize] >> 16 // Right node is in the upper 16 bits
+        }
+    }
 
-// Part 1 implementation
-mod part1_impl {
-    // A closure to encode a node name into a u32. Each character (byte) in the node name is reduced by 'A'
-    // to make 'A' = 0, 'B' = 1, and so on, then shifted left by 10 or 5 bits or left as is, and combined.
     #[inline]
-    pub fn enc(n: &[u8]) -> u32 {
-        // Ensure characters are in valid range (A-Z is 0-25)
-        let c0 = n[0].saturating_sub(b'A').min(25) as u32;
-        let c1 = n[1].saturating_sub(b'A').min(25) as u32;
-        let c2 = n[2].saturating_sub(b'A').min(25) as u32;
-        (c0 << 10) | (c1 << 5) | c2
+    fn ends_with(&self, node: u32, letter: u8) -> bool {
+        node & 0b11111 == (letter - b'A') as u32
     }
 }
 
-#[aoc(day8, part1)]
-pub fn part1(input: &str) -> usize {
-    // Splitting the input into instructions and node mappings
-    let mut sections = input.split("\n\n");
-    let instructions = sections.next().unwrap().as_bytes(); // The first part are the instructions (L/R)
-    let mappings = sections.next().unwrap(); // The second part is the node mapping
+/// Fallback for node names that don't fit the packed encoding.
+struct Named<'a>(HashMap<&'a str, (&'a str, &'a str)>);
 
-    // Preparing a map for node encoding. The size is based on encoding 3 characters into a unique u32.
-    // Each character is encoded into 5 bits, so 3 characters need 3*5 = 15 bits.
-    let mut map = [0u32; 0b11001_11001_11001 + 1]; // +1 because array indexing starts at 0
+impl<'a> Named<'a> {
+    fn new(mappings: &[Mapping<'a>]) -> Self {
+        Named(mappings.iter().map(|&(node, left, right)| (node, (left, right))).collect())
+    }
+}
 
-    // Process each line in the node mappings to fill the map
-    for line in mappings.lines() {
-        let parts: Vec<_> = line.split(" = ").map(|s| s.as_bytes()).collect();
-        let encoded_node = part1_impl::enc(&parts[0][0..3]);
-        map[encoded_node as usize] = part1_impl::enc(&parts[1][1..4]) | (part1_impl::enc(&parts[1][6..9]) << 16);
+impl<'a> Network for Named<'a> {
+    type Node = &'a str;
+
+    fn node(&self, name: &str) -> Option<&'a str> {
+        self.0.get_key_value(name).map(|(&node, _)| node)
+    }
+
+    fn next(&self, node: &'a str, direction: u8) -> &'a str {
+        let (left, right) = self.0[node];
+        if direction == b'L' { left } else { right }
     }
 
-    // Encoding the target node 'ZZZ'
-    let zzz_encoded = part1_impl::enc(b"ZZZ");
-    // Starting from 'AAA'
-    let mut current_node = part1_impl::enc(b"AAA");
-    let mut steps = 0; // Counting steps
+    fn ends_with(&self, node: &'a str, letter: u8) -> bool {
+        node.as_bytes().last() == Some(&letter)
+    }
+}
+
+// A closure to encode a node name into a u32. Each character (byte) in the node name is reduced by 'A'
+// to make 'A' = 0, 'B' = 1, and so on, then shifted left by 10 or 5 bits or left as is, and combined.
+#[inline]
+fn enc(n: &[u8]) -> u32 {
+    // Ensure characters are in valid range (A-Z is 0-25)
+    let c0 = n[0].saturating_sub(b'A').min(25) as u32;
+    let c1 = n[1].saturating_sub(b'A').min(25) as u32;
+    let c2 = n[2].saturating_sub(b'A').min(25) as u32;
+    (c0 << 10) | (c1 << 5) | c2
+}
+
+/// Splits the input into the L/R instructions and the parsed node mappings.
+fn parse_input(input: &str) -> Result<(&[u8], Vec<Mapping<'_>>), String> {
+    // Splitting the input into instructions and node mappings
+    let (instructions, mappings) = input.split_once("\n\n").ok_or("missing node mappings")?;
+    let instructions = instructions.trim().as_bytes();
 
-    // Loop until the current node is 'ZZZ'
-    loop {
+    if instructions.is_empty() {
+        return Err("missing instructions".to_string());
+    }
+
+    let mappings = mappings
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| parse_mapping(line).ok_or_else(|| format!("malformed mapping: {line}")))
+        .collect::<Result<_, _>>()?;
+
+    Ok((instructions, mappings))
+}
+
+/// Parses a `AAA = (BBB, CCC)` line.
+fn parse_mapping(line: &str) -> Option<Mapping<'_>> {
+    let (node, targets) = line.split_once(" = ")?;
+    let (left, right) = targets.trim().strip_prefix('(')?.strip_suffix(')')?.split_once(", ")?;
+    Some((node.trim(), left, right))
+}
+
+/// Checks that every node we could step to is itself mapped.
+fn check_references<N: Network>(network: &N, mappings: &[Mapping]) -> Result<(), String> {
+    match mappings.iter().find(|&&(_, left, right)| network.node(left).is_none() || network.node(right).is_none()) {
+        Some((node, ..)) => Err(format!("node {node} leads to an undefined node")),
+        None => Ok(()),
+    }
+}
+
+/// Follows the instructions from `node` until `is_end` holds, returning the step count.
+///
+/// A walk is fully determined by its node and position in the instructions, so after
+/// `instructions.len() * node_count` steps without reaching an end it must be looping.
+/// That case returns `None`.
+fn walk<N: Network>(
+    network: &N,
+    instructions: &[u8],
+    node_count: usize,
+    mut node: N::Node,
+    is_end: impl Fn(N::Node) -> bool,
+) -> Option<usize> {
+    for steps in 0..instructions.len() * node_count {
         // Getting the next direction from instructions, cycling through them if necessary
-        let direction = instructions[steps % instructions.len()];
-        // Updating the current node based on the direction and the map
-        current_node = if direction == b'L' {
-            map[current_node as usize] & u16::MAX as u32 // Left node is in the lower 16 bits
-        } else {
-            map[current_node as usize] >> 16 // Right node is in the upper 16 bits
-        };
+        node = network.next(node, instructions[steps % instructions.len()]);
 
-        // If the target node 'ZZZ' is reached, exit the loop
-        if current_node == zzz_encoded {
-            break;
+        if is_end(node) {
+            return Some(steps + 1);
         }
+    }
+
+    None
+}
+
+/// Counts the steps from AAA to ZZZ.
+fn steps_to_zzz<N: Network>(network: &N, mappings: &[Mapping], instructions: &[u8]) -> Result<usize, String> {
+    let start = network.node("AAA").ok_or("start node AAA is not defined")?;
+    let target = network.node("ZZZ").ok_or("target node ZZZ is not defined")?;
+
+    walk(network, instructions, mappings.len(), start, |node| node == target)
+        .ok_or_else(|| "ZZZ is unreachable from AAA".to_string())
+}
+
+/// The Z nodes a ghost visits, in terms of the cycle its walk eventually settles into.
+struct Cycle<'a> {
+    name: &'a str,
+    /// Step at which the ghost first enters its cycle.
+    start: usize,
+    /// Length of the cycle in steps.
+    period: usize,
+    /// Every step before `start + period` at which the ghost is on a Z node.
+    hits: Vec<usize>,
+}
 
-        steps += 1; // Increment step count
+impl Cycle<'_> {
+    /// Z hits inside the cycle, which repeat every period.
+    fn repeating(&self) -> impl Iterator<Item = usize> + '_ {
+        self.hits.iter().copied().filter(|&hit| hit >= self.start)
     }
 
-    steps + 1 // Returning the total number of steps (+1 because steps start at 0)
+    /// Whether the ghost is on a Z node after `time` steps.
+    fn hits_at(&self, time: usize) -> bool {
+        if time < self.start {
+            self.hits.contains(&time)
+        } else {
+            self.repeating().any(|hit| (time - hit).is_multiple_of(self.period))
+        }
+    }
 }
 
-// Part 2 implementation
-mod part2_impl {
-    // A closure to encode a node name into a u32. Each character (byte) in the node name is reduced by 'A'
-    // to make 'A' = 0, 'B' = 1, and so on, then shifted left by 10 or 5 bits or left as is, and combined.
-    #[inline]
-    pub fn enc(n: &[u8]) -> u32 {
-        // Ensure characters are in valid range (A-Z is 0-25)
-        let c0 = n[0].saturating_sub(b'A').min(25) as u32;
-        let c1 = n[1].saturating_sub(b'A').min(25) as u32;
-        let c2 = n[2].saturating_sub(b'A').min(25) as u32;
-        (c0 << 10) | (c1 << 5) | c2
+/// Walks each ghost until it repeats a state. A ghost's future only depends on its node and
+/// its position in the instructions, so the first repeated pair closes its cycle.
+fn ghost_cycles<'a, N: Network>(
+    network: &N,
+    mappings: &[Mapping<'a>],
+    instructions: &[u8],
+) -> Result<Vec<Cycle<'a>>, String> {
+    mappings
+        .iter()
+        .filter_map(|&(name, ..)| network.node(name).map(|node| (name, node)))
+        .filter(|&(_, node)| network.ends_with(node, b'A'))
+        .map(|(name, mut node)| {
+            let mut seen = HashMap::default();
+            let mut hits = Vec::new();
+            let mut time = 0;
+
+            let start = loop {
+                let index = time % instructions.len();
+                if let Some(&first) = seen.get(&(node, index)) {
+                    break first;
+                }
+                seen.insert((node, index), time);
+
+                if network.ends_with(node, b'Z') {
+                    hits.push(time);
+                }
+                node = network.next(node, instructions[index]);
+                time += 1;
+            };
+
+            if hits.is_empty() {
+                return Err(format!("ghost starting at {name} never reaches a Z node"));
+            }
+            Ok(Cycle { name, start, period: time - start, hits })
+        })
+        .collect()
+}
+
+/// Parses the input and finds every ghost's cycle, using whichever network fits the node names.
+fn parse_cycles(input: &str) -> Result<Vec<Cycle<'_>>, String> {
+    let (instructions, mappings) = parse_input(input)?;
+
+    if Packed::fits(&mappings) {
+        let network = Packed::new(&mappings);
+        check_references(&network, &mappings)?;
+        ghost_cycles(&network, &mappings, instructions)
+    } else {
+        let network = Named::new(&mappings);
+        check_references(&network, &mappings)?;
+        ghost_cycles(&network, &mappings, instructions)
     }
 }
 
-#[aoc(day8, part2)]
-pub fn part2(input: &str) -> usize {
-    // Splitting the input into instructions and node mappings
-    let mut sections = input.split("\n\n");
-    let instructions = sections.next().unwrap().as_bytes(); // The first part are the instructions (L/R)
-    let mappings = sections.next().unwrap(); // The second part is the node mapping
+/// Combines the congruences `x = r1 (mod m1)` and `x = r2 (mod m2)` into a single one,
+/// or returns `None` if they have no common solution. The moduli needn't be coprime.
+fn crt((r1, m1): (i128, i128), (r2, m2): (i128, i128)) -> Option<(i128, i128)> {
+    let ExtendedGcd { gcd, x, .. } = m1.extended_gcd(&m2);
+    if (r2 - r1) % gcd != 0 {
+        return None;
+    }
 
-    // Preparing a map for node encoding. The size is based on encoding 3 characters into a unique u32.
-    // Each character is encoded into 5 bits, so 3 characters need 3*5 = 15 bits.
-    let mut map = [0u32; 0b11001_11001_11001 + 1];
-    let mut starts = Vec::with_capacity(6); // There are 6 starting nodes (nodes ending in 'A')
-
-    // Process each line in the node mappings to fill the map
-    for line in mappings.lines() {
-        let parts: Vec<_> = line.split(" = ").map(|s| s.as_bytes()).collect();
-        let encoded_node = part2_impl::enc(&parts[0][0..3]);
-        map[encoded_node as usize] = part2_impl::enc(&parts[1][1..4]) | (part2_impl::enc(&parts[1][6..9]) << 16);
-
-        // If the node name ends with 'A', add it to the starting nodes
-        if parts[0][2] == b'A' {
-            starts.push(encoded_node);
+    // x * m1 = gcd (mod m2), so stepping r1 by k * m1 closes the gap to r2.
+    let lcm = m1 / gcd * m2;
+    let k = ((r2 - r1) / gcd * x).rem_euclid(m2 / gcd);
+    Some(((r1 + k * m1).rem_euclid(lcm), lcm))
+}
+
+// The most residues `synchronize` keeps while merging ghosts, past which it gives up.
+const MAX_RESIDUES: usize = 1 << 16;
+
+/// Finds the first time every ghost is on a Z node.
+///
+/// An earlier time than every cycle start must be a Z hit some ghost makes before its cycle,
+/// so those few candidates are checked directly. From then on each ghost is on a Z node exactly
+/// when the time is congruent to one of its in-cycle hits modulo its period, so the ghosts are
+/// merged one at a time with the CRT into the set of times all of them hit, modulo the LCM of
+/// their periods.
+///
+/// Duplicate residues are dropped after each merge, so the set never holds more than that LCM
+/// or the product of the ghosts' hit counts. Beyond `MAX_RESIDUES` it fails rather than
+/// grow without bound.
+fn synchronize(cycles: &[Cycle]) -> Result<usize, String> {
+    let all_hit = |time| cycles.iter().all(|cycle| cycle.hits_at(time));
+    let early = cycles
+        .iter()
+        .flat_map(|cycle| cycle.hits.iter().copied().filter(|&hit| hit < cycle.start))
+        .filter(|&time| all_hit(time))
+        .min();
+
+    // On the real input each ghost has one in-cycle hit at a multiple of its period,
+    // so this reduces to the LCM of the periods.
+    let mut residues = vec![0];
+    let mut modulus = 1;
+    for cycle in cycles {
+        let period = cycle.period as i128;
+        let mut merged: Vec<i128> = residues
+            .iter()
+            .flat_map(|&r| cycle.repeating().filter_map(move |hit| crt((r, modulus), (hit as i128, period))))
+            .map(|(r, _)| r)
+            .collect();
+        merged.sort_unstable();
+        merged.dedup();
+        if merged.len() > MAX_RESIDUES {
+            return Err(format!("ghosts line up in over {MAX_RESIDUES} ways per cycle"));
         }
+        residues = merged;
+        modulus = modulus.lcm(&period);
     }
 
-    // Encode the ending character 'Z'
-    let z_end_encoded = (b'Z' - b'A') as u32;
-    let instructions_len = instructions.len();
+    // The congruences only hold once every ghost is in its cycle, so advance each solution
+    // to a time at or past every cycle start.
+    let latest = cycles.iter().map(|cycle| cycle.start as i128).max().unwrap_or(0);
+    let late = residues
+        .into_iter()
+        .map(|time| if time < latest { time + (latest - time + modulus - 1) / modulus * modulus } else { time })
+        .min();
 
-    // Process each starting node and calculate the steps to reach a node ending with 'Z'
-    starts
+    early
         .into_iter()
-        .map(|node| {
-            let mut current_node = node;
-            let mut steps = 0;
-
-            // Loop until the current node ends with 'Z'
-            loop {
-                let direction = instructions[steps % instructions_len]; // Cycle through instructions
-                current_node = if direction == b'L' {
-                    map[current_node as usize] & u16::MAX as u32 // Get the left node
-                } else {
-                    map[current_node as usize] >> 16 // Get the right node
-                };
-
-                // Check if current node ends with 'Z'
-                if current_node & 0b11111 == z_end_encoded {
-                    break; // Exit loop when a node ending with 'Z' is reached
-                }
-                steps += 1; // Increment step count
+        .chain(late.map(|time| time as usize))
+        .min()
+        .ok_or_else(|| "ghosts never reach Z nodes at the same time".to_string())
+}
+
+#[aoc(day8, part1)]
+pub fn part1(input: &str) -> Result<usize, String> {
+    let (instructions, mappings) = parse_input(input)?;
+
+    if Packed::fits(&mappings) {
+        let network = Packed::new(&mappings);
+        check_references(&network, &mappings)?;
+        steps_to_zzz(&network, &mappings, instructions)
+    } else {
+        let network = Named::new(&mappings);
+        check_references(&network, &mappings)?;
+        steps_to_zzz(&network, &mappings, instructions)
+    }
+}
+
+#[aoc(day8, part2)]
+pub fn part2(input: &str) -> Result<usize, String> {
+    synchronize(&parse_cycles(input)?)
+}
+
+/// Each ghost's starting node and the length of the cycle its walk settles into.
+///
+/// When every ghost reaches a Z node exactly once per cycle, at a multiple of its length
+/// (as on the real input), `part2` is the LCM of these lengths.
+pub fn part2_cycles(input: &str) -> Result<Vec<(String, usize)>, String> {
+    Ok(parse_cycles(input)?.into_iter().map(|cycle| (cycle.name.to_string(), cycle.period)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\
+RL
+
+AAA = (BBB, CCC)
+BBB = (DDD, EEE)
+CCC = (ZZZ, GGG)
+DDD = (DDD, DDD)
+EEE = (EEE, EEE)
+GGG = (GGG, GGG)
+ZZZ = (ZZZ, ZZZ)
+";
+
+    const REPEATING: &str = "\
+LLR
+
+AAA = (BBB, BBB)
+BBB = (AAA, ZZZ)
+ZZZ = (ZZZ, ZZZ)
+";
+
+    const GHOSTS: &str = "\
+LR
+
+11A = (11B, XXX)
+11B = (XXX, 11Z)
+11Z = (11B, XXX)
+22A = (22B, XXX)
+22B = (22C, 22C)
+22C = (22Z, 22Z)
+22Z = (22B, 22B)
+XXX = (XXX, XXX)
+";
+
+    #[test]
+    fn sample_answers() {
+        assert_eq!(part1(SAMPLE), Ok(2));
+        assert_eq!(part1(REPEATING), Ok(6));
+        assert_eq!(part2(GHOSTS), Ok(6));
+    }
+
+    #[test]
+    fn missing_start_or_target_is_an_error() {
+        let no_target = "RL\n\nAAA = (BBB, BBB)\nBBB = (AAA, AAA)\n";
+        assert_eq!(part1(no_target), Err("target node ZZZ is not defined".to_string()));
+
+        let no_start = "RL\n\nBBB = (ZZZ, ZZZ)\nZZZ = (ZZZ, ZZZ)\n";
+        assert_eq!(part1(no_start), Err("start node AAA is not defined".to_string()));
+
+        let dangling = "RL\n\nAAA = (BBB, QQQ)\nBBB = (ZZZ, ZZZ)\nZZZ = (ZZZ, ZZZ)\n";
+        assert_eq!(part1(dangling), Err("node AAA leads to an undefined node".to_string()));
+    }
+
+    #[test]
+    fn ghosts_with_several_z_nodes_per_cycle() {
+        // 11A is on a Z node at steps 2 and 3 of every 4, and 22A at step 4 of every 5.
+        let input = "\
+L
+
+11A = (11B, 11B)
+11B = (11Z, 11Z)
+11Z = (12Z, 12Z)
+12Z = (11C, 11C)
+11C = (11D, 11D)
+11D = (11Z, 11Z)
+22A = (22B, 22B)
+22B = (22C, 22C)
+22C = (22D, 22D)
+22D = (22Z, 22Z)
+22Z = (22E, 22E)
+22E = (22F, 22F)
+22F = (22G, 22G)
+22G = (22H, 22H)
+22H = (22Z, 22Z)
+";
+        assert_eq!(part2(input), Ok(14));
+        assert_eq!(part2_cycles(input), Ok(vec![("11A".to_string(), 4), ("22A".to_string(), 5)]));
+    }
+
+    #[test]
+    fn ghosts_with_offsets_that_differ_from_periods() {
+        // BBA first reaches Z after 2 steps and then every 3, CCA after 3 steps and then every 2.
+        let input = "L\n\nBBA = (BBB, BBB)\nBBB = (BBZ, BBZ)\nBBZ = (BBC, BBC)\nBBC = (BBD, BBD)\nBBD = (BBZ, BBZ)\n\
+                     CCA = (CCB, CCB)\nCCB = (CCC, CCC)\nCCC = (CCZ, CCZ)\nCCZ = (CCD, CCD)\nCCD = (CCZ, CCZ)\n";
+        assert_eq!(part2(input), Ok(5));
+
+        // A Z node passed before the cycle starts counts too.
+        let input = "L\n\nBBA = (BBZ, BBZ)\nBBZ = (BBC, BBC)\nBBC = (BBC, BBC)\nCCA = (CCZ, CCZ)\nCCZ = (CCZ, CCZ)\n";
+        assert_eq!(part2(input), Ok(1));
+
+        // Hits at 1 (mod 2) and 2 (mod 4) never line up.
+        let input = "L\n\nBBA = (BBZ, BBZ)\nBBZ = (BBC, BBC)\nBBC = (BBZ, BBZ)\n\
+                     CCA = (CCB, CCB)\nCCB = (CCZ, CCZ)\nCCZ = (CCD, CCD)\nCCD = (CCE, CCE)\nCCE = (CCF, CCF)\nCCF = (CCZ, CCZ)\n";
+        assert_eq!(part2(input), Err("ghosts never reach Z nodes at the same time".to_string()));
+    }
+
+    #[test]
+    fn longer_node_names_use_the_hash_map() {
+        let input = "\
+LRL
+
+AAA = (AAAA, AAAA)
+AAAA = (BBBB, CCCC)
+BBBB = (DDDD, EEEE)
+CCCC = (ZZZ, GGGG)
+DDDD = (DDDD, DDDD)
+EEEE = (EEEE, EEEE)
+GGGG = (GGGG, GGGG)
+ZZZ = (ZZZ, ZZZ)
+";
+        let (_, mappings) = parse_input(input).unwrap();
+        assert!(!Packed::fits(&mappings));
+        assert_eq!(part1(input), Ok(3));
+
+        let (_, mappings) = parse_input(SAMPLE).unwrap();
+        assert!(Packed::fits(&mappings));
+    }
+
+    #[test]
+    fn cycles_cover_every_ghost_and_fold_to_part2() {
+        let cycles = part2_cycles(GHOSTS).unwrap();
+        let starts = GHOSTS.lines().filter(|line| line.split(' ').next().is_some_and(|node| node.ends_with('A')));
+        assert_eq!(cycles.len(), starts.count());
+        assert_eq!(cycles, vec![("11A".to_string(), 2), ("22A".to_string(), 6)]);
+
+        let lcm = cycles.iter().fold(1, |acc, &(_, length)| acc.lcm(&length));
+        assert_eq!(Ok(lcm), part2(GHOSTS));
+    }
+
+    #[test]
+    fn unreachable_targets_stop_instead_of_looping() {
+        let input = "RL\n\nAAA = (BBB, BBB)\nBBB = (AAA, AAA)\nZZZ = (ZZZ, ZZZ)\n";
+        assert_eq!(part1(input), Err("ZZZ is unreachable from AAA".to_string()));
+        assert_eq!(part2(input), Err("ghost starting at AAA never reaches a Z node".to_string()));
+    }
+
+    // One ghost per period, whose Z nodes are the cycle positions listed in `hits`.
+    fn ghosts(cycles: &[(usize, &[usize])]) -> String {
+        let mut input = "L\n\n".to_string();
+        for (ghost, &(period, hits)) in cycles.iter().enumerate() {
+            let node = |step: usize| {
+                let end = if step == 0 { 'A' } else if hits.contains(&step) { 'Z' } else { 'X' };
+                format!("G{ghost}N{step}{end}")
+            };
+            for step in 0..=period {
+                let next = node(if step == period { 1 } else { step + 1 });
+                input += &format!("{} = ({next}, {next})\n", node(step));
             }
+        }
+        input
+    }
 
-            steps + 1 // Return total steps for this path (+1 for 0 indexing)
-        })
-        // Calculate the least common multiple (LCM) of steps across all paths
-        // This finds the first time all paths end with 'Z' simultaneously
-        .fold(1, num_integer::lcm)
-        // NOTE: This is not a generally correct solution,
-        // this is expliting how the input is structured to have 6 independent paths
-        // where each start has exactly 1 end and the Z nodes always have the same destinations
-        // as the A nodes.
+    #[test]
+    fn many_ghosts_with_many_hits_stay_small() {
+        // Each of 20 ghosts is on a Z node at every step, which would be 6^20 combinations
+        // of hits, but only 6 distinct times modulo 6.
+        let mut cycles = vec![(6, &[1, 2, 3, 4, 5, 6][..]); 20];
+        assert_eq!(part2(&ghosts(&cycles)), Ok(1));
+
+        cycles.push((7, &[7]));
+        assert_eq!(part2(&ghosts(&cycles)), Ok(7));
+
+        // Coprime periods with many hits each do line up in too many ways.
+        let many: Vec<usize> = (1..=30).collect();
+        let cycles: Vec<(usize, &[usize])> =
+            [11, 13, 17, 19, 23, 29].iter().map(|&period| (period, &many[..period / 2])).collect();
+        assert_eq!(part2(&ghosts(&cycles)), Err("ghosts line up in over 65536 ways per cycle".to_string()));
+    }
 }
@@ -2,14 +2,22 @@
 //
 // Extrapolate sequences by computing successive differences until reaching all zeros,
 // then working backwards to find the next (and previous) values.
+//
+// Values may span the whole i64 range: the intermediate sums are computed in i128 and only
+// the extrapolated values have to fit back into an i64.
 
 // Parses the input string into a vector of vectors of i64.
 // Each line of the input string is split into whitespace-separated values,
 // which are parsed into integers and collected into a vector.
-fn parse_input(input: &str) -> Vec<Vec<i64>> {
+fn parse_input(input: &str) -> Result<Vec<Vec<i64>>, String> {
     input
         .lines()
-        .map(|line| line.split_whitespace().map(|s| s.parse().unwrap()).collect())
+        .enumerate()
+        .map(|(row, line)| {
+            line.split_whitespace()
+                .map(|s| s.parse().map_err(|_| format!("invalid value {s:?} on line {}", row + 1)))
+                .collect()
+        })
         .collect()
 }
 
@@ -17,11 +25,16 @@ fn parse_input(input: &str) -> Vec<Vec<i64>> {
 // the differences between the values in the input data.
 // The triangle is a 2D vector where each row represents a level in the triangle,
 // and negative values are introduced at every second step to adjust the calculations.
-fn generate_triangle(max_size: usize) -> Vec<Vec<i64>> {
-    let mut triangle = vec![vec![1]];
+// Fails if the binomial coefficients outgrow an i128, i.e. for histories of well over
+// a hundred values.
+fn generate_triangle(max_size: usize) -> Result<Vec<Vec<i128>>, String> {
+    let mut triangle: Vec<Vec<i128>> = vec![vec![1]];
     for i in 0..max_size {
         let mut next_row = vec![1];
-        next_row.extend(triangle[i].windows(2).map(|w| w[0] + w[1]));
+        for w in triangle[i].windows(2) {
+            let sum = w[0].checked_add(w[1]);
+            next_row.push(sum.ok_or_else(|| format!("history of {max_size} values is too long"))?);
+        }
         next_row.push(1);
         triangle.push(next_row);
     }
@@ -30,42 +43,227 @@ fn generate_triangle(max_size: usize) -> Vec<Vec<i64>> {
             row_values[col] *= -1;
         }
     }
-    triangle
+    Ok(triangle)
 }
 
 // Calculates the sum for a single line of input using the modified Pascal's Triangle.
 // This function extrapolates the next value in the input line's history
 // by applying the logic of the problem statement.
-fn calculate_line_sum(line: &[i64], triangle: &[Vec<i64>]) -> i64 {
-    let mut sum = 0;
+// Fails if the extrapolated value doesn't fit in an i64.
+fn calculate_line_sum(line: &[i64], triangle: &[Vec<i128>]) -> Result<i64, String> {
+    // An empty history (a blank line) has nothing to extrapolate and counts as 0, and
+    // a single value just repeats.
+    match line {
+        [] => return Ok(0),
+        &[value] => return Ok(value),
+        _ => (),
+    }
+
+    let overflow = || format!("extrapolating {line:?} overflows an i64");
+    let mut sum: i128 = 0;
     let row = line.len();
     for (col, &n) in line.iter().enumerate() {
-        sum += triangle[row][col] * n;
+        sum = triangle[row][col]
+            .checked_mul(i128::from(n))
+            .and_then(|term| sum.checked_add(term))
+            .ok_or_else(overflow)?;
+    }
+    let sum = sum * if row.is_multiple_of(2) { 1 } else { -1 };
+    i64::try_from(sum).map_err(|_| overflow())
+}
+
+/// Extrapolates the value that follows a history.
+///
+/// Builds the difference table directly: takes differences until they are all zero, then
+/// the next value is the sum of the last value of every level. An empty history gives 0.
+///
+/// # Panics
+///
+/// If the extrapolated value doesn't fit in an i64.
+pub fn extrapolate_next(seq: &[i64]) -> i64 {
+    difference_table_next(seq).expect("extrapolated value overflows an i64")
+}
+
+// The difference table behind `extrapolate_next`, returning `None` if the value, or any
+// difference on the way, overflows.
+fn difference_table_next(seq: &[i64]) -> Option<i64> {
+    let mut differences: Vec<i128> = seq.iter().map(|&n| i128::from(n)).collect();
+    let mut next: i128 = 0;
+    while differences.iter().any(|&d| d != 0) {
+        next = next.checked_add(differences[differences.len() - 1])?;
+        differences = next_differences(&differences)?;
+    }
+    i64::try_from(next).ok()
+}
+
+// The level below in the difference table, or `None` if a difference overflows an i128,
+// as it can for long histories that swing between extremes.
+fn next_differences(differences: &[i128]) -> Option<Vec<i128>> {
+    differences.windows(2).map(|w| w[1].checked_sub(w[0])).collect()
+}
+
+/// Extrapolates the value that precedes a history.
+///
+/// Like `extrapolate_next`, but each level's first value is subtracted from the level above,
+/// so the first values enter the sum with alternating signs. An empty history gives 0.
+///
+/// # Panics
+///
+/// If the extrapolated value doesn't fit in an i64.
+pub fn extrapolate_prev(seq: &[i64]) -> i64 {
+    difference_table_prev(seq).expect("extrapolated value overflows an i64")
+}
+
+// The difference table behind `extrapolate_prev`, returning `None` on overflow.
+fn difference_table_prev(seq: &[i64]) -> Option<i64> {
+    let mut differences: Vec<i128> = seq.iter().map(|&n| i128::from(n)).collect();
+    let mut previous: i128 = 0;
+    let mut sign = 1;
+    while differences.iter().any(|&d| d != 0) {
+        previous = previous.checked_add(differences[0].checked_mul(sign)?)?;
+        sign = -sign;
+        differences = next_differences(&differences)?;
     }
-    sum * if row % 2 == 0 { 1 } else { -1 }
+    i64::try_from(previous).ok()
 }
 
 #[aoc(day9, part1)]
-pub fn part1(input: &str) -> i64 {
-    let nums = parse_input(input);
+pub fn part1(input: &str) -> Result<i64, String> {
+    let nums = parse_input(input)?;
     let max_len = nums.iter().map(Vec::len).max().unwrap_or(0);
-    let triangle = generate_triangle(max_len);
+    let triangle = generate_triangle(max_len)?;
 
     nums.into_iter()
         .map(|line| calculate_line_sum(&line, &triangle))
-        .sum()
+        .try_fold(0i64, |total, value| total.checked_add(value?).ok_or_else(|| "sum overflows an i64".to_string()))
 }
 
 #[aoc(day9, part2)]
-pub fn part2(input: &str) -> i64 {
-    let nums = parse_input(input);
+pub fn part2(input: &str) -> Result<i64, String> {
+    let nums = parse_input(input)?;
     let max_len = nums.iter().map(Vec::len).max().unwrap_or(0);
-    let triangle = generate_triangle(max_len);
+    let triangle = generate_triangle(max_len)?;
 
     nums.into_iter()
         .map(|mut line| {
             line.reverse(); // Reverse the line to extrapolate in reverse
             calculate_line_sum(&line, &triangle)
         })
-        .sum()
+        .try_fold(0i64, |total, value| total.checked_add(value?).ok_or_else(|| "sum overflows an i64".to_string()))
+}
+
+/// Like `part1`, but extrapolates each history with the plain difference table of
+/// `extrapolate_next` instead of the triangle, to cross-check it.
+pub fn part1_naive(input: &str) -> Result<i64, String> {
+    parse_input(input)?.iter().try_fold(0i64, |total, line| {
+        difference_table_next(line)
+            .and_then(|value| total.checked_add(value))
+            .ok_or_else(|| format!("extrapolating {line:?} overflows an i64"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::rng;
+
+    const SAMPLE: &str = "\
+0 3 6 9 12 15
+1 3 6 10 15 21
+10 13 16 21 30 45
+";
+
+    #[test]
+    fn sample_answers() {
+        assert_eq!(part1(SAMPLE), Ok(114));
+        assert_eq!(part2(SAMPLE), Ok(2));
+    }
+
+    #[test]
+    fn single_sequences_match_the_triangle() {
+        let histories = parse_input(SAMPLE).unwrap();
+        assert_eq!(histories.iter().map(|h| extrapolate_next(h)).collect::<Vec<_>>(), [18, 28, 68]);
+        assert_eq!(histories.iter().map(|h| extrapolate_prev(h)).collect::<Vec<_>>(), [-3, 0, 5]);
+
+        let triangle = generate_triangle(6).unwrap();
+        for mut history in histories {
+            assert_eq!(Ok(extrapolate_next(&history)), calculate_line_sum(&history, &triangle));
+            let prev = extrapolate_prev(&history);
+            history.reverse();
+            assert_eq!(Ok(prev), calculate_line_sum(&history, &triangle));
+        }
+
+        assert_eq!(extrapolate_next(&[]), 0);
+        assert_eq!(extrapolate_next(&[7]), 7);
+        assert_eq!(extrapolate_prev(&[7]), 7);
+    }
+
+    #[test]
+    fn malformed_negative_and_extreme_values() {
+        assert_eq!(part1("1 2 x 4"), Err("invalid value \"x\" on line 1".to_string()));
+        assert_eq!(part2("1 2\n3 4.5"), Err("invalid value \"4.5\" on line 2".to_string()));
+        assert_eq!(part1("-3 -6 -9"), Ok(-12));
+
+        let (max, min) = (i64::MAX, i64::MIN);
+        // Counting up to the maximum overflows forwards but not backwards
+        let line = format!("{} {} {max}", max - 2, max - 1);
+        assert!(part1(&line).is_err());
+        assert_eq!(part2(&line), Ok(max - 3));
+        assert_eq!(part1(&format!("{} {} {}", min + 5, min + 5, min + 5)), Ok(min + 5));
+        assert!(part1(&format!("{min} 0 {min} 0")).is_err());
+        assert!(part1(&format!("{max} {} {max}", -max)).is_err());
+
+        let history = [max - 2, max - 1, max - 2, max - 3];
+        assert_eq!(Ok(extrapolate_prev(&history)), part2(&format!("{} {} {} {}", max - 2, max - 1, max - 2, max - 3)));
+    }
+
+    #[test]
+    fn blank_and_short_histories() {
+        let input = "0 3 6 9 12 15\n\n1 3 6 10 15 21\n   \n10 13 16 21 30 45\n7\n";
+        assert_eq!(part1(input), Ok(114 + 7));
+        assert_eq!(part2(input), Ok(2 + 7));
+        assert_eq!(part1(""), Ok(0));
+        assert_eq!(part2("\n\n"), Ok(0));
+        assert_eq!(part1("5 5"), Ok(5));
+        assert_eq!(part2("1 2"), Ok(0));
+    }
+
+    #[test]
+    fn naive_difference_table_matches_the_triangle() {
+        assert_eq!(part1_naive(SAMPLE), Ok(114));
+
+        let mut seed = 99;
+        for _ in 0..300 {
+            // Several polynomials of degree up to 5, sampled at up to 21 points
+            let mut input = String::new();
+            for _ in 0..rng(&mut seed) % 6 {
+                let coefficients: Vec<i64> = (0..=rng(&mut seed) % 6).map(|_| (rng(&mut seed) % 41) as i64 - 20).collect();
+                let values: Vec<String> = (0..(rng(&mut seed) % 22) as i64)
+                    .map(|x| coefficients.iter().rev().fold(0, |value, &c| value * (x - 5) + c).to_string())
+                    .collect();
+                input += &values.join(" ");
+                input.push('\n');
+            }
+            assert_eq!(part1_naive(&input), part1(&input), "{input}");
+
+            // Arbitrary values, whose differences rarely reach zero before running out
+            let values: Vec<String> =
+                (0..rng(&mut seed) % 21).map(|_| ((rng(&mut seed) % 2001) as i64 - 1000).to_string()).collect();
+            let input = values.join(" ");
+            assert_eq!(part1_naive(&input), part1(&input), "{input}");
+        }
+    }
+
+    #[test]
+    fn long_swinging_histories_overflow_without_panicking() {
+        // The differences double at every level, past an i128 well before they run out
+        let history: Vec<i64> = (0..90u32).map(|i| if i.is_multiple_of(2) { i64::MAX } else { i64::MIN }).collect();
+        assert_eq!(difference_table_next(&history), None);
+        assert_eq!(difference_table_prev(&history), None);
+
+        let input = history.iter().map(i64::to_string).collect::<Vec<_>>().join(" ");
+        assert!(part1(&input).is_err());
+        assert!(part2(&input).is_err());
+        assert!(part1_naive(&input).is_err());
+    }
 }
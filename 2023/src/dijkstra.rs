@@ -0,0 +1,60 @@
+// Shared Dijkstra helpers
+//
+// A monotone bucket queue: a priority queue for small non-negative integer costs where
+// every pushed cost is at least the last popped cost. This holds for Dijkstra's algorithm
+// with non-negative edge weights, and with small weights (e.g. heat loss 1-9) it avoids
+// the log factor and comparisons of a `BinaryHeap`.
+
+/// Priority queue indexed directly by cost, popping items in non-decreasing cost order.
+pub struct BucketQueue<T> {
+    buckets: Vec<Vec<T>>,
+    current: usize,
+    len: usize,
+}
+
+impl<T> BucketQueue<T> {
+    /// Creates an empty queue.
+    pub fn new() -> Self {
+        BucketQueue { buckets: Vec::new(), current: 0, len: 0 }
+    }
+
+    /// Pushes an item with the given cost.
+    ///
+    /// The cost must not be lower than the cost of the last popped item.
+    pub fn push(&mut self, cost: usize, item: T) {
+        debug_assert!(cost >= self.current, "bucket queue costs must be monotone");
+        if cost >= self.buckets.len() {
+            self.buckets.resize_with(cost + 1, Vec::new);
+        }
+        self.buckets[cost].push(item);
+        self.len += 1;
+    }
+
+    /// Removes and returns an item with the lowest cost, along with that cost.
+    pub fn pop(&mut self) -> Option<(usize, T)> {
+        while self.current < self.buckets.len() {
+            if let Some(item) = self.buckets[self.current].pop() {
+                self.len -= 1;
+                return Some((self.current, item));
+            }
+            self.current += 1;
+        }
+        None
+    }
+
+    /// Returns the number of queued items.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if no items are queued.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<T> Default for BucketQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -3,6 +3,14 @@ extern crate aoc_runner;
 #[macro_use]
 extern crate aoc_runner_derive;
 
+pub mod dijkstra;
+pub mod vec3;
+
+#[cfg(test)]
+pub(crate) mod test_util;
+
+pub use vec3::Vector;
+
 pub mod day1;
 pub mod day2;
 pub mod day3;
@@ -0,0 +1,20 @@
+// Helpers shared by the tests of several days, for building reproducible pseudo-random inputs.
+
+/// Advances a xorshift generator and returns its new state. `seed` must not start at 0.
+pub(crate) fn rng(seed: &mut u64) -> u64 {
+    *seed ^= *seed << 13;
+    *seed ^= *seed >> 7;
+    *seed ^= *seed << 17;
+    *seed
+}
+
+/// A `height` x `width` grid with every tile picked uniformly from `tiles`, one line per row.
+/// Repeating a tile in `tiles` makes it more likely.
+pub(crate) fn random_grid(seed: &mut u64, height: usize, width: usize, tiles: &[u8]) -> String {
+    let mut grid = String::with_capacity(height * (width + 1));
+    for _ in 0..height {
+        grid.extend((0..width).map(|_| tiles[rng(seed) as usize % tiles.len()] as char));
+        grid.push('\n');
+    }
+    grid
+}
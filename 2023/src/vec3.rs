@@ -0,0 +1,134 @@
+// Shared 3D vector arithmetic
+//
+// An integer vector with i128 components, wide enough that cross products of puzzle-sized
+// coordinates (around 1e14) and their products with velocities don't overflow.
+
+use num_integer::Integer;
+use std::ops::{Add, Div, Mul, Sub};
+
+/// A 3D vector with integer components.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Vector {
+    pub x: i128,
+    pub y: i128,
+    pub z: i128,
+}
+
+impl Vector {
+    /// Creates a vector from its components.
+    pub fn new(x: i128, y: i128, z: i128) -> Self {
+        Vector { x, y, z }
+    }
+
+    /// Creates a vector from `i64` components.
+    pub fn from_i64(x: i64, y: i64, z: i64) -> Self {
+        Vector {
+            x: x as i128,
+            y: y as i128,
+            z: z as i128,
+        }
+    }
+
+    /// Returns the dot product of two vectors.
+    pub fn dot(self, other: Self) -> i128 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    /// Returns the cross product of two vectors.
+    pub fn cross(self, other: Self) -> Self {
+        Vector {
+            x: self.y * other.z - self.z * other.y,
+            y: self.z * other.x - self.x * other.z,
+            z: self.x * other.y - self.y * other.x,
+        }
+    }
+
+    /// Returns true if every component is zero.
+    pub fn is_zero(self) -> bool {
+        self.x == 0 && self.y == 0 && self.z == 0
+    }
+
+    /// Divides out the GCD of the components, giving the smallest integer vector with the
+    /// same direction. Panics on the zero vector.
+    pub fn gcd(self) -> Self {
+        self / self.x.gcd(&self.y).gcd(&self.z)
+    }
+}
+
+impl Add for Vector {
+    type Output = Vector;
+    fn add(self, other: Vector) -> Vector {
+        Vector {
+            x: self.x + other.x,
+            y: self.y + other.y,
+            z: self.z + other.z,
+        }
+    }
+}
+
+impl Sub for Vector {
+    type Output = Vector;
+    fn sub(self, other: Vector) -> Vector {
+        Vector {
+            x: self.x - other.x,
+            y: self.y - other.y,
+            z: self.z - other.z,
+        }
+    }
+}
+
+impl Mul<i128> for Vector {
+    type Output = Vector;
+    fn mul(self, scalar: i128) -> Vector {
+        Vector {
+            x: self.x * scalar,
+            y: self.y * scalar,
+            z: self.z * scalar,
+        }
+    }
+}
+
+/// Divides each component, truncating toward zero like integer division.
+impl Div<i128> for Vector {
+    type Output = Vector;
+    fn div(self, scalar: i128) -> Vector {
+        Vector {
+            x: self.x / scalar,
+            y: self.y / scalar,
+            z: self.z / scalar,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cross_is_anticommutative_and_perpendicular() {
+        let a = Vector::new(2, 4, -6);
+        let b = Vector::new(1, -3, 5);
+        assert_eq!(a.cross(b), b.cross(a) * -1);
+        assert_eq!(a.cross(b), Vector::new(2, -16, -10));
+        assert_eq!(a.cross(b).dot(a), 0);
+        assert_eq!(a.cross(b).dot(b), 0);
+        assert!(a.cross(a).is_zero());
+    }
+
+    #[test]
+    fn gcd_reduces_to_the_smallest_vector_with_the_same_direction() {
+        assert_eq!(Vector::new(2, 4, -6).gcd(), Vector::new(1, 2, -3));
+        assert_eq!(Vector::new(-9, 0, 12).gcd(), Vector::new(-3, 0, 4));
+        assert_eq!(Vector::new(0, 0, 7).gcd(), Vector::new(0, 0, 1));
+        assert_eq!(Vector::new(5, 7, 11).gcd(), Vector::new(5, 7, 11));
+    }
+
+    #[test]
+    fn arithmetic_round_trips() {
+        let a = Vector::new(2, 4, -6);
+        let b = Vector::from_i64(1, -3, 5);
+        assert_eq!((a + b) - b, a);
+        assert_eq!((a * 3) / 3, a);
+        assert_eq!(Vector::new(-7, 7, 3) / 2, Vector::new(-3, 3, 1));
+    }
+}